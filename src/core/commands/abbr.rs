@@ -0,0 +1,209 @@
+use super::{Command, CommandError};
+use crate::core::config::{Abbreviation, Abbreviations, Position};
+use crate::shell::expand::shell_quote;
+use std::sync::{Arc, Mutex, MutexGuard};
+
+const USAGE: &str = "Usage: abbr [-a] [--position command|anywhere] name expansion... | abbr -e name... | abbr [-s|-l]
+Abbreviations added here last until the shell exits; keep them under [abbreviations] in aorta.toml";
+
+#[derive(Debug, PartialEq, Eq)]
+enum Action<'a> {
+    Add {
+        name: &'a str,
+        expansion: String,
+        position: Position,
+    },
+    Erase(&'a [String]),
+    Show,
+    List,
+}
+
+impl<'a> Action<'a> {
+    fn parse(args: &'a [String]) -> Result<Self, CommandError> {
+        let usage = || CommandError::InvalidArguments(USAGE.to_string());
+        let mut position = Position::Command;
+        let mut rest = args;
+        while let Some((arg, tail)) = rest.split_first() {
+            match arg.as_str() {
+                "-a" | "--add" => {}
+                "-p" | "--position" => {
+                    let (name, tail) = tail.split_first().ok_or_else(usage)?;
+                    position = Position::parse(name).ok_or_else(|| {
+                        CommandError::InvalidArguments(format!(
+                            "abbr: {}: expected command or anywhere",
+                            name
+                        ))
+                    })?;
+                    rest = tail;
+                    continue;
+                }
+                "-e" | "--erase" if !tail.is_empty() => return Ok(Action::Erase(tail)),
+                "-s" | "--show" if tail.is_empty() => return Ok(Action::Show),
+                "-l" | "--list" if tail.is_empty() => return Ok(Action::List),
+                "--" => {
+                    rest = tail;
+                    break;
+                }
+                option if option.starts_with('-') => return Err(usage()),
+                _ => break,
+            }
+            rest = tail;
+        }
+        match rest {
+            [] if args.is_empty() => Ok(Action::Show),
+            [name, expansion @ ..] if !expansion.is_empty() => Ok(Action::Add {
+                name,
+                expansion: expansion.join(" "),
+                position,
+            }),
+            _ => Err(usage()),
+        }
+    }
+}
+
+// `abbr -a -- name 'expansion'`, quoted so the shell reads it back the same
+fn definition(abbreviation: &Abbreviation) -> String {
+    let position = match abbreviation.position {
+        Position::Command => String::new(),
+        position => format!("--position {} ", position),
+    };
+    format!(
+        "abbr -a {}-- {} {}",
+        position,
+        abbreviation.name,
+        shell_quote(&abbreviation.expansion)
+    )
+}
+
+/// `abbr`: fish-style abbreviations, which the line editor expands as
+/// they're typed. Changes last for the session only: the ones to keep
+/// belong in the `[abbreviations]` table of `aorta.toml`, which `abbr -s`
+/// lists alongside.
+#[derive(Clone)]
+pub struct AbbrCommand {
+    abbreviations: Arc<Mutex<Abbreviations>>,
+}
+
+impl AbbrCommand {
+    pub fn new(abbreviations: Arc<Mutex<Abbreviations>>) -> Self {
+        Self { abbreviations }
+    }
+
+    fn abbreviations(&self) -> Result<MutexGuard<'_, Abbreviations>, CommandError> {
+        self.abbreviations.lock().map_err(|e| {
+            CommandError::ExecutionError(format!("Failed to access abbreviations: {}", e))
+        })
+    }
+}
+
+impl Command for AbbrCommand {
+    fn execute(&self, args: &[String]) -> Result<(), CommandError> {
+        let mut abbreviations = self.abbreviations()?;
+        match Action::parse(args)? {
+            Action::Add {
+                name,
+                expansion,
+                position,
+            } => {
+                if name.is_empty() || name.contains(char::is_whitespace) {
+                    return Err(CommandError::InvalidArguments(format!(
+                        "abbr: `{}': invalid abbreviation name",
+                        name
+                    )));
+                }
+                abbreviations.add(Abbreviation::new(name, &expansion).with_position(position));
+            }
+            Action::Erase(names) => {
+                let missing: Vec<&str> = names
+                    .iter()
+                    .filter(|name| !abbreviations.remove(name))
+                    .map(String::as_str)
+                    .collect();
+                if !missing.is_empty() {
+                    return Err(CommandError::InvalidArguments(format!(
+                        "abbr: {}: not found",
+                        missing.join(", ")
+                    )));
+                }
+            }
+            Action::Show => {
+                for abbreviation in abbreviations.iter() {
+                    println!("{}", definition(abbreviation));
+                }
+            }
+            Action::List => {
+                for abbreviation in abbreviations.iter() {
+                    println!("{}", abbreviation.name);
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn args(line: &str) -> Vec<String> {
+        line.split_whitespace().map(String::from).collect()
+    }
+
+    #[test]
+    fn test_parse_actions() {
+        let add = |name, expansion: &str, position| Action::Add {
+            name,
+            expansion: expansion.to_string(),
+            position,
+        };
+        let cases = [
+            ("", Ok(Action::Show)),
+            ("--show", Ok(Action::Show)),
+            ("-l", Ok(Action::List)),
+            (
+                "gco git checkout",
+                Ok(add("gco", "git checkout", Position::Command)),
+            ),
+            ("-a -- -x ls", Ok(add("-x", "ls", Position::Command))),
+            (
+                "--position anywhere L | less",
+                Ok(add("L", "| less", Position::Anywhere)),
+            ),
+            ("gco", Err(())),
+            ("-p sometimes L less", Err(())),
+            ("-e", Err(())),
+            ("-a", Err(())),
+            ("--bogus x y", Err(())),
+        ];
+        for (line, expected) in cases {
+            let args = args(line);
+            assert_eq!(Action::parse(&args).map_err(|_| ()), expected, "{:?}", line);
+        }
+        let erase = args("-e gco L");
+        assert_eq!(Action::parse(&erase).unwrap(), Action::Erase(&erase[1..]));
+    }
+
+    #[test]
+    fn test_abbr_add_show_and_erase() {
+        let abbreviations = Arc::new(Mutex::new(Abbreviations::new()));
+        let cmd = AbbrCommand::new(abbreviations.clone());
+
+        assert!(cmd.execute(&args("-a gco git checkout")).is_ok());
+        assert!(cmd.execute(&args("-p anywhere L | less")).is_ok());
+        assert!(cmd.execute(&[]).is_ok());
+        {
+            let abbreviations = abbreviations.lock().unwrap();
+            let gco = abbreviations.get("gco").unwrap();
+            assert_eq!(definition(gco), "abbr -a -- gco 'git checkout'");
+            let less = abbreviations.get("L").unwrap();
+            assert_eq!(
+                definition(less),
+                "abbr -a --position anywhere -- L '| less'"
+            );
+        }
+
+        assert!(cmd.execute(&args("-e gco")).is_ok());
+        assert!(cmd.execute(&args("-e gco L")).is_err());
+        assert_eq!(abbreviations.lock().unwrap().iter().count(), 0);
+    }
+}
@@ -1,52 +1,138 @@
 use super::{Command, CommandError};
-use std::collections::HashMap;
-use std::sync::{Arc, Mutex};
+use crate::core::config::AliasManager;
+use crate::shell::expand::shell_quote;
+use std::sync::{Arc, Mutex, MutexGuard};
+
+fn lock(aliases: &Mutex<AliasManager>) -> Result<MutexGuard<'_, AliasManager>, CommandError> {
+    aliases
+        .lock()
+        .map_err(|e| CommandError::ExecutionError(format!("Failed to access aliases: {}", e)))
+}
+
+// `alias name='value'`, quoted so the shell reads it back the same
+fn definition(name: &str, value: &str) -> String {
+    format!("alias {}={}", name, shell_quote(value))
+}
+
+// Names the shell can find as the first word of a command
+fn valid_name(name: &str) -> bool {
+    !name.is_empty()
+        && !name
+            .chars()
+            .any(|c| c.is_whitespace() || "=$`'\"\\|&;()<>/".contains(c))
+}
 
 #[derive(Clone)]
 pub struct AliasCommand {
-    aliases: Arc<Mutex<HashMap<String, String>>>,
+    aliases: Arc<Mutex<AliasManager>>,
 }
 
 impl AliasCommand {
-    pub fn new(aliases: Arc<Mutex<HashMap<String, String>>>) -> Self {
+    pub fn new(aliases: Arc<Mutex<AliasManager>>) -> Self {
         Self { aliases }
     }
+
+    fn define(&self, name: &str, value: &str) -> Result<(), CommandError> {
+        if !valid_name(name) {
+            return Err(CommandError::InvalidArguments(format!(
+                "alias: `{}': invalid alias name",
+                name
+            )));
+        }
+        // The value arrives whole when quoted, or with its quotes when
+        // passed as a single word from a config file
+        let value = match value.as_bytes() {
+            [first @ (b'\'' | b'"'), .., last] if first == last => &value[1..value.len() - 1],
+            _ => value,
+        };
+        lock(&self.aliases)?.add(name, value);
+        Ok(())
+    }
+
+    fn print(&self, name: &str) -> Result<(), CommandError> {
+        match lock(&self.aliases)?.get(name) {
+            Some(value) => {
+                println!("{}", definition(name, &value));
+                Ok(())
+            }
+            None => Err(CommandError::InvalidArguments(format!(
+                "alias: {}: not found",
+                name
+            ))),
+        }
+    }
 }
 
 impl Command for AliasCommand {
+    /// `alias [-p]` lists the aliases, `alias name` shows one and
+    /// `alias name=value` defines one; any number can be given.
     fn execute(&self, args: &[String]) -> Result<(), CommandError> {
-        if args.is_empty() {
-            // List all aliases
-            let aliases = self.aliases.lock().map_err(|e| {
-                CommandError::ExecutionError(format!("Failed to access aliases: {}", e))
-            })?;
-
-            for (alias, command) in aliases.iter() {
-                println!("{}='{}'", alias, command);
+        let names = match args.first().map(String::as_str) {
+            Some("-p") => &args[1..],
+            _ => args,
+        };
+        if names.is_empty() {
+            for (name, value) in lock(&self.aliases)?.get_all() {
+                println!("{}", definition(&name, &value));
             }
             return Ok(());
         }
 
-        let alias_str = args.join(" ");
-        if let Some((name, value)) = alias_str.split_once('=') {
-            let name = name.trim().to_string();
-            let value = value
-                .trim()
-                .trim_matches(|c| c == '\'' || c == '"')
-                .to_string();
+        // Carry on past a bad one, as bash does; the last error is returned
+        // for the caller to report, so only the ones before it print here
+        let mut result = Ok(());
+        for arg in names {
+            let outcome = match arg.split_once('=') {
+                Some((name, value)) => self.define(name, value),
+                None => self.print(arg),
+            };
+            if let Err(e) = outcome {
+                if let Err(earlier) = std::mem::replace(&mut result, Err(e)) {
+                    eprintln!("aorta: alias: {}", earlier);
+                }
+            }
+        }
+        result
+    }
+}
+
+#[derive(Clone)]
+pub struct UnaliasCommand {
+    aliases: Arc<Mutex<AliasManager>>,
+}
 
-            let mut aliases = self.aliases.lock().map_err(|e| {
-                CommandError::ExecutionError(format!("Failed to access aliases: {}", e))
-            })?;
+impl UnaliasCommand {
+    pub fn new(aliases: Arc<Mutex<AliasManager>>) -> Self {
+        Self { aliases }
+    }
+}
 
-            aliases.insert(name, value);
-        } else {
+impl Command for UnaliasCommand {
+    /// `unalias name...` removes the named aliases, `unalias -a` all of them.
+    fn execute(&self, args: &[String]) -> Result<(), CommandError> {
+        let mut aliases = lock(&self.aliases)?;
+        if args.first().is_some_and(|arg| arg == "-a") {
+            aliases.clear();
+            return Ok(());
+        }
+        if args.is_empty() {
             return Err(CommandError::InvalidArguments(
-                "Usage: alias name='command'".to_string(),
+                "Usage: unalias [-a] name [name ...]".to_string(),
             ));
         }
-
-        Ok(())
+        let missing: Vec<&str> = args
+            .iter()
+            .filter(|name| !aliases.remove(name))
+            .map(String::as_str)
+            .collect();
+        if missing.is_empty() {
+            Ok(())
+        } else {
+            Err(CommandError::InvalidArguments(format!(
+                "unalias: {}: not found",
+                missing.join(", ")
+            )))
+        }
     }
 }
 
@@ -54,27 +140,66 @@ impl Command for AliasCommand {
 mod tests {
     use super::*;
 
+    fn store() -> Arc<Mutex<AliasManager>> {
+        Arc::new(Mutex::new(AliasManager::new()))
+    }
+
     #[test]
     fn test_alias_add() {
-        let aliases = Arc::new(Mutex::new(HashMap::new()));
-        let cmd = AliasCommand::new(aliases);
+        let aliases = store();
+        let cmd = AliasCommand::new(aliases.clone());
 
         assert!(cmd.execute(&["ll='ls -l'".to_string()]).is_ok());
+        assert!(cmd
+            .execute(&["la=ls -a".to_string(), "g=git".to_string()])
+            .is_ok());
+        let aliases = aliases.lock().unwrap();
+        assert_eq!(aliases.get("ll").unwrap(), "ls -l");
+        assert_eq!(aliases.get("la").unwrap(), "ls -a");
+        assert_eq!(aliases.get("g").unwrap(), "git");
     }
 
     #[test]
     fn test_alias_list() {
-        let aliases = Arc::new(Mutex::new(HashMap::new()));
-        let cmd = AliasCommand::new(aliases);
+        let aliases = store();
+        let cmd = AliasCommand::new(aliases.clone());
 
         assert!(cmd.execute(&[]).is_ok());
+        aliases.lock().unwrap().add("ll", "ls -l");
+        assert!(cmd.execute(&["-p".to_string()]).is_ok());
+        assert!(cmd.execute(&["ll".to_string()]).is_ok());
+        assert!(cmd.execute(&["nope".to_string()]).is_err());
+        assert_eq!(
+            definition("say", "echo 'hi'"),
+            r"alias say='echo '\''hi'\'''"
+        );
     }
 
     #[test]
     fn test_alias_invalid() {
-        let aliases = Arc::new(Mutex::new(HashMap::new()));
-        let cmd = AliasCommand::new(aliases);
+        let aliases = store();
+        let cmd = AliasCommand::new(aliases.clone());
 
         assert!(cmd.execute(&["invalid_format".to_string()]).is_err());
+        assert!(cmd.execute(&["a|b=ls".to_string()]).is_err());
+        assert!(cmd.execute(&["=ls".to_string()]).is_err());
+        assert!(aliases.lock().unwrap().get_all().is_empty());
+    }
+
+    #[test]
+    fn test_unalias() {
+        let aliases = store();
+        for name in ["ll", "la", "g"] {
+            aliases.lock().unwrap().add(name, "ls");
+        }
+        let cmd = UnaliasCommand::new(aliases.clone());
+
+        assert!(cmd.execute(&["ll".to_string()]).is_ok());
+        assert!(aliases.lock().unwrap().get("ll").is_none());
+        assert!(cmd.execute(&["la".to_string(), "ll".to_string()]).is_err());
+        assert!(aliases.lock().unwrap().get("la").is_none());
+        assert!(cmd.execute(&[]).is_err());
+        assert!(cmd.execute(&["-a".to_string()]).is_ok());
+        assert!(aliases.lock().unwrap().get_all().is_empty());
     }
 }
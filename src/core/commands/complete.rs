@@ -0,0 +1,270 @@
+use super::{Command, CommandError};
+use crate::input::{CompletionSpec, CompletionSpecs};
+use std::sync::{Arc, Mutex, MutexGuard};
+
+const USAGE: &str = "Usage: complete [-cdf] [-o nospace|default] [-W words] [-C command] [-X pattern] name... | complete -p [name...] | complete -r [name...]";
+
+#[derive(Debug, PartialEq, Eq)]
+enum Action<'a> {
+    Define(CompletionSpec, &'a [String]),
+    Print(&'a [String]),
+    Remove(&'a [String]),
+}
+
+impl<'a> Action<'a> {
+    fn parse(args: &'a [String]) -> Result<Self, CommandError> {
+        let usage = || CommandError::InvalidArguments(USAGE.to_string());
+        match args.first().map(String::as_str) {
+            None => return Ok(Action::Print(args)),
+            Some("-p") => return Ok(Action::Print(&args[1..])),
+            Some("-r") => return Ok(Action::Remove(&args[1..])),
+            Some(_) => {}
+        }
+        let mut spec = CompletionSpec::default();
+        let mut i = 0;
+        while let Some(option) = args.get(i) {
+            if option == "--" {
+                i += 1;
+                break;
+            }
+            let Some(flags) = option.strip_prefix('-') else {
+                break;
+            };
+            // Short options group as in `-cf`; one taking a value takes the
+            // rest of the group, or the next word
+            for (at, flag) in flags.char_indices() {
+                if !takes_value(flag) {
+                    set_flag(&mut spec, flag)?;
+                    continue;
+                }
+                let value = match &flags[at + flag.len_utf8()..] {
+                    "" => {
+                        i += 1;
+                        args.get(i).ok_or_else(usage)?
+                    }
+                    rest => rest,
+                };
+                set_value(&mut spec, flag, value)?;
+                break;
+            }
+            if flags.is_empty() {
+                return Err(usage());
+            }
+            i += 1;
+        }
+        match &args[i..] {
+            [] => Err(usage()),
+            names => Ok(Action::Define(spec, names)),
+        }
+    }
+}
+
+fn takes_value(flag: char) -> bool {
+    matches!(flag, 'W' | 'C' | 'X' | 'o')
+}
+
+// `-c`, `-d` or `-f`
+fn set_flag(spec: &mut CompletionSpec, flag: char) -> Result<(), CommandError> {
+    match flag {
+        'c' => spec.commands = true,
+        'd' => spec.directories = true,
+        'f' => spec.files = true,
+        _ => return Err(CommandError::InvalidArguments(USAGE.to_string())),
+    }
+    Ok(())
+}
+
+// `-W words`, `-C command`, `-X pattern` or `-o name`
+fn set_value(spec: &mut CompletionSpec, flag: char, value: &str) -> Result<(), CommandError> {
+    match flag {
+        'W' => spec.words = value.split_whitespace().map(String::from).collect(),
+        'C' => spec.command = Some(value.to_string()),
+        'X' => spec.filter = Some(value.to_string()),
+        _ => set_option(spec, value)?,
+    }
+    Ok(())
+}
+
+// `-o name`
+fn set_option(spec: &mut CompletionSpec, name: &str) -> Result<(), CommandError> {
+    match name {
+        "nospace" => spec.nospace = true,
+        "default" => spec.default = true,
+        _ => {
+            return Err(CommandError::InvalidArguments(format!(
+                "complete: {}: invalid option name",
+                name
+            )))
+        }
+    }
+    Ok(())
+}
+
+/// `complete`: sets up how commands' arguments are completed.
+#[derive(Clone)]
+pub struct CompleteCommand {
+    specs: Arc<Mutex<CompletionSpecs>>,
+}
+
+impl CompleteCommand {
+    pub fn new(specs: Arc<Mutex<CompletionSpecs>>) -> Self {
+        Self { specs }
+    }
+
+    fn specs(&self) -> Result<MutexGuard<'_, CompletionSpecs>, CommandError> {
+        self.specs.lock().map_err(|e| {
+            CommandError::ExecutionError(format!("Failed to access completions: {}", e))
+        })
+    }
+}
+
+// `complete options name`, as `complete -p` prints it
+fn definition(name: &str, spec: &CompletionSpec) -> String {
+    let options = spec.to_string();
+    if options.is_empty() {
+        format!("complete {}", name)
+    } else {
+        format!("complete {} {}", options, name)
+    }
+}
+
+fn missing(names: Vec<&str>) -> Result<(), CommandError> {
+    if names.is_empty() {
+        return Ok(());
+    }
+    Err(CommandError::InvalidArguments(format!(
+        "complete: {}: no completion specification",
+        names.join(", ")
+    )))
+}
+
+impl Command for CompleteCommand {
+    fn execute(&self, args: &[String]) -> Result<(), CommandError> {
+        let mut specs = self.specs()?;
+        match Action::parse(args)? {
+            Action::Define(spec, names) => {
+                for name in names {
+                    specs.add(name, spec.clone());
+                }
+                Ok(())
+            }
+            Action::Print([]) => {
+                for (name, spec) in specs.iter() {
+                    println!("{}", definition(name, spec));
+                }
+                Ok(())
+            }
+            Action::Print(names) => {
+                let mut unknown = Vec::new();
+                for name in names {
+                    match specs.get(name) {
+                        Some(spec) => println!("{}", definition(name, spec)),
+                        None => unknown.push(name.as_str()),
+                    }
+                }
+                missing(unknown)
+            }
+            Action::Remove([]) => {
+                specs.clear();
+                Ok(())
+            }
+            Action::Remove(names) => missing(
+                names
+                    .iter()
+                    .filter(|name| !specs.remove(name))
+                    .map(String::as_str)
+                    .collect(),
+            ),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn args(words: &[&str]) -> Vec<String> {
+        words.iter().map(|word| word.to_string()).collect()
+    }
+
+    fn parsed(words: &[&str]) -> Option<(CompletionSpec, Vec<String>)> {
+        match Action::parse(&args(words)) {
+            Ok(Action::Define(spec, names)) => Some((spec, names.to_vec())),
+            _ => None,
+        }
+    }
+
+    #[test]
+    fn test_parse_definitions() {
+        let (spec, names) = parsed(&["-W", "start stop", "svc", "service"]).unwrap();
+        assert_eq!(spec.words, ["start", "stop"]);
+        assert_eq!(names, ["svc", "service"]);
+
+        let (spec, _) = parsed(&["-f", "-X", "!*.rs", "-o", "nospace", "rustc"]).unwrap();
+        assert!(spec.files && spec.nospace && !spec.directories);
+        assert_eq!(spec.filter.as_deref(), Some("!*.rs"));
+
+        let (spec, _) = parsed(&["-c", "-d", "-C", "comp-git", "--", "-x"]).unwrap();
+        assert!(spec.commands && spec.directories);
+        assert_eq!(spec.command.as_deref(), Some("comp-git"));
+
+        for bad in [&["-W"][..], &["-c"], &["-o", "bogus", "x"], &["-Z", "x"]] {
+            assert!(Action::parse(&args(bad)).is_err(), "{:?}", bad);
+        }
+    }
+
+    #[test]
+    fn test_parse_grouped_flags() {
+        let (spec, names) = parsed(&["-cf", "cmd"]).unwrap();
+        assert!(spec.commands && spec.files && !spec.directories);
+        assert_eq!(names, ["cmd"]);
+
+        let (spec, _) = parsed(&["-dX!*.rs", "-onospace", "-fW", "a b", "x"]).unwrap();
+        assert!(spec.directories && spec.files && spec.nospace);
+        assert_eq!(spec.filter.as_deref(), Some("!*.rs"));
+        assert_eq!(spec.words, ["a", "b"]);
+
+        for bad in [&["-cZ", "x"][..], &["-cW"], &["-", "x"]] {
+            assert!(Action::parse(&args(bad)).is_err(), "{:?}", bad);
+        }
+    }
+
+    fn defined() -> (Arc<Mutex<CompletionSpecs>>, CompleteCommand) {
+        let specs = Arc::new(Mutex::new(CompletionSpecs::new()));
+        let cmd = CompleteCommand::new(specs.clone());
+        assert!(cmd.execute(&args(&["-W", "start stop", "svc"])).is_ok());
+        assert!(cmd.execute(&args(&["-d", "pushd", "cd"])).is_ok());
+        (specs, cmd)
+    }
+
+    #[test]
+    fn test_define_and_print() {
+        let (specs, cmd) = defined();
+        assert!(cmd.execute(&[]).is_ok());
+        assert!(cmd.execute(&args(&["-p", "svc"])).is_ok());
+        assert!(cmd.execute(&args(&["-p", "nope"])).is_err());
+        assert_eq!(
+            specs.lock().unwrap().get("svc").unwrap().to_string(),
+            "-W 'start stop'"
+        );
+        assert_eq!(specs.lock().unwrap().get("cd").unwrap().to_string(), "-d");
+
+        assert!(cmd.execute(&args(&["plain"])).is_ok());
+        let specs = specs.lock().unwrap();
+        assert_eq!(
+            definition("plain", specs.get("plain").unwrap()),
+            "complete plain"
+        );
+        assert_eq!(definition("cd", specs.get("cd").unwrap()), "complete -d cd");
+    }
+
+    #[test]
+    fn test_remove() {
+        let (specs, cmd) = defined();
+        assert!(cmd.execute(&args(&["-r", "svc"])).is_ok());
+        assert!(cmd.execute(&args(&["-r", "svc"])).is_err());
+        assert_eq!(specs.lock().unwrap().iter().count(), 2);
+        assert!(cmd.execute(&args(&["-r"])).is_ok());
+        assert_eq!(specs.lock().unwrap().iter().count(), 0);
+    }
+}
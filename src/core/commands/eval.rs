@@ -0,0 +1,45 @@
+use super::{Command, CommandError, CommandExecutor};
+
+#[derive(Clone)]
+pub struct EvalCommand {
+    executor: CommandExecutor,
+}
+
+impl EvalCommand {
+    pub fn new(executor: CommandExecutor) -> Self {
+        Self { executor }
+    }
+}
+
+impl Command for EvalCommand {
+    fn execute(&self, args: &[String]) -> Result<(), CommandError> {
+        let line = args.join(" ");
+        if line.trim().is_empty() {
+            self.executor.set_last_status(0);
+            return Ok(());
+        }
+        self.executor.execute_line(&line)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::env;
+
+    #[test]
+    fn test_eval_reparses_arguments() {
+        let executor = CommandExecutor::new(&crate::flags::Flags::default()).unwrap();
+        let cmd = EvalCommand::new(executor.clone());
+
+        env::set_var("AORTA_EVAL_NAME", "AORTA_EVAL_RESULT");
+        cmd.execute(&[
+            "export".to_string(),
+            "$AORTA_EVAL_NAME=done;".to_string(),
+            "cd /aorta/missing".to_string(),
+        ])
+        .unwrap();
+        assert_eq!(env::var("AORTA_EVAL_RESULT").unwrap(), "done");
+        assert_eq!(executor.last_status(), 1);
+    }
+}
@@ -0,0 +1,121 @@
+use super::{Command, CommandError};
+use crate::process::signal;
+use std::os::unix::process::CommandExt;
+use std::process;
+
+/// Replaces the shell with a program. Redirections on an `exec` line are
+/// applied to the shell itself by the pipeline, so a bare `exec 3>log`
+/// reaches this command with no arguments and has nothing left to do.
+#[derive(Clone, Default)]
+pub struct ExecCommand;
+
+#[derive(Default)]
+struct ExecArgs<'a> {
+    clear_env: bool,
+    login: bool,
+    name: Option<&'a str>,
+    command: &'a [String],
+}
+
+impl ExecCommand {
+    pub fn new() -> Self {
+        Self
+    }
+
+    fn parse_args(args: &[String]) -> Result<ExecArgs<'_>, CommandError> {
+        let mut parsed = ExecArgs::default();
+        let mut index = 0;
+        while let Some(arg) = args.get(index) {
+            match arg.as_str() {
+                "-c" => parsed.clear_env = true,
+                "-l" => parsed.login = true,
+                "-a" => {
+                    index += 1;
+                    let name = args.get(index).ok_or_else(|| {
+                        CommandError::InvalidArguments("exec: -a: option requires an argument".to_string())
+                    })?;
+                    parsed.name = Some(name);
+                }
+                "--" => {
+                    index += 1;
+                    break;
+                }
+                flag if flag.starts_with('-') && flag.len() > 1 => {
+                    return Err(CommandError::InvalidArguments(format!(
+                        "exec: {}: invalid option\nUsage: exec [-cl] [-a name] [command [argument ...]]",
+                        flag
+                    )))
+                }
+                _ => break,
+            }
+            index += 1;
+        }
+        parsed.command = &args[index..];
+        Ok(parsed)
+    }
+}
+
+impl Command for ExecCommand {
+    fn execute(&self, args: &[String]) -> Result<(), CommandError> {
+        let args = Self::parse_args(args)?;
+        let Some(program) = args.command.first() else {
+            return Ok(());
+        };
+
+        let mut command = process::Command::new(program);
+        command.args(&args.command[1..]);
+        if args.clear_env {
+            command.env_clear();
+        }
+
+        // A login program sees its name with a leading dash, like `-bash`
+        let name = args.name.unwrap_or(program);
+        if args.login {
+            command.arg0(format!("-{}", name));
+        } else {
+            command.arg0(name);
+        }
+        signal::prepare_child(&mut command);
+
+        // Only returns if execvp failed
+        let err = command.exec();
+        match err.kind() {
+            std::io::ErrorKind::NotFound => Err(CommandError::NotFound(program.clone())),
+            _ => Err(CommandError::ExecutionError(format!(
+                "{}: {}",
+                program, err
+            ))),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn args(values: &[&str]) -> Vec<String> {
+        values.iter().map(|s| s.to_string()).collect()
+    }
+
+    #[test]
+    fn test_exec_parse_args() {
+        let list = args(&["-c", "-a", "name", "ls", "-l"]);
+        let parsed = ExecCommand::parse_args(&list).unwrap();
+        assert!(parsed.clear_env && !parsed.login);
+        assert_eq!(parsed.name, Some("name"));
+        assert_eq!(parsed.command, &list[3..]);
+
+        assert!(ExecCommand::parse_args(&args(&["-z"])).is_err());
+    }
+
+    #[test]
+    fn test_exec_without_command() {
+        assert!(ExecCommand::new().execute(&[]).is_ok());
+    }
+
+    #[test]
+    fn test_exec_missing_program() {
+        let result = ExecCommand::new().execute(&args(&["/aorta/missing/program"]));
+        assert!(matches!(result, Err(CommandError::NotFound(_))));
+    }
+}
@@ -1,7 +1,9 @@
-use super::{Command, CommandError};
+use super::{Command, CommandError, CommandExecutor};
 
 #[derive(Clone)]
-pub struct ExitCommand;
+pub struct ExitCommand {
+    executor: Option<CommandExecutor>,
+}
 
 impl Default for ExitCommand {
     fn default() -> Self {
@@ -11,13 +13,33 @@ impl Default for ExitCommand {
 
 impl ExitCommand {
     pub fn new() -> Self {
-        Self
+        Self { executor: None }
+    }
+
+    pub fn with_executor(mut self, executor: CommandExecutor) -> Self {
+        self.executor = Some(executor);
+        self
+    }
+
+    #[cfg(test)]
+    pub(super) fn executor(&self) -> Option<&CommandExecutor> {
+        self.executor.as_ref()
     }
 }
 
 impl Command for ExitCommand {
-    fn execute(&self, _args: &[String]) -> Result<(), CommandError> {
-        std::process::exit(0);
+    fn execute(&self, args: &[String]) -> Result<(), CommandError> {
+        let status = match args.first() {
+            Some(code) => code.parse::<i32>().map_err(|_| {
+                CommandError::InvalidArguments(format!("exit: {}: numeric argument required", code))
+            })?,
+            None => self.executor.as_ref().map_or(0, |e| e.last_status()),
+        };
+
+        if let Some(executor) = &self.executor {
+            executor.run_exit_trap();
+        }
+        std::process::exit(status & 0xff);
     }
 }
 
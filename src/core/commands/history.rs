@@ -1,6 +1,12 @@
 use super::{Command, CommandError};
-use crate::input::history::{History, HistoryEntry, HistorySearchMode};
+use crate::input::history::{
+    parse_duration, parse_time, relative_time, stats_to_json, to_bash, to_json, ForeignHistory,
+    History, HistoryEntry, HistoryStats, SearchFilter, TextMatch,
+};
+use crate::sys::format_time;
+use regex::Regex;
 use std::sync::{Arc, Mutex};
+use std::time::{SystemTime, UNIX_EPOCH};
 
 #[derive(Clone)]
 pub struct HistoryCommand {
@@ -12,70 +18,160 @@ impl HistoryCommand {
         Self { history }
     }
 
-    fn show_recent(&self, count: usize) -> Result<(), CommandError> {
+    // history [--relative] [N]
+    fn show_recent(&self, args: &[String]) -> Result<(), CommandError> {
+        let mut count = 10;
+        let mut relative = false;
+        for arg in args {
+            match arg.as_str() {
+                "-r" | "--relative" => relative = true,
+                n => {
+                    count = n.parse().map_err(|_| {
+                        CommandError::InvalidArguments(
+                            "Usage: history [--relative] [N]".to_string(),
+                        )
+                    })?
+                }
+            }
+        }
         let history = self
             .history
             .lock()
             .map_err(|_| CommandError::ExecutionError("Failed to lock history".to_string()))?;
 
+        let times = TimeStyle::new(relative);
         for entry in history.get_recent(count) {
-            println!("{}", self.format_entry(entry));
+            println!("{}", self.format_entry(entry, &times));
         }
         Ok(())
     }
 
+    // history search [--prefix|--contains|--regex] [--failed|--succeeded]
+    //   [--exit N] [--since T] [--until T] [--cwd DIR] [--min-duration D]
+    //   [--last N] [--format full|short|json] [query]
     fn search(&self, args: &[String]) -> Result<(), CommandError> {
-        let mode = match args.first().map(|s| s.as_str()) {
-            Some("--prefix") => HistorySearchMode::Prefix,
-            Some("--contains") => HistorySearchMode::Contains,
-            Some("--last") => {
-                let n = args.get(1).and_then(|s| s.parse().ok()).unwrap_or(10);
-                HistorySearchMode::LastN(n)
-            }
-            _ => HistorySearchMode::Contains,
-        };
-
-        let query = args.last().map(String::as_str).unwrap_or("");
-
+        let search = SearchArgs::parse(args)?;
         let history = self
             .history
             .lock()
             .map_err(|_| CommandError::ExecutionError("Failed to lock history".to_string()))?;
 
-        for entry in history.search(mode, query) {
-            println!("{}", self.format_entry(entry));
+        let mut found = history.find(search.text.as_ref(), &search.filter);
+        if let Some(last) = search.last {
+            found.drain(..found.len().saturating_sub(last));
+        }
+        let times = TimeStyle::new(search.relative);
+        match search.format {
+            SearchFormat::Full => {
+                for (index, entry) in found {
+                    println!("{:>5}  {}", index, self.format_entry(entry, &times));
+                }
+            }
+            SearchFormat::Short => {
+                for (index, entry) in found {
+                    if let HistoryEntry::Command { command, .. } = entry {
+                        println!("{:>5}  {}", index, command);
+                    }
+                }
+            }
+            SearchFormat::Json => print!("{}", to_json(found.into_iter().map(|(_, entry)| entry))),
         }
         Ok(())
     }
 
-    fn show_statistics(&self) -> Result<(), CommandError> {
+    // history stats [--json] [--since T] [--until T] [--cwd DIR] and the
+    // other search filters
+    fn show_statistics(&self, args: &[String]) -> Result<(), CommandError> {
+        let search = SearchArgs::parse(args)?;
+        if search.text.is_some() || search.last.is_some() {
+            return Err(CommandError::InvalidArguments(
+                "Usage: history stats [--json] [--since T] [--until T] [--cwd DIR]".to_string(),
+            ));
+        }
         let history = self
             .history
             .lock()
             .map_err(|_| CommandError::ExecutionError("Failed to lock history".to_string()))?;
 
-        let stats = history.calculate_stats();
-        println!("History Statistics:");
-        println!("Total commands: {}", stats.total_commands);
-        println!("Unique commands: {}", stats.unique_commands);
-        println!("Failed commands: {}", stats.failed_commands);
-        println!("Average duration: {}ms", stats.average_duration);
-        println!("\nMost used commands:");
-        for (cmd, count) in stats.most_used.iter().take(5) {
-            println!("  {} ({}x)", cmd, count);
+        let stats = history.calculate_stats(&search.filter);
+        match search.format {
+            SearchFormat::Json => print!("{}", stats_to_json(&stats)),
+            SearchFormat::Full | SearchFormat::Short => print_stats(&stats),
         }
         Ok(())
     }
 
-    fn format_entry(&self, entry: &HistoryEntry) -> String {
+    // history import --from bash|zsh|fish [file]
+    fn import(&self, args: &[String]) -> Result<(), CommandError> {
+        let (shell, path) = match args {
+            [flag, shell, rest @ ..] if flag == "--from" && rest.len() <= 1 => {
+                (shell, rest.first())
+            }
+            _ => {
+                return Err(CommandError::InvalidArguments(
+                    "Usage: history import --from bash|zsh|fish [file]".to_string(),
+                ))
+            }
+        };
+        let format = ForeignHistory::from_name(shell).ok_or_else(|| {
+            CommandError::InvalidArguments(format!("Can't import history from {}", shell))
+        })?;
+        let path = path
+            .map(Into::into)
+            .or_else(|| format.default_path())
+            .ok_or_else(|| CommandError::InvalidArguments("History file required".to_string()))?;
+        let contents = std::fs::read(&path).map_err(CommandError::IoError)?;
+
+        let mut history = self
+            .history
+            .lock()
+            .map_err(|_| CommandError::ExecutionError("Failed to lock history".to_string()))?;
+        let added = history
+            .import(format.parse(&contents))
+            .map_err(CommandError::HistoryError)?;
+        println!("Imported {} commands from {}", added, path.display());
+        Ok(())
+    }
+
+    // history export --format json|bash
+    fn export(&self, args: &[String]) -> Result<(), CommandError> {
+        let format = match args {
+            [flag, format] if flag == "--format" => format.as_str(),
+            _ => {
+                return Err(CommandError::InvalidArguments(
+                    "Usage: history export --format json|bash".to_string(),
+                ))
+            }
+        };
+        let history = self
+            .history
+            .lock()
+            .map_err(|_| CommandError::ExecutionError("Failed to lock history".to_string()))?;
+        let entries = history.get_recent(usize::MAX).into_iter().rev();
+        let exported = match format {
+            "json" => to_json(entries),
+            "bash" => to_bash(entries),
+            _ => {
+                return Err(CommandError::InvalidArguments(format!(
+                    "Can't export history as {}",
+                    format
+                )))
+            }
+        };
+        print!("{}", exported);
+        Ok(())
+    }
+
+    fn format_entry(&self, entry: &HistoryEntry, times: &TimeStyle) -> String {
         match entry {
             HistoryEntry::Command {
                 command,
                 timestamp,
                 exit_code,
                 duration,
+                ..
             } => {
-                let time = format_timestamp(*timestamp);
+                let time = times.format(*timestamp);
                 format!(
                     "{} [{}] ({}) {} [{}ms]",
                     time,
@@ -89,7 +185,7 @@ impl HistoryCommand {
                 description,
                 timestamp,
             } => {
-                let time = format_timestamp(*timestamp);
+                let time = times.format(*timestamp);
                 format!("{} [EVENT] {}", time, description)
             }
         }
@@ -115,13 +211,16 @@ impl HistoryCommand {
 
 impl Command for HistoryCommand {
     fn execute(&self, args: &[String]) -> Result<(), CommandError> {
-        if args.is_empty() {
-            return self.show_recent(10);
+        let listing = args.first().is_none_or(|arg| {
+            arg == "-r" || arg == "--relative" || arg.bytes().all(|b| b.is_ascii_digit())
+        });
+        if listing {
+            return self.show_recent(args);
         }
 
         match args[0].as_str() {
             "search" => self.search(&args[1..]),
-            "stats" => self.show_statistics(),
+            "stats" => self.show_statistics(&args[1..]),
             "clear" => {
                 let mut history = self.history.lock().map_err(|_| {
                     CommandError::ExecutionError("Failed to lock history".to_string())
@@ -131,6 +230,18 @@ impl Command for HistoryCommand {
                     .map_err(|e| CommandError::ExecutionError(e.to_string()))
             }
             "delete" => self.delete_entry(&args[1..]),
+            "import" => self.import(&args[1..]),
+            "export" => self.export(&args[1..]),
+            // Read in what other sessions added to the file, as in bash
+            "-n" => {
+                let mut history = self.history.lock().map_err(|_| {
+                    CommandError::ExecutionError("Failed to lock history".to_string())
+                })?;
+                history
+                    .reload()
+                    .map(|_| ())
+                    .map_err(CommandError::HistoryError)
+            }
             _ => Err(CommandError::InvalidArguments(
                 "Unknown history subcommand".to_string(),
             )),
@@ -138,9 +249,248 @@ impl Command for HistoryCommand {
     }
 }
 
-fn format_timestamp(timestamp: u64) -> String {
-    let secs = timestamp % 60;
-    let mins = (timestamp / 60) % 60;
-    let hours = (timestamp / 3600) % 24;
-    format!("{:02}:{:02}:{:02}", hours, mins, secs)
+// How many days `history stats` charts
+const CHARTED_DAYS: usize = 14;
+
+fn print_stats(stats: &HistoryStats) {
+    println!("History Statistics:");
+    println!("Total commands: {}", stats.total_commands);
+    println!("Unique commands: {}", stats.unique_commands);
+    println!("Failed commands: {}", stats.failed_commands);
+    println!("Average duration: {}ms", stats.average_duration);
+    println!(
+        "Active days: {} (current streak {}, longest {})",
+        stats.active_days, stats.current_streak, stats.longest_streak
+    );
+    println!("\nMost used commands:");
+    for (cmd, count) in stats.most_used.iter().take(5) {
+        println!("  {} ({}x)", cmd, count);
+    }
+    println!("\nSlowest commands:");
+    for (cmd, duration) in stats.slowest.iter().take(5) {
+        println!("  {} ({}ms)", cmd, duration);
+    }
+    println!("\nHighest failure rates:");
+    for (cmd, runs, failures) in stats.failure_rates.iter().take(5) {
+        println!("  {} ({}/{} failed)", cmd, failures, runs);
+    }
+    println!("\nBusiest directories:");
+    for (dir, commands) in &stats.by_directory {
+        println!("  {}", dir);
+        for (cmd, count) in commands {
+            println!("    {} ({}x)", cmd, count);
+        }
+    }
+
+    println!("\nActivity by hour:");
+    let hours: Vec<_> = (0..24)
+        .map(|hour| (format!("{:02}", hour), stats.by_hour[hour]))
+        .collect();
+    print_histogram(&hours);
+    println!("\nActivity by day:");
+    let days = &stats.by_day[stats.by_day.len().saturating_sub(CHARTED_DAYS)..];
+    print_histogram(days);
+}
+
+fn print_histogram(rows: &[(String, usize)]) {
+    const WIDTH: usize = 40;
+    let max = rows
+        .iter()
+        .map(|(_, count)| *count)
+        .max()
+        .unwrap_or(0)
+        .max(1);
+    for (label, count) in rows {
+        let bar = "█".repeat((count * WIDTH).div_ceil(max));
+        println!("  {} {} {}", label, bar, count);
+    }
+}
+
+enum SearchFormat {
+    // The index `history delete` takes, then the time, status and command
+    Full,
+    // The index and the command
+    Short,
+    Json,
+}
+
+// What `history search` was asked for
+struct SearchArgs {
+    text: Option<TextMatch>,
+    filter: SearchFilter,
+    last: Option<usize>,
+    format: SearchFormat,
+    relative: bool,
+}
+
+impl SearchArgs {
+    fn parse(args: &[String]) -> Result<Self, CommandError> {
+        let now = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_secs();
+        let mut search = SearchArgs {
+            text: None,
+            filter: SearchFilter::default(),
+            last: None,
+            format: SearchFormat::Full,
+            relative: false,
+        };
+        let mut mode = "--contains";
+        let mut query = None;
+        let mut args = args.iter();
+        while let Some(arg) = args.next() {
+            let flag = arg.as_str();
+            let mut value = || args.next().map(String::as_str);
+            match flag {
+                "--prefix" | "--contains" | "--regex" => mode = flag,
+                "--failed" => search.filter.succeeded = Some(false),
+                "--succeeded" => search.filter.succeeded = Some(true),
+                "--exit" => {
+                    search.filter.exit_code = Some(parsed(flag, value(), |v| v.parse().ok())?)
+                }
+                "--since" => {
+                    search.filter.since = Some(parsed(flag, value(), |v| parse_time(v, now))?)
+                }
+                "--until" => {
+                    search.filter.until = Some(parsed(flag, value(), |v| parse_time(v, now))?)
+                }
+                "--cwd" => search.filter.cwd = Some(parsed(flag, value(), resolve_dir)?),
+                "--min-duration" => {
+                    search.filter.min_duration = Some(parsed(flag, value(), parse_duration)?)
+                }
+                "--last" => search.last = Some(parsed(flag, value(), |v| v.parse().ok())?),
+                "--format" => search.format = parsed(flag, value(), SearchFormat::from_name)?,
+                "--json" => search.format = SearchFormat::Json,
+                "-r" | "--relative" => search.relative = true,
+                _ if flag.starts_with("--") => {
+                    return Err(CommandError::InvalidArguments(format!(
+                        "Unknown search option {}",
+                        flag
+                    )))
+                }
+                _ => query = Some(flag),
+            }
+        }
+        search.text = query.map(|query| text_match(mode, query)).transpose()?;
+        Ok(search)
+    }
+}
+
+impl SearchFormat {
+    fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "full" => Some(Self::Full),
+            "short" => Some(Self::Short),
+            "json" => Some(Self::Json),
+            _ => None,
+        }
+    }
+}
+
+// The value after `flag`, parsed
+fn parsed<T>(
+    flag: &str,
+    value: Option<&str>,
+    parse: impl FnOnce(&str) -> Option<T>,
+) -> Result<T, CommandError> {
+    let value =
+        value.ok_or_else(|| CommandError::InvalidArguments(format!("{} needs a value", flag)))?;
+    parse(value)
+        .ok_or_else(|| CommandError::InvalidArguments(format!("Invalid {} value: {}", flag, value)))
+}
+
+// Commands record the directory they ran in with symlinks resolved
+fn resolve_dir(dir: &str) -> Option<String> {
+    let resolved = std::fs::canonicalize(dir).unwrap_or_else(|_| dir.into());
+    Some(resolved.to_string_lossy().into_owned())
+}
+
+fn text_match(mode: &str, query: &str) -> Result<TextMatch, CommandError> {
+    Ok(match mode {
+        "--prefix" => TextMatch::Prefix(query.to_string()),
+        "--regex" => TextMatch::Regex(
+            Regex::new(query)
+                .map_err(|e| CommandError::InvalidArguments(format!("Invalid regex: {}", e)))?,
+        ),
+        _ => TextMatch::Contains(query.to_string()),
+    })
+}
+
+// Without HISTTIMEFORMAT, entries show the local date and time
+const DEFAULT_TIME_FORMAT: &str = "%Y-%m-%d %H:%M:%S";
+
+// How `history` shows when entries ran
+#[derive(Debug, PartialEq, Eq)]
+enum TimeStyle {
+    // A strftime format
+    Local(String),
+    // How long ago, as of the given time
+    Relative(u64),
+}
+
+impl TimeStyle {
+    fn new(relative: bool) -> Self {
+        let now = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_secs();
+        Self::choose(relative, now, std::env::var("HISTTIMEFORMAT").ok())
+    }
+
+    // `--relative` wins over a HISTTIMEFORMAT, and a blank one is unset
+    fn choose(relative: bool, now: u64, format: Option<String>) -> Self {
+        if relative {
+            return TimeStyle::Relative(now);
+        }
+        let format = format.filter(|format| !format.trim().is_empty());
+        TimeStyle::Local(format.unwrap_or_else(|| DEFAULT_TIME_FORMAT.to_string()))
+    }
+
+    fn format(&self, timestamp: u64) -> String {
+        match self {
+            // bash puts the command right after the time, so formats often
+            // end in a space; the entry has its own
+            TimeStyle::Local(format) => format_time(timestamp, format).trim_end().to_string(),
+            TimeStyle::Relative(now) => relative_time(timestamp, *now),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::input::history::parse_time;
+
+    #[test]
+    fn test_time_style_choice() {
+        let local = |format: &str| TimeStyle::Local(format.to_string());
+        let cases = [
+            (false, None, local(DEFAULT_TIME_FORMAT)),
+            (false, Some(""), local(DEFAULT_TIME_FORMAT)),
+            (false, Some("  "), local(DEFAULT_TIME_FORMAT)),
+            (false, Some("%T "), local("%T ")),
+            (true, Some("%T "), TimeStyle::Relative(100)),
+            (true, None, TimeStyle::Relative(100)),
+        ];
+        for (relative, format, expected) in cases {
+            let style = TimeStyle::choose(relative, 100, format.map(String::from));
+            assert_eq!(style, expected, "{:?}", format);
+        }
+    }
+
+    #[test]
+    fn test_time_style_format() {
+        let timestamp = parse_time("2023-11-14 01:02:03", 0).unwrap();
+        let style = |format: &str| TimeStyle::Local(format.to_string());
+        assert_eq!(
+            style(DEFAULT_TIME_FORMAT).format(timestamp),
+            "2023-11-14 01:02:03"
+        );
+        assert_eq!(style("%d/%m %H:%M  ").format(timestamp), "14/11 01:02");
+        assert_eq!(
+            TimeStyle::Relative(timestamp + 7200).format(timestamp),
+            "2h ago"
+        );
+    }
 }
@@ -0,0 +1,164 @@
+use super::{Command, CommandError};
+use crate::process::{signal, Jobs};
+use libc::c_int;
+use std::sync::{Arc, Mutex};
+
+#[derive(Clone)]
+pub struct KillCommand {
+    jobs: Arc<Mutex<Jobs>>,
+}
+
+impl KillCommand {
+    pub fn new(jobs: Arc<Mutex<Jobs>>) -> Self {
+        Self { jobs }
+    }
+
+    fn parse_signal(spec: &str) -> Result<c_int, CommandError> {
+        signal::from_name(spec).ok_or_else(|| {
+            CommandError::InvalidArguments(format!("kill: {}: invalid signal specification", spec))
+        })
+    }
+
+    // `kill -l` lists signals; with arguments it converts between names and
+    // numbers, accepting exit statuses of signalled processes like 130.
+    fn list(specs: &[String]) -> Result<(), CommandError> {
+        if specs.is_empty() {
+            for (name, number) in signal::SIGNALS {
+                println!("{:2}) SIG{}", number, name);
+            }
+            return Ok(());
+        }
+
+        for spec in specs {
+            match spec.parse::<c_int>() {
+                Ok(number) => {
+                    let number = if number > 128 { number - 128 } else { number };
+                    let name = signal::name(number).ok_or_else(|| {
+                        CommandError::InvalidArguments(format!(
+                            "kill: {}: invalid signal specification",
+                            spec
+                        ))
+                    })?;
+                    println!("{}", name);
+                }
+                Err(_) => println!("{}", Self::parse_signal(spec)?),
+            }
+        }
+        Ok(())
+    }
+
+    // Splits off the signal option; the rest are pids and job specs
+    fn parse_args(args: &[String]) -> Result<(c_int, &[String]), CommandError> {
+        match args.first().map(String::as_str) {
+            Some("-s") | Some("-n") => {
+                let spec = args.get(1).ok_or_else(|| {
+                    CommandError::InvalidArguments("kill: option requires an argument".to_string())
+                })?;
+                Ok((Self::parse_signal(spec)?, &args[2..]))
+            }
+            Some("--") => Ok((libc::SIGTERM, &args[1..])),
+            Some(flag) if flag.starts_with('-') && flag.len() > 1 => {
+                Ok((Self::parse_signal(&flag[1..])?, &args[1..]))
+            }
+            _ => Ok((libc::SIGTERM, args)),
+        }
+    }
+
+    // A job is signalled as a whole through its process group
+    fn resolve(&self, target: &str) -> Result<libc::pid_t, String> {
+        if target.starts_with('%') {
+            let jobs = self
+                .jobs
+                .lock()
+                .map_err(|_| "Failed to lock jobs".to_string())?;
+            return jobs
+                .find(target)
+                .map(|job| -job.pgid)
+                .ok_or_else(|| format!("kill: {}: no such job", target));
+        }
+        target
+            .parse()
+            .map_err(|_| format!("kill: {}: arguments must be process or job IDs", target))
+    }
+}
+
+impl Command for KillCommand {
+    fn execute(&self, args: &[String]) -> Result<(), CommandError> {
+        if args.first().map(String::as_str) == Some("-l") {
+            return Self::list(&args[1..]);
+        }
+
+        let (sig, targets) = Self::parse_args(args)?;
+        if targets.is_empty() {
+            return Err(CommandError::InvalidArguments(
+                "Usage: kill [-s sigspec | -n signum | -sigspec] pid | jobspec ... or kill -l [sigspec]"
+                    .to_string(),
+            ));
+        }
+
+        // Every target is tried; the failures are reported together
+        let errors: Vec<String> = targets
+            .iter()
+            .filter_map(|target| {
+                let pid = match self.resolve(target) {
+                    Ok(pid) => pid,
+                    Err(msg) => return Some(msg),
+                };
+                if unsafe { libc::kill(pid, sig) } != 0 {
+                    let err = std::io::Error::last_os_error();
+                    return Some(format!("kill: ({}) - {}", target, err));
+                }
+                None
+            })
+            .collect();
+
+        if errors.is_empty() {
+            Ok(())
+        } else {
+            Err(CommandError::ExecutionError(errors.join("\n")))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::os::unix::process::CommandExt;
+
+    fn args(values: &[&str]) -> Vec<String> {
+        values.iter().map(|s| s.to_string()).collect()
+    }
+
+    #[test]
+    fn test_parse_signal_forms() {
+        let list = args(&["-s", "HUP", "1"]);
+        assert_eq!(KillCommand::parse_args(&list).unwrap().0, libc::SIGHUP);
+        let list = args(&["-9", "1"]);
+        assert_eq!(KillCommand::parse_args(&list).unwrap().0, libc::SIGKILL);
+        let list = args(&["-SIGUSR1", "1"]);
+        assert_eq!(KillCommand::parse_args(&list).unwrap().0, libc::SIGUSR1);
+        let list = args(&["1"]);
+        assert_eq!(KillCommand::parse_args(&list).unwrap().0, libc::SIGTERM);
+        assert!(KillCommand::parse_args(&args(&["-BOGUS", "1"])).is_err());
+    }
+
+    #[test]
+    fn test_kill_job() {
+        let jobs = Arc::new(Mutex::new(Jobs::new()));
+        let cmd = KillCommand::new(jobs.clone());
+
+        let mut child = std::process::Command::new("/bin/sleep")
+            .arg("30")
+            .process_group(0)
+            .spawn()
+            .unwrap();
+        jobs.lock()
+            .unwrap()
+            .add(vec![child.id() as i32], "sleep 30");
+
+        cmd.execute(&args(&["-TERM", "%sleep"])).unwrap();
+        let status = child.wait().unwrap();
+        assert_eq!(crate::process::exit_code(status), 128 + libc::SIGTERM);
+        assert!(cmd.execute(&args(&["%2"])).is_err());
+    }
+}
@@ -1,25 +1,52 @@
 use std::collections::BTreeMap;
-use std::collections::HashMap;
-use std::sync::{Arc, Mutex};
+use std::sync::atomic::{AtomicBool, AtomicI32, Ordering};
+use std::sync::{Arc, Mutex, OnceLock};
 
+mod abbr;
 mod alias;
 mod cd;
+mod complete;
+mod eval;
+mod exec;
 mod exit;
 mod export;
 mod history;
+mod kill;
+mod set;
+mod shopt;
 mod source;
-
-pub use alias::AliasCommand;
+mod times;
+mod trap;
+mod ulimit;
+mod umask;
+mod wait;
+
+pub use abbr::AbbrCommand;
+pub use alias::{AliasCommand, UnaliasCommand};
 pub use cd::CdCommand;
+pub use complete::CompleteCommand;
+pub use eval::EvalCommand;
+pub use exec::ExecCommand;
 pub use exit::ExitCommand;
 pub use export::ExportCommand;
 pub use history::HistoryCommand;
+pub use kill::KillCommand;
+pub use set::SetCommand;
+pub use shopt::ShoptCommand;
 pub use source::SourceCommand;
+pub use times::TimesCommand;
+pub use trap::{TrapCommand, TrapCondition, Traps};
+pub use ulimit::UlimitCommand;
+pub use umask::UmaskCommand;
+pub use wait::WaitCommand;
 
+use crate::core::config::{Abbreviations, AliasManager};
 use crate::core::env::EnvVarManager;
+use crate::core::options::ShellOptions;
 use crate::input::history::HistoryError;
-use crate::input::History;
-use crate::process::{ProcessError, ProcessExecutor};
+use crate::input::{CompletionSpecs, History};
+use crate::process::{signal, Job, Jobs, ProcessError, ProcessExecutor};
+use crate::shell::pipeline::{Pipeline, PipelineError};
 
 #[derive(Debug)]
 pub enum CommandError {
@@ -29,6 +56,8 @@ pub enum CommandError {
     IoError(std::io::Error),
     ProcessError(ProcessError),
     HistoryError(HistoryError),
+    // The shell should terminate with this status (errexit in a sourced file)
+    Exit(i32),
 }
 
 impl std::fmt::Display for CommandError {
@@ -40,6 +69,7 @@ impl std::fmt::Display for CommandError {
             CommandError::IoError(err) => write!(f, "IO error: {}", err),
             CommandError::ProcessError(err) => write!(f, "Process error: {}", err),
             CommandError::HistoryError(err) => write!(f, "History error: {}", err),
+            CommandError::Exit(code) => write!(f, "exit {}", code),
         }
     }
 }
@@ -66,8 +96,32 @@ enum CommandType {
     Source(SourceCommand),
     Exit(ExitCommand),
     Alias(AliasCommand),
+    Unalias(UnaliasCommand),
+    Abbr(AbbrCommand),
+    Complete(CompleteCommand),
     History(HistoryCommand),
     Export(ExportCommand),
+    Trap(TrapCommand),
+    Set(SetCommand),
+    Shopt(ShoptCommand),
+    Eval(EvalCommand),
+    Exec(ExecCommand),
+    Kill(KillCommand),
+    Wait(WaitCommand),
+    Ulimit(UlimitCommand),
+    Umask(UmaskCommand),
+    Times(TimesCommand),
+}
+
+impl CommandType {
+    // Builtins that run other commands or report a child's status set
+    // `last_status` themselves
+    fn sets_status(&self) -> bool {
+        matches!(
+            self,
+            CommandType::Source(_) | CommandType::Eval(_) | CommandType::Wait(_)
+        )
+    }
 }
 
 impl Command for CommandType {
@@ -77,29 +131,47 @@ impl Command for CommandType {
             CommandType::Source(cmd) => cmd.execute(args),
             CommandType::Exit(cmd) => cmd.execute(args),
             CommandType::Alias(cmd) => cmd.execute(args),
+            CommandType::Unalias(cmd) => cmd.execute(args),
+            CommandType::Abbr(cmd) => cmd.execute(args),
+            CommandType::Complete(cmd) => cmd.execute(args),
             CommandType::History(cmd) => cmd.execute(args),
             CommandType::Export(cmd) => cmd.execute(args),
+            CommandType::Trap(cmd) => cmd.execute(args),
+            CommandType::Set(cmd) => cmd.execute(args),
+            CommandType::Shopt(cmd) => cmd.execute(args),
+            CommandType::Eval(cmd) => cmd.execute(args),
+            CommandType::Exec(cmd) => cmd.execute(args),
+            CommandType::Kill(cmd) => cmd.execute(args),
+            CommandType::Wait(cmd) => cmd.execute(args),
+            CommandType::Ulimit(cmd) => cmd.execute(args),
+            CommandType::Umask(cmd) => cmd.execute(args),
+            CommandType::Times(cmd) => cmd.execute(args),
         }
     }
 }
 
 #[derive(Clone)]
 pub struct CommandExecutor {
-    commands: BTreeMap<String, CommandType>,
+    // Filled once every builtin exists; shared so that the copies `source`,
+    // `eval`, `exit` and `wait` hold can run builtins registered after them
+    commands: Arc<OnceLock<BTreeMap<String, CommandType>>>,
     process_executor: ProcessExecutor,
     env_vars: Arc<Mutex<EnvVarManager>>,
+    traps: Arc<Mutex<Traps>>,
+    options: Arc<Mutex<ShellOptions>>,
+    jobs: Arc<Mutex<Jobs>>,
+    last_status: Arc<AtomicI32>,
+    in_trap: Arc<AtomicBool>,
+    history: Arc<Mutex<History>>,
+    aliases: Arc<Mutex<AliasManager>>,
+    abbreviations: Arc<Mutex<Abbreviations>>,
+    completions: Arc<Mutex<CompletionSpecs>>,
+    // Reading commands from a terminal rather than a script
+    interactive: bool,
 }
 
 impl CommandExecutor {
     pub fn new(flags: &crate::flags::Flags) -> Result<Self, CommandError> {
-        let mut executor = Self {
-            commands: BTreeMap::new(),
-            process_executor: ProcessExecutor::new(flags)?,
-            env_vars: Arc::new(Mutex::new(EnvVarManager::new().map_err(|e| {
-                CommandError::ExecutionError(format!("Failed to create env manager: {}", e))
-            })?)),
-        };
-
         let history_path = dirs::home_dir()
             .ok_or_else(|| {
                 CommandError::IoError(std::io::Error::new(
@@ -109,59 +181,253 @@ impl CommandExecutor {
             })?
             .join(".aorta_history");
 
-        // Create History instance first
+        // The default history until the settings say otherwise; the shell
+        // and the `history` builtin share it
         let history_instance = History::new(history_path, 1000).map_err(|e| {
             CommandError::ExecutionError(format!("Failed to create history: {:#?}", e))
         })?;
-
-        // Then wrap it in Arc<Mutex>
         let history = Arc::new(Mutex::new(history_instance));
-        let aliases = Arc::new(Mutex::new(HashMap::new()));
+
+        let executor = Self {
+            commands: Arc::default(),
+            process_executor: ProcessExecutor::new(flags)?,
+            env_vars: Arc::new(Mutex::new(EnvVarManager::new().map_err(|e| {
+                CommandError::ExecutionError(format!("Failed to create env manager: {}", e))
+            })?)),
+            traps: Arc::new(Mutex::new(Traps::new())),
+            options: Arc::new(Mutex::new(ShellOptions::new())),
+            jobs: Arc::new(Mutex::new(Jobs::new())),
+            last_status: Arc::new(AtomicI32::new(0)),
+            in_trap: Arc::new(AtomicBool::new(false)),
+            history: Arc::clone(&history),
+            aliases: Arc::new(Mutex::new(AliasManager::new())),
+            abbreviations: Arc::new(Mutex::new(Abbreviations::new())),
+            completions: Arc::new(Mutex::new(CompletionSpecs::new())),
+            interactive: flags.is_set("interactive"),
+        };
 
         // Register commands
-        executor
-            .commands
-            .insert("cd".to_string(), CommandType::Cd(CdCommand::new()));
-        executor.commands.insert(
+        let mut commands = BTreeMap::new();
+        commands.insert("cd".to_string(), CommandType::Cd(CdCommand::new()));
+        commands.insert("exec".to_string(), CommandType::Exec(ExecCommand::new()));
+        commands.insert(
+            "trap".to_string(),
+            CommandType::Trap(TrapCommand::new(executor.traps.clone())),
+        );
+        commands.insert(
+            "kill".to_string(),
+            CommandType::Kill(KillCommand::new(executor.jobs.clone())),
+        );
+        commands.insert(
+            "wait".to_string(),
+            CommandType::Wait(WaitCommand::new(executor.clone())),
+        );
+        commands.insert(
+            "ulimit".to_string(),
+            CommandType::Ulimit(UlimitCommand::new()),
+        );
+        commands.insert("umask".to_string(), CommandType::Umask(UmaskCommand::new()));
+        commands.insert("times".to_string(), CommandType::Times(TimesCommand::new()));
+        commands.insert(
+            "set".to_string(),
+            CommandType::Set(SetCommand::new(executor.options.clone())),
+        );
+        commands.insert(
+            "shopt".to_string(),
+            CommandType::Shopt(ShoptCommand::new(executor.options.clone())),
+        );
+        commands.insert(
             "source".to_string(),
             CommandType::Source(SourceCommand::new(executor.clone())),
         );
-        executor
-            .commands
-            .insert("exit".to_string(), CommandType::Exit(ExitCommand::new()));
-        executor.commands.insert(
+        commands.insert(
             "alias".to_string(),
-            CommandType::Alias(AliasCommand::new(aliases)),
+            CommandType::Alias(AliasCommand::new(executor.aliases.clone())),
+        );
+        commands.insert(
+            "unalias".to_string(),
+            CommandType::Unalias(UnaliasCommand::new(executor.aliases.clone())),
+        );
+        commands.insert(
+            "abbr".to_string(),
+            CommandType::Abbr(AbbrCommand::new(executor.abbreviations.clone())),
         );
-        executor.commands.insert(
+        commands.insert(
+            "complete".to_string(),
+            CommandType::Complete(CompleteCommand::new(executor.completions.clone())),
+        );
+        commands.insert(
             "history".to_string(),
             CommandType::History(HistoryCommand::new(history)),
         );
-        executor.commands.insert(
+        commands.insert(
             "export".to_string(),
             CommandType::Export(ExportCommand::new(executor.env_vars.clone())),
         );
 
+        let exit = ExitCommand::new().with_executor(executor.clone());
+        commands.insert("exit".to_string(), CommandType::Exit(exit));
+
+        let eval = EvalCommand::new(executor.clone());
+        commands.insert("eval".to_string(), CommandType::Eval(eval));
+
+        // Nothing else sets the table, and it was created empty above
+        let _ = executor.commands.set(commands);
+
         Ok(executor)
     }
 
     pub fn execute(&self, command: &str, args: &[String]) -> Result<(), CommandError> {
         // Convert args to String only for built-in commands
-        if let Some(cmd) = self.commands.get(command) {
-            cmd.execute(args)
+        if let Some(cmd) = self.builtin(command) {
+            let result = cmd.execute(args);
+            match result {
+                Ok(()) if cmd.sets_status() => {}
+                Ok(()) => self.set_last_status(0),
+                Err(_) => self.set_last_status(1),
+            }
+            result
         } else {
             // For external commands, use process executor with string slices
             let mut full_args = vec![command];
             let args_refs: Vec<&str> = args.iter().map(|s| s.as_str()).collect();
             full_args.extend(args_refs);
-            self.process_executor
-                .spawn_process(&full_args)
+            let result = self.process_executor.spawn_process(&full_args);
+            self.set_last_status(*result.as_ref().unwrap_or(&1));
+            result
+                .map(|_| ())
                 .map_err(|e| CommandError::ExecutionError(e.to_string()))
         }
     }
 
+    /// Parses a full command line (pipes, `&&`, quoting) and executes it.
+    pub fn execute_line(&self, line: &str) -> Result<(), CommandError> {
+        let pipeline =
+            Pipeline::parse(line).map_err(|e| CommandError::ExecutionError(e.to_string()))?;
+        let aliases = self
+            .aliases
+            .lock()
+            .map(|aliases| aliases.snapshot())
+            .unwrap_or_default();
+        pipeline
+            .execute_with_context(&aliases, self)
+            .map_err(|e| match e {
+                PipelineError::Exit(code) => CommandError::Exit(code),
+                e => CommandError::ExecutionError(e.to_string()),
+            })
+    }
+
+    /// A snapshot of the current `set`/`shopt` options.
+    pub fn options(&self) -> ShellOptions {
+        self.options
+            .lock()
+            .map(|options| options.clone())
+            .unwrap_or_default()
+    }
+
+    /// The command history, shared with the line editor and the `history`
+    /// builtin.
+    pub fn history(&self) -> Arc<Mutex<History>> {
+        Arc::clone(&self.history)
+    }
+
+    /// The aliases, shared with the config that loads them and the `alias`
+    /// and `unalias` builtins.
+    pub fn aliases(&self) -> Arc<Mutex<AliasManager>> {
+        Arc::clone(&self.aliases)
+    }
+
+    /// The abbreviations the line editor expands, shared with the `abbr`
+    /// builtin.
+    pub fn abbreviations(&self) -> Arc<Mutex<Abbreviations>> {
+        Arc::clone(&self.abbreviations)
+    }
+
+    /// The completion specs `complete` sets up, which the completer uses.
+    pub fn completions(&self) -> Arc<Mutex<CompletionSpecs>> {
+        Arc::clone(&self.completions)
+    }
+
+    /// Registers a background pipeline and returns its job number.
+    pub fn add_job(&self, pids: Vec<i32>, command: &str) -> usize {
+        match self.jobs.lock() {
+            Ok(mut jobs) => jobs.add(pids, command),
+            Err(_) => 0,
+        }
+    }
+
+    /// The number of background jobs still in the table.
+    pub fn job_count(&self) -> usize {
+        self.jobs
+            .lock()
+            .map(|jobs| jobs.iter().count())
+            .unwrap_or(0)
+    }
+
+    /// Reaps background jobs that have finished since the last call.
+    pub fn reap_jobs(&self) -> Vec<Job> {
+        self.jobs
+            .lock()
+            .map(|mut jobs| jobs.reap())
+            .unwrap_or_default()
+    }
+
+    pub fn is_interactive(&self) -> bool {
+        self.interactive
+    }
+
+    pub fn last_status(&self) -> i32 {
+        self.last_status.load(Ordering::SeqCst)
+    }
+
+    pub fn set_last_status(&self, status: i32) {
+        self.last_status.store(status, Ordering::SeqCst);
+    }
+
+    /// Runs the handler registered for `condition`, if any. Handlers don't
+    /// trigger other traps and leave `last_status` untouched.
+    pub fn run_trap(&self, condition: TrapCondition) {
+        let handler = match self.traps.lock() {
+            Ok(traps) => traps.get(condition).map(str::to_string),
+            Err(_) => None,
+        };
+        let Some(handler) = handler.filter(|h| !h.is_empty()) else {
+            return;
+        };
+        if self.in_trap.swap(true, Ordering::SeqCst) {
+            return;
+        }
+
+        let status = self.last_status();
+        if let Err(e) = self.execute_line(&handler) {
+            eprintln!("aorta: trap {}: {}", condition.name(), e);
+        }
+        self.set_last_status(status);
+        self.in_trap.store(false, Ordering::SeqCst);
+    }
+
+    /// Runs the traps of signals received since the last command boundary.
+    pub fn run_pending_traps(&self) {
+        for sig in signal::take_pending() {
+            self.run_trap(TrapCondition::Signal(sig));
+        }
+    }
+
+    /// Runs the EXIT trap once; later calls are no-ops.
+    pub fn run_exit_trap(&self) {
+        self.run_pending_traps();
+        self.run_trap(TrapCondition::Exit);
+        if let Ok(mut traps) = self.traps.lock() {
+            let _ = traps.reset(TrapCondition::Exit);
+        }
+    }
+
     pub fn is_builtin(&self, command: &str) -> bool {
-        self.commands.contains_key(command)
+        self.builtin(command).is_some()
+    }
+
+    fn builtin(&self, name: &str) -> Option<&CommandType> {
+        self.commands.get()?.get(name)
     }
 }
 
@@ -204,13 +470,15 @@ mod tests {
         let (executor, temp_dir) = setup_test_env();
         let test_file = temp_dir.join("test_commands.txt");
 
-        // Create test script
-        fs::write(&test_file, "cd ~\nexit\n")?;
+        // Create test script; `exit` there would end the test run, as it
+        // ends the shell
+        fs::write(&test_file, "cd ~\nexport AORTA_SOURCED=yes\n")?;
 
         // Test source with valid file
         assert!(executor
             .execute("source", &[test_file.to_str().unwrap().to_string()])
             .is_ok());
+        assert_eq!(env::var("AORTA_SOURCED")?, "yes");
 
         // Test source with invalid file
         let result = executor.execute("source", &["/invalid/path".to_string()]);
@@ -226,6 +494,46 @@ mod tests {
         Ok(())
     }
 
+    // Sources `script`, failing if any line of it did
+    fn source_script(executor: &CommandExecutor, name: &str, script: &str) {
+        let file = env::temp_dir().join(name);
+        fs::write(&file, script).unwrap();
+        let sourced = executor.execute_line(&format!("source {}", file.display()));
+        fs::remove_file(file).unwrap();
+        assert!(sourced.is_ok(), "{:?}", sourced);
+        assert_eq!(executor.last_status(), 0);
+    }
+
+    #[test]
+    fn test_source_defines_aliases() {
+        let (executor, _) = setup_test_env();
+        source_script(
+            &executor,
+            "aorta_source_aliases.sh",
+            "alias ll='ls -la'\nalias gs='git status'\nunalias gs\n",
+        );
+        let aliases = executor.aliases();
+        let aliases = aliases.lock().unwrap();
+        assert_eq!(aliases.get("ll").unwrap(), "ls -la");
+        assert!(aliases.get("gs").is_none());
+    }
+
+    #[test]
+    fn test_source_defines_completions_and_abbreviations() {
+        let (executor, _) = setup_test_env();
+        source_script(
+            &executor,
+            "aorta_source_completions.sh",
+            "complete -W 'start stop' svc\nabbr gco git checkout\n",
+        );
+        let completions = executor.completions();
+        let completions = completions.lock().unwrap();
+        assert_eq!(completions.get("svc").unwrap().words, ["start", "stop"]);
+        let abbreviations = executor.abbreviations();
+        let abbreviations = abbreviations.lock().unwrap();
+        assert_eq!(abbreviations.get("gco").unwrap().expansion, "git checkout");
+    }
+
     #[test]
     fn test_execute_exit() {
         use std::panic;
@@ -275,6 +583,9 @@ mod tests {
         assert!(executor.is_builtin("cd"));
         assert!(executor.is_builtin("source"));
         assert!(executor.is_builtin("exit"));
+        assert!(executor.is_builtin("trap"));
+        assert!(executor.is_builtin("eval"));
+        assert!(executor.is_builtin("exec"));
         assert!(!executor.is_builtin("unknown"));
         assert!(!executor.is_builtin(""));
     }
@@ -296,6 +607,46 @@ mod tests {
         }
     }
 
+    #[test]
+    fn test_trap_handler_runs() -> Result<(), CommandError> {
+        let (executor, _) = setup_test_env();
+
+        executor.execute_line(
+            "trap 'export AORTA_ERR_TRAP=yes' ERR; trap 'export AORTA_USR1_TRAP=yes' USR1",
+        )?;
+        unsafe {
+            libc::raise(libc::SIGUSR1);
+        }
+        executor.run_pending_traps();
+        assert_eq!(env::var("AORTA_USR1_TRAP").unwrap(), "yes");
+
+        executor.set_last_status(3);
+        executor.run_trap(TrapCondition::Err);
+        assert_eq!(env::var("AORTA_ERR_TRAP").unwrap(), "yes");
+        assert_eq!(executor.last_status(), 3);
+
+        executor.execute("trap", &["-".to_string(), "USR1".to_string()])?;
+        Ok(())
+    }
+
+    #[test]
+    fn test_builtin_copies_see_every_builtin() -> Result<(), CommandError> {
+        let (executor, _) = setup_test_env();
+
+        // `eval` takes its copy of the executor before it's registered
+        executor.execute_line("eval eval export AORTA_NESTED_EVAL=yes")?;
+        assert_eq!(env::var("AORTA_NESTED_EVAL").unwrap(), "yes");
+
+        // As does `exit`, whose EXIT trap runs from that copy
+        executor.execute_line("trap 'eval export AORTA_EXIT_EVAL=yes' EXIT")?;
+        let CommandType::Exit(exit) = executor.builtin("exit").unwrap() else {
+            panic!("exit isn't the exit builtin");
+        };
+        exit.executor().unwrap().run_exit_trap();
+        assert_eq!(env::var("AORTA_EXIT_EVAL").unwrap(), "yes");
+        Ok(())
+    }
+
     #[test]
     fn test_command_error_display() {
         let errors = vec![
@@ -0,0 +1,131 @@
+use super::{Command, CommandError};
+use crate::core::options::{OptionError, ShellOptions};
+use std::sync::{Arc, Mutex, MutexGuard};
+
+#[derive(Clone)]
+pub struct SetCommand {
+    options: Arc<Mutex<ShellOptions>>,
+}
+
+impl SetCommand {
+    pub fn new(options: Arc<Mutex<ShellOptions>>) -> Self {
+        Self { options }
+    }
+
+    fn lock(&self) -> Result<MutexGuard<'_, ShellOptions>, CommandError> {
+        self.options
+            .lock()
+            .map_err(|_| CommandError::ExecutionError("Failed to lock shell options".to_string()))
+    }
+
+    fn print_variables() {
+        let mut vars: Vec<(String, String)> = std::env::vars().collect();
+        vars.sort();
+        for (name, value) in vars {
+            println!("{}={}", name, value);
+        }
+    }
+
+    // `set -o` prints a table, `set +o` prints commands that recreate it
+    fn print_options(&self, as_commands: bool) -> Result<(), CommandError> {
+        let options = self.lock()?;
+        for (name, enabled) in options.set_options() {
+            if as_commands {
+                println!("set {}o {}", if enabled { '-' } else { '+' }, name);
+            } else {
+                println!("{:<15}\t{}", name, if enabled { "on" } else { "off" });
+            }
+        }
+        Ok(())
+    }
+
+    // Applies one `-abc`/`+abc` argument; `o` consumes the next argument as
+    // an option name, or lists the options when there is none.
+    fn apply_flags<'a>(
+        &self,
+        arg: &str,
+        rest: &mut impl Iterator<Item = &'a String>,
+    ) -> Result<(), CommandError> {
+        let enabled = arg.starts_with('-');
+        for flag in arg[1..].chars() {
+            if flag != 'o' {
+                self.lock()?.set_flag(flag, enabled).map_err(invalid)?;
+                continue;
+            }
+            match rest.next() {
+                Some(name) => self.lock()?.set(name, enabled).map_err(invalid)?,
+                None => self.print_options(!enabled)?,
+            }
+        }
+        Ok(())
+    }
+}
+
+impl Command for SetCommand {
+    fn execute(&self, args: &[String]) -> Result<(), CommandError> {
+        if args.is_empty() {
+            Self::print_variables();
+            return Ok(());
+        }
+
+        let mut iter = args.iter();
+        while let Some(arg) = iter.next() {
+            match arg.as_str() {
+                "--" | "-" => break,
+                flags if flags.len() > 1 && (flags.starts_with('-') || flags.starts_with('+')) => {
+                    self.apply_flags(flags, &mut iter)?
+                }
+                other => {
+                    return Err(CommandError::InvalidArguments(format!(
+                        "set: {}: positional parameters are not supported",
+                        other
+                    )))
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+fn invalid(err: OptionError) -> CommandError {
+    CommandError::InvalidArguments(format!("set: {}", err))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn setup_command() -> (SetCommand, Arc<Mutex<ShellOptions>>) {
+        let options = Arc::new(Mutex::new(ShellOptions::new()));
+        (SetCommand::new(options.clone()), options)
+    }
+
+    fn args(values: &[&str]) -> Vec<String> {
+        values.iter().map(|s| s.to_string()).collect()
+    }
+
+    #[test]
+    fn test_set_combined_flags() {
+        let (cmd, options) = setup_command();
+
+        cmd.execute(&args(&["-euxo", "pipefail"])).unwrap();
+        {
+            let options = options.lock().unwrap();
+            assert!(options.errexit() && options.nounset() && options.xtrace());
+            assert!(options.pipefail());
+        }
+
+        cmd.execute(&args(&["+ex", "+o", "pipefail"])).unwrap();
+        let options = options.lock().unwrap();
+        assert!(!options.errexit() && !options.xtrace() && !options.pipefail());
+        assert!(options.nounset());
+    }
+
+    #[test]
+    fn test_set_invalid() {
+        let (cmd, _) = setup_command();
+        assert!(cmd.execute(&args(&["-Z"])).is_err());
+        assert!(cmd.execute(&args(&["-o", "nonsense"])).is_err());
+        assert!(cmd.execute(&args(&["positional"])).is_err());
+    }
+}
@@ -0,0 +1,147 @@
+use super::{Command, CommandError};
+use crate::core::options::ShellOptions;
+use std::sync::{Arc, Mutex, MutexGuard};
+
+#[derive(Clone)]
+pub struct ShoptCommand {
+    options: Arc<Mutex<ShellOptions>>,
+}
+
+#[derive(Default)]
+struct ShoptArgs<'a> {
+    enable: Option<bool>,
+    print: bool,
+    quiet: bool,
+    // `-o` works on the `set -o` options instead
+    set_options: bool,
+    names: Vec<&'a str>,
+}
+
+impl ShoptCommand {
+    pub fn new(options: Arc<Mutex<ShellOptions>>) -> Self {
+        Self { options }
+    }
+
+    fn lock(&self) -> Result<MutexGuard<'_, ShellOptions>, CommandError> {
+        self.options
+            .lock()
+            .map_err(|_| CommandError::ExecutionError("Failed to lock shell options".to_string()))
+    }
+
+    fn parse_args(args: &[String]) -> Result<ShoptArgs<'_>, CommandError> {
+        let mut parsed = ShoptArgs::default();
+        for arg in args {
+            match arg.as_str() {
+                "-s" => parsed.enable = Some(true),
+                "-u" => parsed.enable = Some(false),
+                "-p" => parsed.print = true,
+                "-q" => parsed.quiet = true,
+                "-o" => parsed.set_options = true,
+                flag if flag.starts_with('-') => {
+                    return Err(CommandError::InvalidArguments(format!(
+                        "shopt: {}: invalid option\nUsage: shopt [-pqsuo] [optname ...]",
+                        flag
+                    )))
+                }
+                name => parsed.names.push(name),
+            }
+        }
+        Ok(parsed)
+    }
+
+    fn set(&self, args: &ShoptArgs<'_>, enabled: bool) -> Result<(), CommandError> {
+        let mut options = self.lock()?;
+        for name in &args.names {
+            let result = if args.set_options {
+                options.set(name, enabled)
+            } else {
+                options.set_shopt(name, enabled)
+            };
+            result.map_err(|e| CommandError::InvalidArguments(format!("shopt: {}", e)))?;
+        }
+        Ok(())
+    }
+
+    fn print(&self, args: &ShoptArgs<'_>) -> Result<(), CommandError> {
+        let options = self.lock()?;
+        let all: Vec<(&str, bool)> = if args.set_options {
+            options.set_options().collect()
+        } else {
+            options.shopt_options().collect()
+        };
+
+        let mut all_on = true;
+        for name in &args.names {
+            if !all.iter().any(|(known, _)| known == name) {
+                return Err(CommandError::InvalidArguments(format!(
+                    "shopt: {}: invalid shell option name",
+                    name
+                )));
+            }
+        }
+        for (name, enabled) in all {
+            if !args.names.is_empty() && !args.names.contains(&name) {
+                continue;
+            }
+            all_on &= enabled;
+            if args.quiet {
+                continue;
+            }
+            match (args.print, args.set_options) {
+                (true, true) => println!("set {}o {}", if enabled { '-' } else { '+' }, name),
+                (true, false) => println!("shopt {} {}", if enabled { "-s" } else { "-u" }, name),
+                _ => println!("{:<15}\t{}", name, if enabled { "on" } else { "off" }),
+            }
+        }
+
+        // Like bash, querying named options fails when any of them is off
+        if !args.names.is_empty() && !all_on {
+            return Err(CommandError::ExecutionError(
+                "shopt: option not set".to_string(),
+            ));
+        }
+        Ok(())
+    }
+}
+
+impl Command for ShoptCommand {
+    fn execute(&self, args: &[String]) -> Result<(), CommandError> {
+        let args = Self::parse_args(args)?;
+        match args.enable {
+            Some(enabled) if !args.names.is_empty() => self.set(&args, enabled),
+            _ => self.print(&args),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn args(values: &[&str]) -> Vec<String> {
+        values.iter().map(|s| s.to_string()).collect()
+    }
+
+    #[test]
+    fn test_shopt_set_and_query() {
+        let options = Arc::new(Mutex::new(ShellOptions::new()));
+        let cmd = ShoptCommand::new(options.clone());
+
+        cmd.execute(&args(&["-s", "nullglob", "dotglob"])).unwrap();
+        assert!(options.lock().unwrap().is_shopt("nullglob"));
+        assert!(cmd.execute(&args(&["-q", "nullglob"])).is_ok());
+
+        cmd.execute(&args(&["-u", "nullglob"])).unwrap();
+        assert!(cmd.execute(&args(&["-q", "nullglob"])).is_err());
+
+        cmd.execute(&args(&["-o", "-s", "noglob"])).unwrap();
+        assert!(options.lock().unwrap().noglob());
+    }
+
+    #[test]
+    fn test_shopt_invalid_name() {
+        let cmd = ShoptCommand::new(Arc::new(Mutex::new(ShellOptions::new())));
+        assert!(cmd.execute(&args(&["-s", "nonsense"])).is_err());
+        assert!(cmd.execute(&args(&["nonsense"])).is_err());
+    }
+}
@@ -1,7 +1,8 @@
 use std::fs;
 
-use super::{Command, CommandError, CommandExecutor};
+use super::{Command, CommandError, CommandExecutor, TrapCondition};
 use crate::path::PathExpander;
+use crate::shell::pipeline::Pipeline;
 
 #[derive(Clone)]
 pub struct SourceCommand {
@@ -16,6 +17,30 @@ impl SourceCommand {
             executor,
         }
     }
+
+    fn execute_line(&self, line: &str) -> Result<(), CommandError> {
+        // `set -n` reads commands without running them, to check syntax
+        if self.executor.options().noexec() {
+            return Pipeline::parse(line).map(|_| ()).map_err(|e| {
+                CommandError::ExecutionError(format!("Failed to parse '{}': {}", line, e))
+            });
+        }
+
+        self.executor.run_trap(TrapCondition::Debug);
+        let result = self.executor.execute_line(line);
+
+        // Failed commands ran the ERR trap in the pipeline; this is for lines
+        // that couldn't run
+        if matches!(result, Err(CommandError::ExecutionError(_))) {
+            self.executor.run_trap(TrapCondition::Err);
+        }
+        self.executor.run_pending_traps();
+
+        result.map_err(|e| match e {
+            CommandError::Exit(code) => CommandError::Exit(code),
+            e => CommandError::ExecutionError(format!("Failed to execute '{}': {}", line, e)),
+        })
+    }
 }
 
 impl Command for SourceCommand {
@@ -34,28 +59,15 @@ impl Command for SourceCommand {
         let content = fs::read_to_string(&path)
             .map_err(|e| CommandError::ExecutionError(format!("Failed to read file: {}", e)))?;
 
-        // Execute each line from the file
-        for line in content.lines() {
-            let line = line.trim();
-            // Skip empty lines and comments
-            if line.is_empty() || line.starts_with('#') {
-                continue;
-            }
-
-            let parts: Vec<String> = line.split_whitespace().map(String::from).collect();
-            if parts.is_empty() {
-                continue;
-            }
-
-            let command = &parts[0];
-            let args = &parts[1..];
-
-            self.executor.execute(command, args).map_err(|e| {
-                CommandError::ExecutionError(format!("Failed to execute '{}': {}", line, e))
-            })?;
-        }
+        // Execute each line from the file, running traps between commands
+        let result = content
+            .lines()
+            .map(str::trim)
+            .filter(|line| !line.is_empty() && !line.starts_with('#'))
+            .try_for_each(|line| self.execute_line(line));
 
-        Ok(())
+        self.executor.run_trap(TrapCondition::Return);
+        result
     }
 }
 
@@ -0,0 +1,47 @@
+use super::{Command, CommandError};
+use std::io;
+
+/// Prints the user and system time used by the shell and by its children.
+#[derive(Clone, Default)]
+pub struct TimesCommand;
+
+impl TimesCommand {
+    pub fn new() -> Self {
+        Self
+    }
+
+    fn usage(who: libc::c_int) -> io::Result<(f64, f64)> {
+        let mut usage: libc::rusage = unsafe { std::mem::zeroed() };
+        if unsafe { libc::getrusage(who, &mut usage) } != 0 {
+            return Err(io::Error::last_os_error());
+        }
+        let seconds = |tv: libc::timeval| tv.tv_sec as f64 + tv.tv_usec as f64 / 1_000_000.0;
+        Ok((seconds(usage.ru_utime), seconds(usage.ru_stime)))
+    }
+}
+
+fn format_time(seconds: f64) -> String {
+    let minutes = (seconds / 60.0).floor();
+    format!("{}m{:.3}s", minutes, seconds - minutes * 60.0)
+}
+
+impl Command for TimesCommand {
+    fn execute(&self, _args: &[String]) -> Result<(), CommandError> {
+        for who in [libc::RUSAGE_SELF, libc::RUSAGE_CHILDREN] {
+            let (user, system) = Self::usage(who)?;
+            println!("{} {}", format_time(user), format_time(system));
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_format_time() {
+        assert_eq!(format_time(0.0), "0m0.000s");
+        assert_eq!(format_time(61.25), "1m1.250s");
+    }
+}
@@ -0,0 +1,233 @@
+use super::{Command, CommandError};
+use crate::process::signal;
+use crate::shell::expand::shell_quote;
+use std::collections::BTreeMap;
+use std::sync::{Arc, Mutex};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum TrapCondition {
+    Exit,
+    Signal(i32),
+    Err,
+    Debug,
+    Return,
+}
+
+impl TrapCondition {
+    pub fn parse(spec: &str) -> Option<Self> {
+        match spec.to_ascii_uppercase().as_str() {
+            "EXIT" | "SIGEXIT" | "0" => Some(TrapCondition::Exit),
+            "ERR" => Some(TrapCondition::Err),
+            "DEBUG" => Some(TrapCondition::Debug),
+            "RETURN" => Some(TrapCondition::Return),
+            _ => signal::from_name(spec).map(TrapCondition::Signal),
+        }
+    }
+
+    pub fn name(&self) -> String {
+        match self {
+            TrapCondition::Exit => "EXIT".to_string(),
+            TrapCondition::Err => "ERR".to_string(),
+            TrapCondition::Debug => "DEBUG".to_string(),
+            TrapCondition::Return => "RETURN".to_string(),
+            TrapCondition::Signal(sig) => match signal::name(*sig) {
+                Some(name) => format!("SIG{}", name),
+                None => sig.to_string(),
+            },
+        }
+    }
+}
+
+/// Trap handlers by condition; an empty handler means the signal is ignored.
+#[derive(Debug, Default)]
+pub struct Traps {
+    handlers: BTreeMap<TrapCondition, String>,
+}
+
+impl Traps {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn get(&self, condition: TrapCondition) -> Option<&str> {
+        self.handlers.get(&condition).map(String::as_str)
+    }
+
+    pub fn set(&mut self, condition: TrapCondition, handler: &str) -> Result<(), CommandError> {
+        if let TrapCondition::Signal(sig) = condition {
+            if handler.is_empty() {
+                signal::ignore(sig)?;
+            } else {
+                signal::catch(sig)?;
+            }
+        }
+        self.handlers.insert(condition, handler.to_string());
+        Ok(())
+    }
+
+    pub fn reset(&mut self, condition: TrapCondition) -> Result<(), CommandError> {
+        if let TrapCondition::Signal(sig) = condition {
+            signal::restore(sig)?;
+        }
+        self.handlers.remove(&condition);
+        Ok(())
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = (TrapCondition, &str)> {
+        self.handlers
+            .iter()
+            .map(|(cond, handler)| (*cond, handler.as_str()))
+    }
+}
+
+#[derive(Clone)]
+pub struct TrapCommand {
+    traps: Arc<Mutex<Traps>>,
+}
+
+impl TrapCommand {
+    pub fn new(traps: Arc<Mutex<Traps>>) -> Self {
+        Self { traps }
+    }
+
+    fn parse_conditions(specs: &[String]) -> Result<Vec<TrapCondition>, CommandError> {
+        specs
+            .iter()
+            .map(|spec| {
+                TrapCondition::parse(spec).ok_or_else(|| {
+                    CommandError::InvalidArguments(format!(
+                        "trap: {}: invalid signal specification",
+                        spec
+                    ))
+                })
+            })
+            .collect()
+    }
+
+    fn print(&self, specs: &[String]) -> Result<(), CommandError> {
+        let conditions = Self::parse_conditions(specs)?;
+        let traps = self.lock()?;
+
+        for (condition, handler) in traps.iter() {
+            if conditions.is_empty() || conditions.contains(&condition) {
+                println!("trap -- {} {}", shell_quote(handler), condition.name());
+            }
+        }
+        Ok(())
+    }
+
+    fn list_signals() {
+        for (name, number) in signal::SIGNALS {
+            println!("{:2}) SIG{}", number, name);
+        }
+    }
+
+    fn lock(&self) -> Result<std::sync::MutexGuard<'_, Traps>, CommandError> {
+        self.traps
+            .lock()
+            .map_err(|_| CommandError::ExecutionError("Failed to lock traps".to_string()))
+    }
+}
+
+impl Command for TrapCommand {
+    fn execute(&self, args: &[String]) -> Result<(), CommandError> {
+        let args = match args.first().map(String::as_str) {
+            Some("--") => &args[1..],
+            _ => args,
+        };
+
+        match args.first().map(String::as_str) {
+            None => self.print(&[]),
+            Some("-p") => self.print(&args[1..]),
+            Some("-l") => {
+                Self::list_signals();
+                Ok(())
+            }
+            Some("-") => {
+                let conditions = Self::parse_conditions(&args[1..])?;
+                let mut traps = self.lock()?;
+                conditions
+                    .into_iter()
+                    .try_for_each(|cond| traps.reset(cond))
+            }
+            // A lone condition resets it, like `trap INT`
+            Some(spec) if args.len() == 1 && TrapCondition::parse(spec).is_some() => {
+                let conditions = Self::parse_conditions(args)?;
+                let mut traps = self.lock()?;
+                conditions
+                    .into_iter()
+                    .try_for_each(|cond| traps.reset(cond))
+            }
+            Some(_) if args.len() == 1 => Err(CommandError::InvalidArguments(
+                "Usage: trap [-lp] [[handler] condition ...]".to_string(),
+            )),
+            Some(handler) => {
+                let conditions = Self::parse_conditions(&args[1..])?;
+                let mut traps = self.lock()?;
+                conditions
+                    .into_iter()
+                    .try_for_each(|cond| traps.set(cond, handler))
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn setup_command() -> (TrapCommand, Arc<Mutex<Traps>>) {
+        let traps = Arc::new(Mutex::new(Traps::new()));
+        (TrapCommand::new(traps.clone()), traps)
+    }
+
+    #[test]
+    fn test_trap_set_and_reset() {
+        let (cmd, traps) = setup_command();
+
+        cmd.execute(&["echo bye".to_string(), "EXIT".to_string()])
+            .unwrap();
+        assert_eq!(
+            traps.lock().unwrap().get(TrapCondition::Exit),
+            Some("echo bye")
+        );
+
+        cmd.execute(&["-".to_string(), "EXIT".to_string()]).unwrap();
+        assert_eq!(traps.lock().unwrap().get(TrapCondition::Exit), None);
+    }
+
+    #[test]
+    fn test_trap_signal() {
+        let (cmd, traps) = setup_command();
+
+        cmd.execute(&["echo alrm".to_string(), "ALRM".to_string()])
+            .unwrap();
+        let condition = TrapCondition::Signal(libc::SIGALRM);
+        assert_eq!(traps.lock().unwrap().get(condition), Some("echo alrm"));
+        assert_eq!(
+            signal::disposition(libc::SIGALRM),
+            signal::Disposition::Catch
+        );
+
+        cmd.execute(&["SIGALRM".to_string()]).unwrap();
+        assert_eq!(traps.lock().unwrap().get(condition), None);
+    }
+
+    #[test]
+    fn test_trap_invalid_signal() {
+        let (cmd, _) = setup_command();
+        assert!(cmd
+            .execute(&["echo".to_string(), "NOTASIGNAL".to_string()])
+            .is_err());
+        assert!(cmd
+            .execute(&["echo".to_string(), "KILL".to_string()])
+            .is_err());
+    }
+
+    #[test]
+    fn test_condition_names() {
+        assert_eq!(TrapCondition::parse("int").unwrap().name(), "SIGINT");
+        assert_eq!(TrapCondition::parse("0"), Some(TrapCondition::Exit));
+        assert_eq!(TrapCondition::parse("ERR"), Some(TrapCondition::Err));
+    }
+}
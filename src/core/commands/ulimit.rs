@@ -0,0 +1,264 @@
+use super::{Command, CommandError};
+use std::io;
+
+#[cfg(all(target_os = "linux", target_env = "gnu"))]
+type Resource = libc::__rlimit_resource_t;
+#[cfg(not(all(target_os = "linux", target_env = "gnu")))]
+type Resource = libc::c_int;
+
+struct Limit {
+    flag: char,
+    resource: Resource,
+    description: &'static str,
+    unit: &'static str,
+    // Bytes per displayed unit
+    scale: u64,
+}
+
+const LIMITS: &[Limit] = &[
+    Limit {
+        flag: 'c',
+        resource: libc::RLIMIT_CORE,
+        description: "core file size",
+        unit: "blocks",
+        scale: 512,
+    },
+    Limit {
+        flag: 'd',
+        resource: libc::RLIMIT_DATA,
+        description: "data seg size",
+        unit: "kbytes",
+        scale: 1024,
+    },
+    Limit {
+        flag: 'f',
+        resource: libc::RLIMIT_FSIZE,
+        description: "file size",
+        unit: "blocks",
+        scale: 512,
+    },
+    Limit {
+        flag: 'l',
+        resource: libc::RLIMIT_MEMLOCK,
+        description: "max locked memory",
+        unit: "kbytes",
+        scale: 1024,
+    },
+    Limit {
+        flag: 'm',
+        resource: libc::RLIMIT_RSS,
+        description: "max memory size",
+        unit: "kbytes",
+        scale: 1024,
+    },
+    Limit {
+        flag: 'n',
+        resource: libc::RLIMIT_NOFILE,
+        description: "open files",
+        unit: "",
+        scale: 1,
+    },
+    Limit {
+        flag: 's',
+        resource: libc::RLIMIT_STACK,
+        description: "stack size",
+        unit: "kbytes",
+        scale: 1024,
+    },
+    Limit {
+        flag: 't',
+        resource: libc::RLIMIT_CPU,
+        description: "cpu time",
+        unit: "seconds",
+        scale: 1,
+    },
+    Limit {
+        flag: 'u',
+        resource: libc::RLIMIT_NPROC,
+        description: "max user processes",
+        unit: "",
+        scale: 1,
+    },
+    Limit {
+        flag: 'v',
+        resource: libc::RLIMIT_AS,
+        description: "virtual memory",
+        unit: "kbytes",
+        scale: 1024,
+    },
+];
+
+#[derive(Default)]
+struct UlimitArgs<'a> {
+    hard: bool,
+    soft: bool,
+    all: bool,
+    limits: Vec<&'static Limit>,
+    value: Option<&'a str>,
+}
+
+#[derive(Clone, Default)]
+pub struct UlimitCommand;
+
+impl UlimitCommand {
+    pub fn new() -> Self {
+        Self
+    }
+
+    fn parse_args(args: &[String]) -> Result<UlimitArgs<'_>, CommandError> {
+        let mut parsed = UlimitArgs::default();
+        for arg in args {
+            let Some(flags) = arg.strip_prefix('-').filter(|f| !f.is_empty()) else {
+                if parsed.value.is_some() {
+                    return Err(CommandError::InvalidArguments(format!(
+                        "ulimit: {}: too many arguments",
+                        arg
+                    )));
+                }
+                parsed.value = Some(arg);
+                continue;
+            };
+            for flag in flags.chars() {
+                match flag {
+                    'H' => parsed.hard = true,
+                    'S' => parsed.soft = true,
+                    'a' => parsed.all = true,
+                    _ => parsed
+                        .limits
+                        .push(LIMITS.iter().find(|l| l.flag == flag).ok_or_else(|| {
+                            CommandError::InvalidArguments(format!(
+                                "ulimit: -{}: invalid option\nUsage: ulimit [-SHa{}] [limit]",
+                                flag,
+                                LIMITS.iter().map(|l| l.flag).collect::<String>()
+                            ))
+                        })?),
+                }
+            }
+        }
+
+        // Like bash, `-f` is the default resource
+        if parsed.limits.is_empty() && !parsed.all {
+            parsed.limits.extend(LIMITS.iter().find(|l| l.flag == 'f'));
+        }
+        Ok(parsed)
+    }
+
+    fn get(limit: &Limit) -> io::Result<libc::rlimit> {
+        let mut value = libc::rlimit {
+            rlim_cur: 0,
+            rlim_max: 0,
+        };
+        if unsafe { libc::getrlimit(limit.resource, &mut value) } != 0 {
+            return Err(io::Error::last_os_error());
+        }
+        Ok(value)
+    }
+
+    fn format(limit: &Limit, value: libc::rlim_t) -> String {
+        if value == libc::RLIM_INFINITY {
+            "unlimited".to_string()
+        } else {
+            (value / limit.scale).to_string()
+        }
+    }
+
+    fn show(args: &UlimitArgs<'_>, limits: &[&Limit]) -> Result<(), CommandError> {
+        for limit in limits {
+            let value = Self::get(limit)?;
+            let current = if args.hard {
+                value.rlim_max
+            } else {
+                value.rlim_cur
+            };
+            let shown = Self::format(limit, current);
+            if limits.len() == 1 {
+                println!("{}", shown);
+                continue;
+            }
+            let unit = if limit.unit.is_empty() {
+                format!("(-{})", limit.flag)
+            } else {
+                format!("({}, -{})", limit.unit, limit.flag)
+            };
+            println!("{:<28}{:>14} {}", limit.description, unit, shown);
+        }
+        Ok(())
+    }
+
+    fn set(args: &UlimitArgs<'_>, value: &str) -> Result<(), CommandError> {
+        for limit in &args.limits {
+            let new = match value {
+                "unlimited" => libc::RLIM_INFINITY,
+                number => number
+                    .parse::<u64>()
+                    .ok()
+                    .and_then(|n| n.checked_mul(limit.scale))
+                    .ok_or_else(|| {
+                        CommandError::InvalidArguments(format!("ulimit: {}: invalid number", value))
+                    })?,
+            };
+
+            // Without -H or -S both limits change
+            let mut current = Self::get(limit)?;
+            if args.soft || !args.hard {
+                current.rlim_cur = new;
+            }
+            if args.hard || !args.soft {
+                current.rlim_max = new;
+            }
+            if unsafe { libc::setrlimit(limit.resource, &current) } != 0 {
+                return Err(CommandError::ExecutionError(format!(
+                    "ulimit: {}: cannot modify limit: {}",
+                    limit.description,
+                    io::Error::last_os_error()
+                )));
+            }
+        }
+        Ok(())
+    }
+}
+
+impl Command for UlimitCommand {
+    fn execute(&self, args: &[String]) -> Result<(), CommandError> {
+        let args = Self::parse_args(args)?;
+        match args.value {
+            Some(value) => Self::set(&args, value),
+            None if args.all => Self::show(&args, &LIMITS.iter().collect::<Vec<_>>()),
+            None => Self::show(&args, &args.limits),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn args(values: &[&str]) -> Vec<String> {
+        values.iter().map(|s| s.to_string()).collect()
+    }
+
+    #[test]
+    fn test_parse_args() {
+        let list = args(&["-Sn", "512"]);
+        let parsed = UlimitCommand::parse_args(&list).unwrap();
+        assert!(parsed.soft && !parsed.hard);
+        assert_eq!(parsed.limits[0].flag, 'n');
+        assert_eq!(parsed.value, Some("512"));
+
+        let parsed = UlimitCommand::parse_args(&[]).unwrap();
+        assert_eq!(parsed.limits[0].flag, 'f');
+        assert!(UlimitCommand::parse_args(&args(&["-Q"])).is_err());
+    }
+
+    #[test]
+    fn test_set_soft_limit() {
+        let nofile = &LIMITS[5];
+        let before = UlimitCommand::get(nofile).unwrap();
+        let lower = before.rlim_cur.min(before.rlim_max).min(4096);
+
+        let cmd = UlimitCommand::new();
+        cmd.execute(&args(&["-Sn", &lower.to_string()])).unwrap();
+        assert_eq!(UlimitCommand::get(nofile).unwrap().rlim_cur, lower);
+        assert!(cmd.execute(&args(&["-n", "lots"])).is_err());
+    }
+}
@@ -0,0 +1,133 @@
+use super::{Command, CommandError};
+use libc::mode_t;
+
+#[derive(Clone, Default)]
+pub struct UmaskCommand;
+
+impl UmaskCommand {
+    pub fn new() -> Self {
+        Self
+    }
+
+    fn current() -> mode_t {
+        // umask can only be read by setting it, so put the old value back
+        let mask = unsafe { libc::umask(0) };
+        unsafe { libc::umask(mask) };
+        mask
+    }
+
+    /// Formats a mask the way `umask -S` does, as the permissions it allows.
+    fn symbolic(mask: mode_t) -> String {
+        let allowed = !mask & 0o777;
+        [('u', 6), ('g', 3), ('o', 0)]
+            .iter()
+            .map(|(who, shift)| {
+                let bits = (allowed >> shift) & 0o7;
+                let perms: String = [('r', 0o4), ('w', 0o2), ('x', 0o1)]
+                    .iter()
+                    .filter(|(_, bit)| bits & bit != 0)
+                    .map(|(c, _)| *c)
+                    .collect();
+                format!("{}={}", who, perms)
+            })
+            .collect::<Vec<_>>()
+            .join(",")
+    }
+
+    /// Parses an octal mask or a symbolic mode like `u=rwx,g+r,o-w`, which
+    /// is applied to the permissions the current mask allows.
+    fn parse(mode: &str, mask: mode_t) -> Result<mode_t, CommandError> {
+        let invalid = || CommandError::InvalidArguments(format!("umask: {}: invalid mode", mode));
+
+        if mode.chars().all(|c| c.is_ascii_digit()) {
+            return mode_t::from_str_radix(mode, 8)
+                .ok()
+                .filter(|m| *m <= 0o777)
+                .ok_or_else(invalid);
+        }
+
+        let mut allowed = !mask & 0o777;
+        for clause in mode.split(',') {
+            let op_at = clause.find(['+', '-', '=']).ok_or_else(invalid)?;
+            let (who, rest) = clause.split_at(op_at);
+            let (op, perms) = rest.split_at(1);
+
+            let who_bits = who.chars().try_fold(0, |acc, c| match c {
+                'u' => Some(acc | 0o700),
+                'g' => Some(acc | 0o070),
+                'o' => Some(acc | 0o007),
+                'a' => Some(acc | 0o777),
+                _ => None,
+            });
+            let who_bits = match who_bits.ok_or_else(invalid)? {
+                0 => 0o777,
+                bits => bits,
+            };
+            let perm_bits = perms.chars().try_fold(0, |acc, c| match c {
+                'r' => Some(acc | 0o444),
+                'w' => Some(acc | 0o222),
+                'x' => Some(acc | 0o111),
+                _ => None,
+            });
+            let bits = perm_bits.ok_or_else(invalid)? & who_bits;
+
+            allowed = match op {
+                "+" => allowed | bits,
+                "-" => allowed & !bits,
+                _ => (allowed & !who_bits) | bits,
+            };
+        }
+        Ok(!allowed & 0o777)
+    }
+}
+
+impl Command for UmaskCommand {
+    fn execute(&self, args: &[String]) -> Result<(), CommandError> {
+        let symbolic = args.iter().any(|a| a == "-S");
+        let reusable = args.iter().any(|a| a == "-p");
+        let mode = args.iter().find(|a| !a.starts_with('-'));
+
+        let mask = Self::current();
+        match mode {
+            Some(mode) => {
+                let new = Self::parse(mode, mask)?;
+                unsafe { libc::umask(new) };
+            }
+            None => {
+                let shown = if symbolic {
+                    Self::symbolic(mask)
+                } else {
+                    format!("{:04o}", mask)
+                };
+                match (reusable, symbolic) {
+                    (true, true) => println!("umask -S {}", shown),
+                    (true, false) => println!("umask {}", shown),
+                    _ => println!("{}", shown),
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_symbolic_output() {
+        assert_eq!(UmaskCommand::symbolic(0o022), "u=rwx,g=rx,o=rx");
+        assert_eq!(UmaskCommand::symbolic(0o077), "u=rwx,g=,o=");
+    }
+
+    #[test]
+    fn test_parse_modes() {
+        assert_eq!(UmaskCommand::parse("027", 0o022).unwrap(), 0o027);
+        assert_eq!(UmaskCommand::parse("u=rwx,g=rx,o=", 0o022).unwrap(), 0o027);
+        assert_eq!(UmaskCommand::parse("g-w", 0o002).unwrap(), 0o022);
+        assert_eq!(UmaskCommand::parse("o+w", 0o022).unwrap(), 0o020);
+        assert_eq!(UmaskCommand::parse("=r", 0o022).unwrap(), 0o333);
+        assert!(UmaskCommand::parse("999", 0o022).is_err());
+        assert!(UmaskCommand::parse("u=q", 0o022).is_err());
+    }
+}
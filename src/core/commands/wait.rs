@@ -0,0 +1,142 @@
+use super::{Command, CommandError, CommandExecutor};
+use crate::process::jobs::{wait_any, wait_pid};
+
+#[derive(Clone)]
+pub struct WaitCommand {
+    executor: CommandExecutor,
+}
+
+impl WaitCommand {
+    pub fn new(executor: CommandExecutor) -> Self {
+        Self { executor }
+    }
+
+    fn lock(&self) -> Result<std::sync::MutexGuard<'_, crate::process::Jobs>, CommandError> {
+        self.executor
+            .jobs
+            .lock()
+            .map_err(|_| CommandError::ExecutionError("Failed to lock jobs".to_string()))
+    }
+
+    // Waits for every process of a job and returns the job's status
+    fn wait_job(&self, id: usize) -> Result<i32, CommandError> {
+        let pids: Vec<i32> = match self.lock()?.get(id) {
+            Some(job) => job.pids().collect(),
+            None => return Ok(127),
+        };
+        for pid in pids {
+            if let Some(status) = wait_pid(pid, 0) {
+                self.lock()?.record(pid, status);
+            }
+        }
+        Ok(self
+            .lock()?
+            .remove(id)
+            .and_then(|job| job.status())
+            .unwrap_or(127))
+    }
+
+    fn wait_target(&self, target: &str) -> Result<i32, CommandError> {
+        let job = if target.starts_with('%') {
+            self.lock()?.find(target).map(|job| job.id)
+        } else {
+            let pid: i32 = target.parse().map_err(|_| {
+                CommandError::InvalidArguments(format!(
+                    "wait: `{}': not a pid or valid job spec",
+                    target
+                ))
+            })?;
+            if let Some(status) = self.lock()?.take_finished(pid) {
+                return Ok(status);
+            }
+            let job = self.lock()?.job_of(pid);
+            if job.is_none() {
+                eprintln!("aorta: wait: pid {} is not a child of this shell", pid);
+                return Ok(127);
+            }
+            // Waiting for one process of a job leaves the others running
+            if let Some(status) = wait_pid(pid, 0) {
+                let mut jobs = self.lock()?;
+                if let Some(id) = jobs.record(pid, status) {
+                    jobs.remove(id);
+                }
+                jobs.take_finished(pid);
+                return Ok(status);
+            }
+            return Ok(127);
+        };
+
+        match job {
+            Some(id) => self.wait_job(id),
+            None => {
+                eprintln!("aorta: wait: {}: no such job", target);
+                Ok(127)
+            }
+        }
+    }
+
+    // `wait -n`: returns the status of the next job to complete
+    fn wait_next(&self) -> Result<i32, CommandError> {
+        if self.lock()?.is_empty() {
+            return Ok(127);
+        }
+        while let Some((pid, status)) = wait_any(-1, 0) {
+            let mut jobs = self.lock()?;
+            if let Some(id) = jobs.record(pid, status) {
+                return Ok(jobs
+                    .remove(id)
+                    .and_then(|job| job.status())
+                    .unwrap_or(status));
+            }
+        }
+        Ok(127)
+    }
+
+    fn wait_all(&self) -> Result<i32, CommandError> {
+        let ids: Vec<usize> = self.lock()?.iter().map(|job| job.id).collect();
+        for id in ids {
+            self.wait_job(id)?;
+        }
+        Ok(0)
+    }
+}
+
+impl Command for WaitCommand {
+    fn execute(&self, args: &[String]) -> Result<(), CommandError> {
+        let status = match args.first().map(String::as_str) {
+            None => self.wait_all()?,
+            Some("-n") => self.wait_next()?,
+            Some(_) => args
+                .iter()
+                .map(|target| self.wait_target(target))
+                .try_fold(0, |_, status| status)?,
+        };
+        self.executor.set_last_status(status);
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_wait_returns_job_status() {
+        let executor = CommandExecutor::new(&crate::flags::Flags::default()).unwrap();
+        let cmd = WaitCommand::new(executor.clone());
+
+        // Reaped by `wait` through the job table
+        #[allow(clippy::zombie_processes)]
+        let child = std::process::Command::new("/bin/sh")
+            .args(["-c", "exit 3"])
+            .spawn()
+            .unwrap();
+        let id = executor.add_job(vec![child.id() as i32], "sh -c 'exit 3'");
+
+        cmd.execute(&[format!("%{}", id)]).unwrap();
+        assert_eq!(executor.last_status(), 3);
+
+        cmd.execute(&["%9".to_string()]).unwrap();
+        assert_eq!(executor.last_status(), 127);
+    }
+}
@@ -0,0 +1,165 @@
+use std::borrow::Cow;
+use std::collections::BTreeMap;
+use std::fmt;
+
+/// Where an abbreviation expands.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Position {
+    // Only as a command: first on the line, or after `|`, `&`, `;` or `(`
+    Command,
+    Anywhere,
+}
+
+impl Position {
+    pub fn parse(name: &str) -> Option<Self> {
+        match name {
+            "command" => Some(Position::Command),
+            "anywhere" => Some(Position::Anywhere),
+            _ => None,
+        }
+    }
+}
+
+impl fmt::Display for Position {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Position::Command => write!(f, "command"),
+            Position::Anywhere => write!(f, "anywhere"),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Abbreviation {
+    pub name: String,
+    pub expansion: String,
+    pub position: Position,
+}
+
+impl Abbreviation {
+    pub fn new(name: &str, expansion: &str) -> Self {
+        Self {
+            name: name.to_string(),
+            expansion: expansion.to_string(),
+            position: Position::Command,
+        }
+    }
+
+    pub fn with_position(mut self, position: Position) -> Self {
+        self.position = position;
+        self
+    }
+}
+
+/// Fish-style abbreviations: words the line editor replaces with their
+/// expansion as they're typed, so the full command is what runs and what
+/// the history records.
+#[derive(Debug, Default)]
+pub struct Abbreviations {
+    abbreviations: BTreeMap<String, Abbreviation>,
+}
+
+impl Abbreviations {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn add(&mut self, abbreviation: Abbreviation) {
+        self.abbreviations
+            .insert(abbreviation.name.clone(), abbreviation);
+    }
+
+    /// Removes an abbreviation, reporting whether there was one.
+    pub fn remove(&mut self, name: &str) -> bool {
+        self.abbreviations.remove(name).is_some()
+    }
+
+    pub fn get(&self, name: &str) -> Option<&Abbreviation> {
+        self.abbreviations.get(name)
+    }
+
+    /// All of them, by name.
+    pub fn iter(&self) -> impl Iterator<Item = &Abbreviation> {
+        self.abbreviations.values()
+    }
+
+    /// The expansion for the word in `line` that ends at `end`, with where
+    /// that word starts, if it's an abbreviation in a place it expands.
+    pub fn expansion_at(&self, line: &str, end: usize) -> Option<(usize, &str)> {
+        let before = line.get(..end)?;
+        let start = before
+            .char_indices()
+            .rfind(|(_, c)| c.is_whitespace())
+            .map_or(0, |(space, c)| space + c.len_utf8());
+        let abbreviation = self.abbreviations.get(&before[start..])?;
+        let in_position = match abbreviation.position {
+            Position::Anywhere => true,
+            Position::Command => before[..start]
+                .trim_end()
+                .chars()
+                .last()
+                .is_none_or(|c| matches!(c, '|' | '&' | ';' | '(')),
+        };
+        in_position.then_some((start, abbreviation.expansion.as_str()))
+    }
+
+    /// `line` with its last word expanded, as when it's accepted with that
+    /// word still being typed.
+    pub fn expand_last<'a>(&self, line: &'a str) -> Cow<'a, str> {
+        match self.expansion_at(line, line.len()) {
+            Some((start, expansion)) => Cow::Owned(format!("{}{}", &line[..start], expansion)),
+            None => Cow::Borrowed(line),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn abbreviations() -> Abbreviations {
+        let mut abbreviations = Abbreviations::new();
+        abbreviations.add(Abbreviation::new("gco", "git checkout"));
+        abbreviations.add(Abbreviation::new("L", "| less").with_position(Position::Anywhere));
+        abbreviations
+    }
+
+    #[test]
+    fn test_expansion_at() {
+        let abbreviations = abbreviations();
+        let cases = [
+            ("gco", 3, Some((0, "git checkout"))),
+            ("  gco", 5, Some((2, "git checkout"))),
+            ("make && gco", 11, Some((8, "git checkout"))),
+            ("ls | gco", 8, Some((5, "git checkout"))),
+            ("echo gco", 8, None),
+            ("gco main", 3, Some((0, "git checkout"))),
+            ("gcom", 4, None),
+            ("go", 2, None),
+            ("cat file L", 10, Some((9, "| less"))),
+            ("L", 1, Some((0, "| less"))),
+        ];
+        for (line, end, expected) in cases {
+            assert_eq!(
+                abbreviations.expansion_at(line, end),
+                expected,
+                "{:?}",
+                line
+            );
+        }
+    }
+
+    #[test]
+    fn test_expand_last_and_remove() {
+        let mut abbreviations = abbreviations();
+        assert_eq!(abbreviations.expand_last("gco"), "git checkout");
+        assert_eq!(abbreviations.expand_last("cat x L"), "cat x | less");
+        assert_eq!(abbreviations.expand_last("gco "), "gco ");
+        assert_eq!(abbreviations.expand_last(""), "");
+
+        assert!(abbreviations.remove("gco"));
+        assert!(!abbreviations.remove("gco"));
+        assert_eq!(abbreviations.expand_last("gco"), "gco");
+        assert_eq!(abbreviations.iter().count(), 1);
+    }
+}
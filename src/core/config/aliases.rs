@@ -5,6 +5,12 @@ pub struct AliasManager {
     aliases: HashMap<Box<str>, Box<str>>,
 }
 
+impl Default for AliasManager {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 impl AliasManager {
     pub fn new() -> Self {
         Self {
@@ -16,19 +22,21 @@ impl AliasManager {
         self.aliases.insert(name.into(), command.into());
     }
 
+    /// Removes an alias, reporting whether there was one.
+    pub fn remove(&mut self, name: &str) -> bool {
+        self.aliases.remove(name).is_some()
+    }
+
+    pub fn clear(&mut self) {
+        self.aliases.clear();
+    }
+
     pub fn get<'a>(&'a self, cmd: &str) -> Option<Cow<'a, str>> {
         self.aliases.get(cmd).map(|s| Cow::Borrowed(&**s))
     }
 
     pub fn expand_command<'a>(&'a self, command: &'a str) -> Cow<'a, str> {
-        let mut parts: Vec<&str> = command.split_whitespace().collect();
-        if let Some(first_word) = parts.first() {
-            if let Some(alias_value) = self.get(first_word) {
-                parts[0] = &alias_value;
-                return Cow::Owned(parts.join(" "));
-            }
-        }
-        Cow::Borrowed(command)
+        expand_aliases(command, |word| self.aliases.get(word).map(|value| &**value))
     }
 
     pub fn get_all(&self) -> BTreeMap<Cow<'_, str>, Cow<'_, str>> {
@@ -37,6 +45,71 @@ impl AliasManager {
             .map(|(k, v)| (Cow::Borrowed(&**k), Cow::Borrowed(&**v)))
             .collect()
     }
+
+    /// The aliases as they are now, to use once the store is unlocked.
+    pub fn snapshot(&self) -> BTreeMap<Cow<'static, str>, Cow<'static, str>> {
+        self.aliases
+            .iter()
+            .map(|(k, v)| (Cow::Owned(k.to_string()), Cow::Owned(v.to_string())))
+            .collect()
+    }
+}
+
+/// Expands the alias a command starts with, as bash does: the value's own
+/// first word is expanded in turn unless that alias is already being
+/// expanded, and a value ending in a blank has the word after it checked
+/// as well.
+pub fn expand_aliases<'v>(command: &str, lookup: impl Fn(&str) -> Option<&'v str>) -> Cow<'_, str> {
+    let mut line = Cow::Borrowed(command);
+    let mut position = 0;
+    loop {
+        let start = word_start(&line, position);
+        // What follows the word stays put however it's expanded
+        let after = line.len() - (start + word_at(&line, start).len());
+        match expand_word(&mut line, start, after, &lookup) {
+            Some(true) => position = line.len() - after,
+            _ => return line,
+        }
+    }
+}
+
+// Expands the word at `start` until it's no longer an alias, or one already
+// expanded here. Returns whether anything was, and if so whether a value
+// ended in a blank.
+fn expand_word<'v>(
+    line: &mut Cow<'_, str>,
+    start: usize,
+    after: usize,
+    lookup: impl Fn(&str) -> Option<&'v str>,
+) -> Option<bool> {
+    let mut expanded: Vec<String> = Vec::new();
+    let mut chains = false;
+    loop {
+        let word = word_at(line, start);
+        // Only the words the aliases expanded to, not the arguments after
+        let within = start + word.len() <= line.len() - after;
+        if word.is_empty() || !within || expanded.iter().any(|name| name == word) {
+            break;
+        }
+        let Some(value) = lookup(word) else {
+            break;
+        };
+        expanded.push(word.to_string());
+        let end = start + word.len();
+        line.to_mut().replace_range(start..end, value);
+        chains |= value.ends_with([' ', '\t']);
+    }
+    (!expanded.is_empty()).then_some(chains)
+}
+
+fn word_start(line: &str, from: usize) -> usize {
+    let rest = &line[from..];
+    from + (rest.len() - rest.trim_start().len())
+}
+
+fn word_at(line: &str, start: usize) -> &str {
+    let rest = &line[start..];
+    &rest[..rest.find(char::is_whitespace).unwrap_or(rest.len())]
 }
 
 #[cfg(test)]
@@ -68,6 +141,35 @@ mod tests {
         assert_eq!(expanded, command);
     }
 
+    #[test]
+    fn test_expand_recursively() {
+        let mut manager = AliasManager::new();
+        manager.add("ll", "ls -la");
+        manager.add("l", "ll");
+        manager.add("ls", "ls --color");
+        manager.add("loop1", "loop2 x");
+        manager.add("loop2", "loop1 y");
+        manager.add("sudo", "sudo ");
+        manager.add("nice", "nice -n5 ");
+        manager.add("empty", "");
+
+        let cases = [
+            ("l /tmp", "ls --color -la /tmp"),
+            ("loop1", "loop1 y x"),
+            ("sudo ll", "sudo  ls --color -la"),
+            ("sudo nice l ~", "sudo  nice -n5  ls --color -la ~"),
+            ("echo ll", "echo ll"),
+            ("ll ll", "ls --color -la ll"),
+            ("empty ll", " ll"),
+        ];
+        for (command, expected) in cases {
+            assert_eq!(manager.expand_command(command), expected, "{:?}", command);
+        }
+        assert!(manager.remove("ll"));
+        assert!(!manager.remove("ll"));
+        assert_eq!(manager.expand_command("l"), "ll");
+    }
+
     #[test]
     fn test_get_all() {
         let mut manager = AliasManager::new();
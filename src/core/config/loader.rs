@@ -12,11 +12,31 @@ impl<'a> ConfigLoader<'a> {
     }
 
     pub fn load_configs(&self, config: &mut Config) -> Result<(), ConfigError> {
-        self.source_if_exists(&self.paths.profile_path, config)?;
-        self.source_if_exists(&self.paths.rc_path, config)?;
+        if self.paths.load_profile {
+            self.source_startup_file(&self.paths.system_profile_path, config);
+            self.source_startup_file(&self.paths.profile_path, config);
+        }
+        if self.paths.load_rc {
+            if self.paths.explicit_rc && !self.paths.rc_path.exists() {
+                return Err(ConfigError::ConfigFileNotFound(
+                    self.paths.rc_path.display().to_string(),
+                ));
+            }
+            self.source_startup_file(&self.paths.rc_path, config);
+        }
         Ok(())
     }
 
+    // A broken startup file is reported but doesn't keep the shell from starting
+    fn source_startup_file(&self, path: &Path, config: &mut Config) {
+        if config.debug && path.exists() {
+            eprintln!("aorta: debug: reading {}", path.display());
+        }
+        if let Err(e) = self.source_if_exists(path, config) {
+            eprintln!("aorta: {}: {}", path.display(), e);
+        }
+    }
+
     fn source_if_exists(&self, path: &Path, config: &mut Config) -> Result<(), ConfigError> {
         if path.exists() {
             let content = fs::read_to_string(path)?;
@@ -99,7 +119,7 @@ impl<'a> ConfigLoader<'a> {
                 command = &command[1..command.len() - 1];
             }
 
-            config.aliases.add(name, command);
+            config.aliases().add(name, command);
         }
         Ok(())
     }
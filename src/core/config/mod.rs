@@ -1,27 +1,45 @@
-use std::{borrow::Cow, collections::BTreeMap, fmt};
-
+use std::{
+    borrow::Cow,
+    collections::BTreeMap,
+    fmt, fs,
+    path::Path,
+    sync::{Arc, Mutex, MutexGuard},
+    time::SystemTime,
+};
+
+mod abbreviations;
 mod aliases;
 mod env_vars;
 mod loader;
 mod paths;
+mod settings;
+
+pub use abbreviations::{Abbreviation, Abbreviations, Position};
+pub use aliases::{expand_aliases, AliasManager};
+pub use settings::{ColorSettings, GitSettings, KeyBinding, MotdSettings, Settings, SettingsError};
 
 use super::commands::{CommandError, CommandExecutor};
-use aliases::AliasManager;
+use crate::flags::Flags;
 use env_vars::EnvVarManager;
 use loader::ConfigLoader;
 use paths::ConfigPaths;
 
 pub struct Config {
     paths: ConfigPaths,
-    aliases: AliasManager,
+    // Shared with the executor once it's given, for the `alias` builtin
+    aliases: Arc<Mutex<AliasManager>>,
     env_vars: EnvVarManager,
     executor: Option<CommandExecutor>,
+    debug: bool,
+    settings: Settings,
+    // Modification time of the settings file when it was last read
+    settings_modified: Option<SystemTime>,
 }
 
 impl Config {
     pub fn new() -> Result<Self, ConfigError> {
         let paths = ConfigPaths::new()?;
-        let aliases = AliasManager::new();
+        let aliases = Arc::new(Mutex::new(AliasManager::new()));
         let env_vars = EnvVarManager::new();
 
         Ok(Config {
@@ -29,10 +47,21 @@ impl Config {
             aliases,
             env_vars,
             executor: None,
+            debug: false,
+            settings: Settings::default(),
+            settings_modified: None,
         })
     }
 
+    /// Selects the startup files from the command-line flags.
+    pub fn with_flags(mut self, flags: &Flags) -> Self {
+        self.paths = self.paths.with_flags(flags);
+        self.debug = flags.is_set("debug");
+        self
+    }
+
     pub fn with_executor(mut self, executor: CommandExecutor) -> Self {
+        self.aliases = executor.aliases();
         self.executor = Some(executor);
         self
     }
@@ -61,22 +90,68 @@ impl Config {
     }
 
     pub fn load(&mut self) -> Result<(), ConfigError> {
+        // Settings come first so that the rc file can still override them
+        if let Err(e) = self.load_settings() {
+            eprintln!("aorta: {}", e);
+        }
+
         let paths = self.paths.clone();
         let loader: ConfigLoader<'_> = ConfigLoader::new(&paths);
         loader.load_configs(self)?;
         Ok(())
     }
 
-    pub fn get_alias<'a>(&'a self, cmd: &str) -> Option<Cow<'a, str>> {
-        self.aliases.get(cmd)
+    pub fn settings(&self) -> &Settings {
+        &self.settings
+    }
+
+    /// Re-reads the settings file if it changed since it was last read and
+    /// returns whether the settings differ. Invalid settings are reported
+    /// and the previous ones stay in effect.
+    pub fn reload_settings(&mut self) -> Result<bool, ConfigError> {
+        if modified(&self.paths.settings_path) == self.settings_modified {
+            return Ok(false);
+        }
+        let previous = self.settings.clone();
+        self.load_settings()?;
+        Ok(self.settings != previous)
+    }
+
+    fn load_settings(&mut self) -> Result<(), ConfigError> {
+        let path = &self.paths.settings_path;
+        self.settings_modified = modified(path);
+        if !path.exists() {
+            self.settings = Settings::default();
+            return Ok(());
+        }
+        if self.debug {
+            eprintln!("aorta: debug: reading {}", path.display());
+        }
+
+        let text = fs::read_to_string(path)?;
+        self.settings = Settings::parse(&text)
+            .map_err(|e| ConfigError::InvalidSettings(path.display().to_string(), e))?;
+        Ok(())
+    }
+
+    fn aliases(&self) -> MutexGuard<'_, AliasManager> {
+        self.aliases.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    pub fn get_alias(&self, cmd: &str) -> Option<Cow<'_, str>> {
+        let alias = self.aliases().get(cmd).map(Cow::into_owned);
+        alias.map(Cow::Owned)
     }
 
-    pub fn expand_aliases<'a>(&'a self, command: &'a str) -> Cow<'a, str> {
-        self.aliases.expand_command(command)
+    pub fn expand_aliases<'a>(&self, command: &'a str) -> Cow<'a, str> {
+        match self.aliases().expand_command(command) {
+            Cow::Borrowed(_) => Cow::Borrowed(command),
+            Cow::Owned(expanded) => Cow::Owned(expanded),
+        }
     }
 
     pub fn get_aliases(&self) -> BTreeMap<Cow<'_, str>, Cow<'_, str>> {
-        self.aliases.get_all()
+        self.aliases().snapshot()
     }
 }
 
@@ -87,6 +162,11 @@ pub enum ConfigError {
     ConfigFileNotFound(String),
     IoError(std::io::Error),
     CommandError(CommandError),
+    InvalidSettings(String, SettingsError),
+}
+
+fn modified(path: &Path) -> Option<SystemTime> {
+    fs::metadata(path).and_then(|meta| meta.modified()).ok()
 }
 
 impl From<std::io::Error> for ConfigError {
@@ -111,6 +191,7 @@ impl fmt::Display for ConfigError {
             ConfigError::ConfigFileNotFound(path) => write!(f, "Config file not found: {}", path),
             ConfigError::IoError(e) => write!(f, "IO error: {}", e),
             ConfigError::CommandError(e) => write!(f, "Command error: {}", e),
+            ConfigError::InvalidSettings(path, e) => write!(f, "{}: {}", path, e),
         }
     }
 }
@@ -1,4 +1,7 @@
 use super::ConfigError;
+use crate::core::env::EnvPaths;
+use crate::flags::Flags;
+use crate::path::PathExpander;
 use std::env;
 use std::path::PathBuf;
 
@@ -6,18 +9,49 @@ use std::path::PathBuf;
 pub struct ConfigPaths {
     pub rc_path: PathBuf,
     pub profile_path: PathBuf,
+    pub system_profile_path: PathBuf,
+    // Declarative settings, `$XDG_CONFIG_HOME/aorta/aorta.toml`
+    pub settings_path: PathBuf,
+    // Which of the files above this shell reads at startup
+    pub load_rc: bool,
+    pub load_profile: bool,
+    // The rc file was given with `--config`, so it must exist
+    pub explicit_rc: bool,
 }
 
 impl ConfigPaths {
     pub fn new() -> Result<Self, ConfigError> {
         let home = env::var("HOME").map_err(|_| ConfigError::HomeDirNotFound)?;
         let home_path = PathBuf::from(home);
+        let config_dir = EnvPaths::new()
+            .map(|paths| paths.config_dir().to_path_buf())
+            .unwrap_or_else(|_| home_path.join(".config"));
 
         Ok(ConfigPaths {
+            settings_path: config_dir.join("aorta").join("aorta.toml"),
             rc_path: home_path.join(".aortarc"),
             profile_path: home_path.join(".profile"),
+            system_profile_path: PathBuf::from("/etc/profile"),
+            load_rc: true,
+            load_profile: true,
+            explicit_rc: false,
         })
     }
+
+    /// Applies the startup flags: `--config` replaces the rc file, the
+    /// profiles are only read by login shells and the rc file only by
+    /// interactive ones, unless it was named explicitly.
+    pub fn with_flags(mut self, flags: &Flags) -> Self {
+        if let Some(path) = flags.get_value("config") {
+            self.rc_path = PathExpander::new()
+                .expand(path)
+                .unwrap_or_else(|_| PathBuf::from(path));
+            self.explicit_rc = true;
+        }
+        self.load_profile = flags.is_set("login") && !flags.is_set("noprofile");
+        self.load_rc = !flags.is_set("norc") && (flags.is_set("interactive") || self.explicit_rc);
+        self
+    }
 }
 
 #[cfg(test)]
@@ -34,6 +68,38 @@ mod tests {
         assert_eq!(paths.profile_path, PathBuf::from("/home/testuser/.profile"));
     }
 
+    #[test]
+    fn test_with_flags() {
+        let base = ConfigPaths {
+            rc_path: PathBuf::from("/home/u/.aortarc"),
+            profile_path: PathBuf::from("/home/u/.profile"),
+            system_profile_path: PathBuf::from("/etc/profile"),
+            settings_path: PathBuf::from("/home/u/.config/aorta/aorta.toml"),
+            load_rc: true,
+            load_profile: true,
+            explicit_rc: false,
+        };
+
+        let mut flags = Flags::new();
+        flags.set("interactive");
+        let paths = base.clone().with_flags(&flags);
+        assert!(paths.load_rc && !paths.load_profile);
+
+        let mut flags = Flags::new();
+        flags.parse(&["--login".into(), "--norc".into()]).unwrap();
+        let paths = base.clone().with_flags(&flags);
+        assert!(!paths.load_rc && paths.load_profile);
+
+        let mut flags = Flags::new();
+        flags
+            .parse(&["-c".into(), "/tmp/custom_rc".into(), "--noprofile".into()])
+            .unwrap();
+        flags.set("login");
+        let paths = base.with_flags(&flags);
+        assert_eq!(paths.rc_path, PathBuf::from("/tmp/custom_rc"));
+        assert!(paths.load_rc && paths.explicit_rc && !paths.load_profile);
+    }
+
     #[test]
     fn test_missing_home() {
         env::remove_var("HOME");
@@ -0,0 +1,767 @@
+use super::abbreviations::{Abbreviation, Position};
+use crate::path::PathExpander;
+use inksac::Color;
+use rustyline::{
+    Anchor, At, Cmd, CompletionType, EditMode, KeyCode, KeyEvent, Modifiers, Movement, Word,
+};
+use std::fmt;
+use std::path::PathBuf;
+use toml::{Table, Value};
+
+/// An error in the settings file, naming the offending key once it's known.
+#[derive(Debug, Clone, PartialEq)]
+pub struct SettingsError {
+    pub key: Option<String>,
+    pub message: String,
+}
+
+impl fmt::Display for SettingsError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match &self.key {
+            Some(key) => write!(f, "{}: {}", key, self.message),
+            None => write!(f, "{}", self.message),
+        }
+    }
+}
+
+// A value with the keys leading to it through the tables, as in
+// `history.size`
+struct Entry<'a> {
+    path: Vec<&'a str>,
+    value: &'a Value,
+}
+
+impl Entry<'_> {
+    fn key(&self) -> String {
+        self.path.join(".")
+    }
+}
+
+// The values in `table` and the tables inside it, in file order
+fn flatten<'a>(table: &'a Table, path: &[&'a str], entries: &mut Vec<Entry<'a>>) {
+    for (key, value) in table {
+        let mut path = path.to_vec();
+        path.push(key);
+        match value {
+            Value::Table(table) => flatten(table, &path, entries),
+            value => entries.push(Entry { path, value }),
+        }
+    }
+}
+
+// Where a syntax error is, as a line number
+fn syntax_error(input: &str, error: &toml::de::Error) -> SettingsError {
+    let message = match error.span() {
+        Some(span) => {
+            let line = input[..span.start].matches('\n').count() + 1;
+            format!("line {}: {}", line, error.message())
+        }
+        None => error.message().to_string(),
+    };
+    SettingsError { key: None, message }
+}
+
+/// The declarative settings read from `aorta.toml`. Unset keys keep these
+/// defaults; shell code still belongs in `~/.aortarc`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Settings {
+    pub history: HistorySettings,
+    pub prompt: PromptSettings,
+    pub colors: ColorSettings,
+    pub keybindings: Vec<KeyBinding>,
+    // `[abbreviations]`, and `[abbreviations.anywhere]` for ones that
+    // expand past the command
+    pub abbreviations: Vec<Abbreviation>,
+    pub completion: CompletionSettings,
+    pub edit_mode: EditMode,
+    pub motd: MotdSettings,
+    pub git: GitSettings,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct HistorySettings {
+    pub size: usize,
+    // Defaults to ~/.aorta_history
+    pub path: Option<PathBuf>,
+    // Pick up commands other sessions add to the file before each prompt,
+    // rather than only on `history -n`
+    pub shared: bool,
+    // Expand `!!`, `!$`, `^old^new` and the like in typed commands
+    pub expand: bool,
+    // Put an expanded command back in the editor rather than running it
+    pub verify: bool,
+    // HISTCONTROL-style options and HISTIGNORE-style patterns, used when
+    // those variables aren't set
+    pub control: Vec<String>,
+    pub ignore: Vec<String>,
+    // Write likely passwords and tokens as REDACTED
+    pub redact_secrets: bool,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct PromptSettings {
+    // Used when `PS1` and `PS2` aren't set
+    pub format: Option<String>,
+    pub continuation: Option<String>,
+    // Used when `RPROMPT` isn't set; empty turns the right prompt off
+    pub right: Option<String>,
+    // Run before each prompt, after `PROMPT_COMMAND`
+    pub precmd: Option<String>,
+    // Commands that ran shorter than this don't show their duration
+    pub duration_threshold_ms: u64,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ColorSettings {
+    pub enabled: bool,
+    pub command: Color,
+    pub flag: Color,
+    pub error: Color,
+    pub success: Color,
+    pub hint: Color,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct KeyBinding {
+    pub key: KeyEvent,
+    pub action: Cmd,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct CompletionSettings {
+    pub mode: CompletionType,
+    // Ask before listing more candidates than this
+    pub max_items: usize,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct MotdSettings {
+    pub enabled: bool,
+    // Defaults to ~/.aorta_motd, then `motd` in the config directory
+    pub path: Option<PathBuf>,
+    // Run after the file is shown, for output like `fortune`
+    pub command: Option<String>,
+    pub once_per_day: bool,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct GitSettings {
+    pub enabled: bool,
+    // How long `git status` may take before only HEAD and the state are shown
+    pub timeout_ms: u64,
+    // Read the status in the background and redraw the prompt with it,
+    // rather than wait before showing the prompt
+    pub async_status: bool,
+}
+
+impl Default for GitSettings {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            timeout_ms: 200,
+            async_status: true,
+        }
+    }
+}
+
+impl Default for PromptSettings {
+    fn default() -> Self {
+        Self {
+            format: None,
+            continuation: None,
+            right: None,
+            precmd: None,
+            duration_threshold_ms: 2000,
+        }
+    }
+}
+
+impl Default for MotdSettings {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            path: None,
+            command: None,
+            once_per_day: false,
+        }
+    }
+}
+
+impl Default for Settings {
+    fn default() -> Self {
+        Self {
+            history: HistorySettings {
+                size: 1000,
+                path: None,
+                shared: false,
+                expand: true,
+                verify: false,
+                control: Vec::new(),
+                ignore: Vec::new(),
+                redact_secrets: true,
+            },
+            prompt: PromptSettings::default(),
+            colors: ColorSettings::default(),
+            keybindings: Vec::new(),
+            abbreviations: Vec::new(),
+            completion: CompletionSettings {
+                mode: CompletionType::Circular,
+                max_items: 100,
+            },
+            edit_mode: EditMode::Emacs,
+            motd: MotdSettings::default(),
+            git: GitSettings::default(),
+        }
+    }
+}
+
+impl Default for ColorSettings {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            command: Color::Cyan,
+            flag: Color::Yellow,
+            error: Color::Red,
+            success: Color::Green,
+            hint: Color::RGB(128, 128, 128),
+        }
+    }
+}
+
+impl Settings {
+    pub fn parse(input: &str) -> Result<Self, SettingsError> {
+        let table: Table = input.parse().map_err(|e| syntax_error(input, &e))?;
+        let mut entries = Vec::new();
+        flatten(&table, &[], &mut entries);
+
+        let mut settings = Settings::default();
+        for entry in &entries {
+            settings.apply(entry)?;
+        }
+        Ok(settings)
+    }
+
+    fn apply(&mut self, entry: &Entry) -> Result<(), SettingsError> {
+        match entry.path[..] {
+            ["edit_mode"] => {
+                self.edit_mode = match string(entry)? {
+                    "emacs" => EditMode::Emacs,
+                    "vi" => EditMode::Vi,
+                    _ => return Err(invalid(entry, "expected \"emacs\" or \"vi\"")),
+                }
+            }
+            ["history", name] => self.apply_history(name, entry)?,
+            ["prompt", name] => self.prompt.apply(name, entry)?,
+            ["colors", name] => self.colors.apply(name, entry)?,
+            ["keybindings", name] => self.keybindings.push(KeyBinding {
+                key: parse_key(name).ok_or_else(|| invalid(entry, "unknown key"))?,
+                action: parse_action(string(entry)?)
+                    .ok_or_else(|| invalid(entry, "unknown action"))?,
+            }),
+            ["abbreviations", name] => self.add_abbreviation(Position::Command, name, entry)?,
+            ["abbreviations", position, name] => {
+                let position = Position::parse(position)
+                    .ok_or_else(|| invalid(entry, "expected \"command\" or \"anywhere\""))?;
+                self.add_abbreviation(position, name, entry)?
+            }
+            ["completion", name] => self.apply_completion(name, entry)?,
+            ["motd", name] => self.motd.apply(name, entry)?,
+            ["git", name] => self.apply_git(name, entry)?,
+            _ => return Err(invalid(entry, "unknown setting")),
+        }
+        Ok(())
+    }
+
+    fn apply_history(&mut self, name: &str, entry: &Entry) -> Result<(), SettingsError> {
+        match name {
+            "size" => self.history.size = unsigned(entry)?,
+            "path" => self.history.path = Some(path(entry)?),
+            "shared" => self.history.shared = boolean(entry)?,
+            "expand" => self.history.expand = boolean(entry)?,
+            "verify" => self.history.verify = boolean(entry)?,
+            "control" => {
+                let control = strings(entry)?;
+                let known = ["ignorespace", "ignoredups", "ignoreboth", "erasedups"];
+                if let Some(unknown) = control.iter().find(|c| !known.contains(&c.as_str())) {
+                    return Err(invalid(entry, &format!("unknown option \"{}\"", unknown)));
+                }
+                self.history.control = control;
+            }
+            "ignore" => self.history.ignore = strings(entry)?,
+            "redact_secrets" => self.history.redact_secrets = boolean(entry)?,
+            _ => return Err(invalid(entry, "unknown setting")),
+        }
+        Ok(())
+    }
+
+    fn add_abbreviation(
+        &mut self,
+        position: Position,
+        name: &str,
+        entry: &Entry,
+    ) -> Result<(), SettingsError> {
+        let abbreviation = Abbreviation::new(name, string(entry)?).with_position(position);
+        self.abbreviations.push(abbreviation);
+        Ok(())
+    }
+
+    fn apply_git(&mut self, name: &str, entry: &Entry) -> Result<(), SettingsError> {
+        match name {
+            "enabled" => self.git.enabled = boolean(entry)?,
+            "timeout_ms" => self.git.timeout_ms = unsigned(entry)? as u64,
+            "async" => self.git.async_status = boolean(entry)?,
+            _ => return Err(invalid(entry, "unknown setting")),
+        }
+        Ok(())
+    }
+
+    fn apply_completion(&mut self, name: &str, entry: &Entry) -> Result<(), SettingsError> {
+        match name {
+            "mode" => {
+                self.completion.mode = match string(entry)? {
+                    "circular" => CompletionType::Circular,
+                    "list" => CompletionType::List,
+                    _ => return Err(invalid(entry, "expected \"circular\" or \"list\"")),
+                }
+            }
+            "max_items" => self.completion.max_items = unsigned(entry)?,
+            _ => return Err(invalid(entry, "unknown setting")),
+        }
+        Ok(())
+    }
+}
+
+impl PromptSettings {
+    fn apply(&mut self, name: &str, entry: &Entry) -> Result<(), SettingsError> {
+        if name == "duration_threshold_ms" {
+            self.duration_threshold_ms = unsigned(entry)? as u64;
+            return Ok(());
+        }
+        let slot = match name {
+            "format" => &mut self.format,
+            "continuation" => &mut self.continuation,
+            "right" => &mut self.right,
+            "precmd" => &mut self.precmd,
+            _ => return Err(invalid(entry, "unknown setting")),
+        };
+        *slot = Some(string(entry)?.to_string());
+        Ok(())
+    }
+}
+
+impl MotdSettings {
+    fn apply(&mut self, name: &str, entry: &Entry) -> Result<(), SettingsError> {
+        match name {
+            "enabled" => self.enabled = boolean(entry)?,
+            "path" => self.path = Some(path(entry)?),
+            "command" => self.command = Some(string(entry)?.to_string()),
+            "once_per_day" => self.once_per_day = boolean(entry)?,
+            _ => return Err(invalid(entry, "unknown setting")),
+        }
+        Ok(())
+    }
+}
+
+impl ColorSettings {
+    fn apply(&mut self, name: &str, entry: &Entry) -> Result<(), SettingsError> {
+        if name == "enabled" {
+            self.enabled = boolean(entry)?;
+            return Ok(());
+        }
+        let slot = match name {
+            "command" => &mut self.command,
+            "flag" => &mut self.flag,
+            "error" => &mut self.error,
+            "success" => &mut self.success,
+            "hint" => &mut self.hint,
+            _ => return Err(invalid(entry, "unknown setting")),
+        };
+        *slot = parse_color(string(entry)?)
+            .ok_or_else(|| invalid(entry, "expected a color name or \"#rrggbb\""))?;
+        Ok(())
+    }
+}
+
+fn invalid(entry: &Entry, message: &str) -> SettingsError {
+    SettingsError {
+        key: Some(entry.key()),
+        message: message.to_string(),
+    }
+}
+
+fn type_name(value: &Value) -> &'static str {
+    match value {
+        Value::String(_) => "a string",
+        Value::Integer(_) => "an integer",
+        Value::Float(_) => "a float",
+        Value::Boolean(_) => "a boolean",
+        Value::Datetime(_) => "a date",
+        Value::Array(_) => "an array",
+        Value::Table(_) => "a table",
+    }
+}
+
+fn mismatch(entry: &Entry, expected: &str) -> SettingsError {
+    invalid(
+        entry,
+        &format!("expected {}, found {}", expected, type_name(entry.value)),
+    )
+}
+
+fn string<'a>(entry: &Entry<'a>) -> Result<&'a str, SettingsError> {
+    entry
+        .value
+        .as_str()
+        .ok_or_else(|| mismatch(entry, "a string"))
+}
+
+fn strings(entry: &Entry) -> Result<Vec<String>, SettingsError> {
+    let Value::Array(values) = entry.value else {
+        return Err(mismatch(entry, "an array of strings"));
+    };
+    values
+        .iter()
+        .map(|value| match value {
+            Value::String(s) => Ok(s.clone()),
+            _ => Err(invalid(entry, "expected an array of strings")),
+        })
+        .collect()
+}
+
+fn boolean(entry: &Entry) -> Result<bool, SettingsError> {
+    entry
+        .value
+        .as_bool()
+        .ok_or_else(|| mismatch(entry, "a boolean"))
+}
+
+fn unsigned(entry: &Entry) -> Result<usize, SettingsError> {
+    match entry.value {
+        Value::Integer(n) => {
+            usize::try_from(*n).map_err(|_| invalid(entry, "must not be negative"))
+        }
+        _ => Err(mismatch(entry, "an integer")),
+    }
+}
+
+fn path(entry: &Entry) -> Result<PathBuf, SettingsError> {
+    PathExpander::new()
+        .expand(string(entry)?)
+        .map_err(|e| invalid(entry, &e.to_string()))
+}
+
+fn parse_color(name: &str) -> Option<Color> {
+    if let Some(hex) = name.strip_prefix('#') {
+        let channel = |i: usize| u8::from_str_radix(hex.get(i..i + 2)?, 16).ok();
+        if hex.len() != 6 {
+            return None;
+        }
+        return Some(Color::RGB(channel(0)?, channel(2)?, channel(4)?));
+    }
+    Some(match name {
+        "black" => Color::Black,
+        "red" => Color::Red,
+        "green" => Color::Green,
+        "yellow" => Color::Yellow,
+        "blue" => Color::Blue,
+        "magenta" => Color::Magenta,
+        "cyan" => Color::Cyan,
+        "white" => Color::White,
+        "gray" | "grey" => Color::RGB(128, 128, 128),
+        _ => return None,
+    })
+}
+
+/// Parses a key like `ctrl-r`, `alt-f`, `shift-tab`, `up` or `f5`.
+fn parse_key(name: &str) -> Option<KeyEvent> {
+    let (mods, key) = match name.rsplit_once('-') {
+        Some((prefix, key)) if !key.is_empty() => (parse_modifiers(prefix)?, key),
+        _ => (Modifiers::NONE, name),
+    };
+    let code = match key {
+        "up" => KeyCode::Up,
+        "down" => KeyCode::Down,
+        "left" => KeyCode::Left,
+        "right" => KeyCode::Right,
+        "home" => KeyCode::Home,
+        "end" => KeyCode::End,
+        "pageup" => KeyCode::PageUp,
+        "pagedown" => KeyCode::PageDown,
+        "tab" if mods == Modifiers::SHIFT => {
+            return Some(KeyEvent(KeyCode::BackTab, Modifiers::NONE))
+        }
+        "tab" => KeyCode::Tab,
+        "enter" => KeyCode::Enter,
+        "esc" => KeyCode::Esc,
+        "backspace" => KeyCode::Backspace,
+        "delete" => KeyCode::Delete,
+        "insert" => KeyCode::Insert,
+        "space" => KeyCode::Char(' '),
+        f if f.len() > 1 && f.starts_with('f') => KeyCode::F(f[1..].parse().ok()?),
+        // `KeyEvent::new` drops SHIFT from characters, so apply it here
+        c if c.chars().count() == 1 => {
+            let c = c.chars().next()?;
+            let c = if mods.contains(Modifiers::SHIFT) {
+                c.to_ascii_uppercase()
+            } else {
+                c
+            };
+            return Some(KeyEvent::new(c, mods));
+        }
+        _ => return None,
+    };
+    Some(KeyEvent(code, mods))
+}
+
+fn parse_modifiers(prefix: &str) -> Option<Modifiers> {
+    prefix.split('-').try_fold(Modifiers::NONE, |mods, part| {
+        Some(
+            mods | match part {
+                "ctrl" | "c" => Modifiers::CTRL,
+                "alt" | "meta" | "m" => Modifiers::ALT,
+                "shift" | "s" => Modifiers::SHIFT,
+                _ => return None,
+            },
+        )
+    })
+}
+
+/// Maps readline command names to editor commands.
+fn parse_action(name: &str) -> Option<Cmd> {
+    let word = Word::Emacs;
+    Some(match name {
+        "accept-line" => Cmd::AcceptLine,
+        "beginning-of-line" => Cmd::Move(Movement::BeginningOfLine),
+        "end-of-line" => Cmd::Move(Movement::EndOfLine),
+        "forward-char" => Cmd::Move(Movement::ForwardChar(1)),
+        "backward-char" => Cmd::Move(Movement::BackwardChar(1)),
+        "forward-word" => Cmd::Move(Movement::ForwardWord(1, At::AfterEnd, word)),
+        "backward-word" => Cmd::Move(Movement::BackwardWord(1, word)),
+        "kill-line" => Cmd::Kill(Movement::EndOfLine),
+        "unix-line-discard" => Cmd::Kill(Movement::BeginningOfLine),
+        "kill-word" => Cmd::Kill(Movement::ForwardWord(1, At::AfterEnd, word)),
+        "backward-kill-word" => Cmd::Kill(Movement::BackwardWord(1, word)),
+        "yank" => Cmd::Yank(1, Anchor::Before),
+        "yank-pop" => Cmd::YankPop,
+        "undo" => Cmd::Undo(1),
+        "transpose-chars" => Cmd::TransposeChars,
+        "upcase-word" => Cmd::UpcaseWord,
+        "downcase-word" => Cmd::DowncaseWord,
+        "capitalize-word" => Cmd::CapitalizeWord,
+        "clear-screen" => Cmd::ClearScreen,
+        "complete" => Cmd::Complete,
+        "complete-hint" => Cmd::CompleteHint,
+        "previous-history" => Cmd::PreviousHistory,
+        "next-history" => Cmd::NextHistory,
+        "beginning-of-history" => Cmd::BeginningOfHistory,
+        "end-of-history" => Cmd::EndOfHistory,
+        "history-search-backward" => Cmd::HistorySearchBackward,
+        "history-search-forward" => Cmd::HistorySearchForward,
+        "reverse-search-history" => Cmd::ReverseSearchHistory,
+        "forward-search-history" => Cmd::ForwardSearchHistory,
+        "quoted-insert" => Cmd::QuotedInsert,
+        "abort" => Cmd::Abort,
+        "noop" => Cmd::Noop,
+        _ => return None,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_settings() {
+        let settings = Settings::parse(
+            r##"
+edit_mode = "vi"
+
+[history]
+size = 5000
+path = "/tmp/aorta_history"
+
+[prompt]
+format = "\\w $ "
+
+[colors]
+command = "#ff8000"
+enabled = false
+
+[completion]
+mode = "list"
+"##,
+        )
+        .unwrap();
+
+        assert_eq!(settings.edit_mode, EditMode::Vi);
+        assert_eq!(settings.history.size, 5000);
+        assert_eq!(
+            settings.history.path,
+            Some(PathBuf::from("/tmp/aorta_history"))
+        );
+        assert_eq!(settings.prompt.format.as_deref(), Some("\\w $ "));
+        assert_eq!(settings.colors.command, Color::RGB(255, 128, 0));
+        assert!(!settings.colors.enabled);
+        assert_eq!(settings.completion.mode, CompletionType::List);
+        assert_eq!(settings.completion.max_items, 100);
+    }
+
+    #[test]
+    fn test_parse_history_sharing() {
+        let settings = Settings::parse("[history]\nshared = true\nverify = true\n").unwrap();
+        assert!(settings.history.shared);
+        assert!(settings.history.expand);
+        assert!(settings.history.verify);
+    }
+
+    #[test]
+    fn test_parse_history_filter() {
+        let settings = Settings::parse(
+            "[history]\ncontrol = [\"ignoreboth\"]\nignore = [\"ls\", \"cd *\"]\nredact_secrets = false\n",
+        )
+        .unwrap();
+        assert_eq!(settings.history.control, ["ignoreboth"]);
+        assert_eq!(settings.history.ignore, ["ls", "cd *"]);
+        assert!(!settings.history.redact_secrets);
+
+        assert!(Settings::parse("[history]\ncontrol = [\"ignorecase\"]\n").is_err());
+        assert!(Settings::parse("[history]\nignore = [1]\n").is_err());
+    }
+
+    #[test]
+    fn test_parse_abbreviations() {
+        let settings = Settings::parse(
+            "[abbreviations]\ngco = \"git checkout\"\n[abbreviations.anywhere]\nL = \"| less\"\n",
+        )
+        .unwrap();
+        assert_eq!(
+            settings.abbreviations,
+            [
+                Abbreviation::new("gco", "git checkout"),
+                Abbreviation::new("L", "| less").with_position(Position::Anywhere),
+            ]
+        );
+        assert!(Settings::parse("[abbreviations.sometimes]\nx = \"y\"\n").is_err());
+        assert!(Settings::parse("[abbreviations]\nx = 1\n").is_err());
+    }
+
+    #[test]
+    fn test_parse_motd() {
+        let settings =
+            Settings::parse("[motd]\ncommand = \"uptime\"\nonce_per_day = true\n").unwrap();
+        assert_eq!(settings.motd.command.as_deref(), Some("uptime"));
+        assert!(settings.motd.enabled && settings.motd.once_per_day);
+        assert_eq!(settings.motd.path, None);
+
+        let settings = Settings::parse("[git]\ntimeout_ms = 0\nasync = false\n").unwrap();
+        assert_eq!(settings.git.timeout_ms, 0);
+        assert!(settings.git.enabled && !settings.git.async_status);
+
+        let settings =
+            Settings::parse("[prompt]\nright = \"\\\\S\"\nduration_threshold_ms = 500\n").unwrap();
+        assert_eq!(settings.prompt.right.as_deref(), Some("\\S"));
+        assert_eq!(settings.prompt.duration_threshold_ms, 500);
+    }
+
+    #[test]
+    fn test_parse_keybindings() {
+        let settings = Settings::parse(
+            r#"
+[keybindings]
+ctrl-k = "kill-line"
+alt-shift-b = "backward-word"
+up = "history-search-backward"
+"#,
+        )
+        .unwrap();
+
+        assert_eq!(settings.keybindings.len(), 3);
+        assert_eq!(settings.keybindings[0].key, KeyEvent::ctrl('k'));
+        assert_eq!(
+            settings.keybindings[1].key,
+            KeyEvent(KeyCode::Char('B'), Modifiers::ALT)
+        );
+        assert_eq!(settings.keybindings[2].action, Cmd::HistorySearchBackward);
+    }
+
+    #[test]
+    fn test_invalid_settings_name_the_key() {
+        let cases = [
+            (
+                "[history]\nsize = \"big\"\n",
+                "history.size",
+                "expected an integer, found a string",
+            ),
+            (
+                "[history]\nsize = -1\n",
+                "history.size",
+                "must not be negative",
+            ),
+            (
+                "edit_mode = \"ed\"\n",
+                "edit_mode",
+                "expected \"emacs\" or \"vi\"",
+            ),
+            (
+                "[colors]\nflag = \"plaid\"\n",
+                "colors.flag",
+                "expected a color name or \"#rrggbb\"",
+            ),
+            (
+                "[keybindings]\nhyper-x = \"undo\"\n",
+                "keybindings.hyper-x",
+                "unknown key",
+            ),
+            (
+                "[keybindings]\nctrl-x = \"explode\"\n",
+                "keybindings.ctrl-x",
+                "unknown action",
+            ),
+            ("[histroy]\nsize = 1\n", "histroy.size", "unknown setting"),
+        ];
+        for (input, key, message) in cases {
+            let err = Settings::parse(input).unwrap_err();
+            assert_eq!(err.key.as_deref(), Some(key), "{}", input);
+            assert_eq!(err.message, message, "{}", input);
+        }
+    }
+
+    #[test]
+    fn test_parse_full_toml() {
+        let settings = Settings::parse(
+            r#"
+history = { size = 2_000, ignore = [
+    "ls",
+    "cd *",   # trailing commas and comments
+] }
+prompt.format = "\e[1m\\w\e[0m $ "
+abbreviations.anywhere = { L = "| less" }
+"#,
+        )
+        .unwrap();
+        assert_eq!(settings.history.size, 2000);
+        assert_eq!(settings.history.ignore, ["ls", "cd *"]);
+        assert_eq!(
+            settings.prompt.format.as_deref(),
+            Some("\x1b[1m\\w\x1b[0m $ ")
+        );
+        assert_eq!(
+            settings.abbreviations,
+            [Abbreviation::new("L", "| less").with_position(Position::Anywhere)]
+        );
+    }
+
+    #[test]
+    fn test_syntax_errors_give_the_line() {
+        let err = Settings::parse("[history]\nsize = lots\n").unwrap_err();
+        assert_eq!(err.key, None);
+        assert!(err.message.starts_with("line 2: "), "{}", err.message);
+        assert!(Settings::parse("edit_mode = \"vi\"\nedit_mode = \"emacs\"\n").is_err());
+        assert!(Settings::parse("[history\n").is_err());
+    }
+
+    #[test]
+    fn test_empty_settings_are_defaults() {
+        assert_eq!(Settings::parse("# nothing\n").unwrap(), Settings::default());
+    }
+}
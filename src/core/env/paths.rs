@@ -1,4 +1,5 @@
 use super::EnvError;
+use std::ffi::OsString;
 use std::path::{Path, PathBuf};
 
 #[derive(Debug, Clone)]
@@ -19,8 +20,8 @@ impl EnvPaths {
             return Err(EnvError::InvalidPath(home));
         }
 
-        let config_dir = home.join(".config");
-        let cache_dir = home.join(".cache");
+        let config_dir = xdg_dir(std::env::var_os("XDG_CONFIG_HOME"), home.join(".config"));
+        let cache_dir = xdg_dir(std::env::var_os("XDG_CACHE_HOME"), home.join(".cache"));
 
         Ok(Self {
             home,
@@ -56,6 +57,14 @@ impl EnvPaths {
     }
 }
 
+// The XDG base directory spec ignores unset, empty and relative values
+fn xdg_dir(value: Option<OsString>, fallback: PathBuf) -> PathBuf {
+    value
+        .map(PathBuf::from)
+        .filter(|path| path.is_absolute())
+        .unwrap_or(fallback)
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -83,4 +92,16 @@ mod tests {
 
         Ok(())
     }
+
+    #[test]
+    fn test_xdg_dir() {
+        let fallback = PathBuf::from("/home/u/.config");
+        assert_eq!(
+            xdg_dir(Some("/xdg/config".into()), fallback.clone()),
+            PathBuf::from("/xdg/config")
+        );
+        assert_eq!(xdg_dir(Some("relative".into()), fallback.clone()), fallback);
+        assert_eq!(xdg_dir(Some("".into()), fallback.clone()), fallback);
+        assert_eq!(xdg_dir(None, fallback.clone()), fallback);
+    }
 }
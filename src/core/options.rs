@@ -0,0 +1,167 @@
+use std::collections::BTreeMap;
+use std::fmt;
+
+/// `set -o` options with their single-letter flag, if they have one.
+const SET_OPTIONS: &[(&str, Option<char>)] = &[
+    ("errexit", Some('e')),
+    ("noclobber", Some('C')),
+    ("noexec", Some('n')),
+    ("noglob", Some('f')),
+    ("nounset", Some('u')),
+    ("pipefail", None),
+    ("xtrace", Some('x')),
+];
+
+/// `shopt` options and their defaults.
+const SHOPT_OPTIONS: &[(&str, bool)] = &[
+    ("dotglob", false),
+    ("expand_aliases", true),
+    ("nullglob", false),
+];
+
+#[derive(Debug)]
+pub enum OptionError {
+    InvalidName(String),
+    InvalidFlag(char),
+}
+
+impl fmt::Display for OptionError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            OptionError::InvalidName(name) => write!(f, "{}: invalid option name", name),
+            OptionError::InvalidFlag(flag) => write!(f, "-{}: invalid option", flag),
+        }
+    }
+}
+
+/// The shell's option table, toggled by `set` and `shopt`.
+#[derive(Debug, Clone)]
+pub struct ShellOptions {
+    set_options: BTreeMap<&'static str, bool>,
+    shopt_options: BTreeMap<&'static str, bool>,
+}
+
+impl Default for ShellOptions {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl ShellOptions {
+    pub fn new() -> Self {
+        Self {
+            set_options: SET_OPTIONS.iter().map(|(name, _)| (*name, false)).collect(),
+            shopt_options: SHOPT_OPTIONS.iter().copied().collect(),
+        }
+    }
+
+    pub fn is_set(&self, name: &str) -> bool {
+        self.set_options.get(name).copied().unwrap_or(false)
+    }
+
+    pub fn set(&mut self, name: &str, enabled: bool) -> Result<(), OptionError> {
+        match self.set_options.get_mut(name) {
+            Some(value) => {
+                *value = enabled;
+                Ok(())
+            }
+            None => Err(OptionError::InvalidName(name.to_string())),
+        }
+    }
+
+    pub fn set_flag(&mut self, flag: char, enabled: bool) -> Result<(), OptionError> {
+        let name = SET_OPTIONS
+            .iter()
+            .find(|(_, short)| *short == Some(flag))
+            .map(|(name, _)| *name)
+            .ok_or(OptionError::InvalidFlag(flag))?;
+        self.set(name, enabled)
+    }
+
+    /// The active single-letter flags, as shown by `$-`.
+    pub fn flags(&self) -> String {
+        SET_OPTIONS
+            .iter()
+            .filter(|(name, _)| self.is_set(name))
+            .filter_map(|(_, short)| *short)
+            .collect()
+    }
+
+    pub fn set_options(&self) -> impl Iterator<Item = (&'static str, bool)> + '_ {
+        self.set_options.iter().map(|(name, value)| (*name, *value))
+    }
+
+    pub fn is_shopt(&self, name: &str) -> bool {
+        self.shopt_options.get(name).copied().unwrap_or(false)
+    }
+
+    pub fn set_shopt(&mut self, name: &str, enabled: bool) -> Result<(), OptionError> {
+        match self.shopt_options.get_mut(name) {
+            Some(value) => {
+                *value = enabled;
+                Ok(())
+            }
+            None => Err(OptionError::InvalidName(name.to_string())),
+        }
+    }
+
+    pub fn shopt_options(&self) -> impl Iterator<Item = (&'static str, bool)> + '_ {
+        self.shopt_options
+            .iter()
+            .map(|(name, value)| (*name, *value))
+    }
+
+    pub fn errexit(&self) -> bool {
+        self.is_set("errexit")
+    }
+
+    pub fn nounset(&self) -> bool {
+        self.is_set("nounset")
+    }
+
+    pub fn xtrace(&self) -> bool {
+        self.is_set("xtrace")
+    }
+
+    pub fn pipefail(&self) -> bool {
+        self.is_set("pipefail")
+    }
+
+    pub fn noclobber(&self) -> bool {
+        self.is_set("noclobber")
+    }
+
+    pub fn noglob(&self) -> bool {
+        self.is_set("noglob")
+    }
+
+    pub fn noexec(&self) -> bool {
+        self.is_set("noexec")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_defaults() {
+        let options = ShellOptions::new();
+        assert!(!options.errexit());
+        assert!(options.is_shopt("expand_aliases"));
+        assert_eq!(options.flags(), "");
+    }
+
+    #[test]
+    fn test_set_by_flag_and_name() {
+        let mut options = ShellOptions::new();
+        options.set_flag('e', true).unwrap();
+        options.set_flag('u', true).unwrap();
+        options.set("pipefail", true).unwrap();
+
+        assert!(options.errexit() && options.nounset() && options.pipefail());
+        assert_eq!(options.flags(), "eu");
+        assert!(options.set_flag('z', true).is_err());
+        assert!(options.set("nonsense", true).is_err());
+    }
+}
@@ -14,7 +14,6 @@ pub enum ShellError {
     ProcessError(ProcessError),
     ConfigError(ConfigError),
     FlagError(String),
-    CtrlC(String),
     CommandError(CommandError),
     HistoryError(HistoryError),
     PipelineError(PipelineError),
@@ -36,12 +35,6 @@ impl From<std::io::Error> for ShellError {
     }
 }
 
-impl From<ctrlc::Error> for ShellError {
-    fn from(err: ctrlc::Error) -> Self {
-        ShellError::CtrlC(err.to_string())
-    }
-}
-
 impl From<ProcessError> for ShellError {
     fn from(err: ProcessError) -> Self {
         ShellError::ProcessError(err)
@@ -82,7 +75,6 @@ impl std::fmt::Display for ShellError {
             ShellError::CommandNotFound(cmd) => write!(f, "command not found: {}", cmd),
             ShellError::ConfigError(e) => write!(f, "Config error: {}", e),
             ShellError::FlagError(msg) => write!(f, "Flag error: {}", msg),
-            ShellError::CtrlC(msg) => write!(f, "Ctrl-C error: {}", msg),
             ShellError::ProcessError(e) => write!(f, "Process error: {}", e),
             ShellError::CommandError(e) => write!(f, "Command error: {}", e),
             ShellError::HistoryError(e) => write!(f, "History error: {}", e),
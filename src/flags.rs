@@ -75,6 +75,57 @@ impl Flags {
             },
         );
 
+        flags.insert(
+            "login".to_string(),
+            Flag {
+                short: "-l".to_string(),
+                long: "--login".to_string(),
+                description: "Act as a login shell and read the profile files".to_string(),
+                value: None,
+            },
+        );
+
+        flags.insert(
+            "interactive".to_string(),
+            Flag {
+                short: "-i".to_string(),
+                long: "--interactive".to_string(),
+                description: "Force an interactive shell".to_string(),
+                value: None,
+            },
+        );
+
+        flags.insert(
+            "stdin".to_string(),
+            Flag {
+                short: "-s".to_string(),
+                long: "--stdin".to_string(),
+                description: "Read commands from standard input".to_string(),
+                value: None,
+            },
+        );
+
+        // Long-only flags have an empty short form
+        flags.insert(
+            "norc".to_string(),
+            Flag {
+                short: String::new(),
+                long: "--norc".to_string(),
+                description: "Do not read the rc file".to_string(),
+                value: None,
+            },
+        );
+
+        flags.insert(
+            "noprofile".to_string(),
+            Flag {
+                short: String::new(),
+                long: "--noprofile".to_string(),
+                description: "Do not read /etc/profile or ~/.profile".to_string(),
+                value: None,
+            },
+        );
+
         Flags { flags }
     }
 
@@ -85,7 +136,7 @@ impl Flags {
 
             // Check for both short and long flags
             for flag in self.flags.values_mut() {
-                if arg == &flag.short || arg == &flag.long {
+                if (!flag.short.is_empty() && arg == &flag.short) || arg == &flag.long {
                     // Check if the flag expects a value
                     if arg == "-c" || arg == "--config" {
                         if i + 1 < args.len() {
@@ -114,6 +165,14 @@ impl Flags {
             .is_some()
     }
 
+    /// Turns on a switch flag, for modes detected rather than passed, such
+    /// as a login shell started with a leading `-` in argv[0].
+    pub fn set(&mut self, name: &str) {
+        if let Some(flag) = self.flags.get_mut(name) {
+            flag.value = Some("true".to_string());
+        }
+    }
+
     pub fn get_value(&self, name: &str) -> Option<&String> {
         self.flags.get(name).and_then(|f| f.value.as_ref())
     }
@@ -122,7 +181,12 @@ impl Flags {
         println!("Usage: aorta [OPTIONS]");
         println!("\nOptions:");
         for flag in self.flags.values() {
-            println!("  {}, {:<15} {}", flag.short, flag.long, flag.description);
+            let short = if flag.short.is_empty() {
+                "   ".to_string()
+            } else {
+                format!("{},", flag.short)
+            };
+            println!("  {} {:<15} {}", short, flag.long, flag.description);
         }
     }
 }
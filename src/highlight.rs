@@ -1,8 +1,10 @@
+use crate::core::config::ColorSettings;
 use inksac::prelude::*;
 
 #[derive(Debug, Clone, Copy)]
 pub struct SyntaxHighlighter {
     color_support: ColorSupport,
+    colors: ColorSettings,
 }
 
 impl Default for SyntaxHighlighter {
@@ -16,11 +18,22 @@ impl SyntaxHighlighter {
         let support = check_color_support().unwrap_or(ColorSupport::NoColor);
         Self {
             color_support: support,
+            colors: ColorSettings::default(),
         }
     }
 
+    /// Uses the colors from the `[colors]` settings.
+    pub fn with_colors(mut self, colors: ColorSettings) -> Self {
+        self.colors = colors;
+        self
+    }
+
+    fn no_color(&self) -> bool {
+        !self.colors.enabled || matches!(self.color_support, ColorSupport::NoColor)
+    }
+
     pub fn highlight_command(&self, input: &str) -> String {
-        if matches!(self.color_support, ColorSupport::NoColor) {
+        if self.no_color() {
             return input.to_string();
         }
 
@@ -29,14 +42,17 @@ impl SyntaxHighlighter {
             return input.to_string();
         }
 
-        // Highlight command name in cyan
-        let command_style = Style::builder().foreground(Color::Cyan).bold().build();
+        // Highlight command name, cyan by default
+        let command_style = Style::builder()
+            .foreground(self.colors.command)
+            .bold()
+            .build();
         parts[0] = parts[0].clone().style(command_style).to_string();
 
-        // Highlight flags/options in yellow
+        // Highlight flags/options, yellow by default
         for part in parts.iter_mut().skip(1) {
             if part.starts_with('-') {
-                let flag_style = Style::builder().foreground(Color::Yellow).build();
+                let flag_style = Style::builder().foreground(self.colors.flag).build();
                 *part = part.clone().style(flag_style).to_string();
             }
         }
@@ -45,33 +61,34 @@ impl SyntaxHighlighter {
     }
 
     pub fn highlight_error(&self, error: &str) -> String {
-        if matches!(self.color_support, ColorSupport::NoColor) {
+        if self.no_color() {
             return error.to_string();
         }
 
-        let error_style = Style::builder().foreground(Color::Red).bold().build();
+        let error_style = Style::builder()
+            .foreground(self.colors.error)
+            .bold()
+            .build();
 
         error.style(error_style).to_string()
     }
 
     pub fn highlight_success(&self, message: &str) -> String {
-        if matches!(self.color_support, ColorSupport::NoColor) {
+        if self.no_color() {
             return message.to_string();
         }
 
-        let success_style = Style::builder().foreground(Color::Green).build();
+        let success_style = Style::builder().foreground(self.colors.success).build();
 
         message.style(success_style).to_string()
     }
 
     pub fn highlight_hint(&self, hint: &str) -> String {
-        if matches!(self.color_support, ColorSupport::NoColor) {
+        if self.no_color() {
             return hint.to_string();
         }
 
-        let hint_style = Style::builder()
-            .foreground(Color::RGB(128, 128, 128))
-            .build();
+        let hint_style = Style::builder().foreground(self.colors.hint).build();
 
         hint.style(hint_style).to_string()
     }
@@ -0,0 +1,60 @@
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+
+use rustyline::{Cmd, ConditionalEventHandler, Event, EventContext, InputMode, RepeatCount};
+
+use crate::core::config::Abbreviations;
+
+/// Space: expands the abbreviation just typed, then the space goes in.
+/// Anywhere else, or in vi command mode, the key does what it usually does.
+///
+/// Replacing text leaves the editor's cursor before what was put in, so
+/// the key asks for a completion instead, which the completer answers with
+/// the expansion; in circular completion mode a Tab or Esc right after
+/// puts the abbreviation back.
+#[derive(Clone)]
+pub struct ExpandAbbreviation {
+    abbreviations: Arc<Mutex<Abbreviations>>,
+    requested: Arc<AtomicBool>,
+}
+
+impl ExpandAbbreviation {
+    pub fn new(abbreviations: Arc<Mutex<Abbreviations>>) -> Self {
+        Self {
+            abbreviations,
+            requested: Arc::default(),
+        }
+    }
+
+    /// The completion for a Space that asked for one: where the abbreviation
+    /// starts, and what replaces it.
+    pub fn take_expansion(&self, line: &str, pos: usize) -> Option<(usize, String)> {
+        if !self.requested.swap(false, Ordering::Relaxed) {
+            return None;
+        }
+        let abbreviations = self.abbreviations.lock().ok()?;
+        let (start, expansion) = abbreviations.expansion_at(line, pos)?;
+        Some((start, format!("{} ", expansion)))
+    }
+}
+
+impl ConditionalEventHandler for ExpandAbbreviation {
+    fn handle(
+        &self,
+        _evt: &Event,
+        _n: RepeatCount,
+        _positive: bool,
+        ctx: &EventContext,
+    ) -> Option<Cmd> {
+        let (line, pos) = (ctx.line(), ctx.pos());
+        // Not part way through a word
+        let at_word_end = !line[pos..].starts_with(|c: char| !c.is_whitespace());
+        if ctx.input_mode() == InputMode::Command || !at_word_end {
+            return None;
+        }
+        let abbreviations = self.abbreviations.lock().ok()?;
+        abbreviations.expansion_at(line, pos)?;
+        self.requested.store(true, Ordering::Relaxed);
+        Some(Cmd::Complete)
+    }
+}
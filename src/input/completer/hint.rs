@@ -0,0 +1,61 @@
+use rustyline::{Cmd, ConditionalEventHandler, Event, EventContext, RepeatCount};
+
+/// Accepts the suggestion shown after the input, all of it or just its next
+/// word. Away from the end of the line, or with nothing suggested, the key
+/// does what it usually does.
+pub struct AcceptSuggestion {
+    word: bool,
+}
+
+impl AcceptSuggestion {
+    pub fn whole() -> Self {
+        Self { word: false }
+    }
+
+    pub fn word() -> Self {
+        Self { word: true }
+    }
+}
+
+impl ConditionalEventHandler for AcceptSuggestion {
+    fn handle(
+        &self,
+        _evt: &Event,
+        _n: RepeatCount,
+        _positive: bool,
+        ctx: &EventContext,
+    ) -> Option<Cmd> {
+        if ctx.pos() < ctx.line().len() {
+            return None;
+        }
+        let hint = ctx.hint_text().filter(|hint| !hint.is_empty())?;
+        if self.word {
+            Some(Cmd::Insert(1, next_word(hint).to_string()))
+        } else {
+            Some(Cmd::CompleteHint)
+        }
+    }
+}
+
+// The start of `text` up to the end of its first word, spaces before it
+// included
+fn next_word(text: &str) -> &str {
+    let start = text.len() - text.trim_start().len();
+    let end = text[start..]
+        .find(char::is_whitespace)
+        .map_or(text.len(), |length| start + length);
+    &text[..end]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_next_word() {
+        assert_eq!(next_word("build --release"), "build");
+        assert_eq!(next_word(" --release"), " --release");
+        assert_eq!(next_word("  a b"), "  a");
+        assert_eq!(next_word("x"), "x");
+    }
+}
@@ -1,5 +1,11 @@
+mod abbreviation;
 mod command;
+mod hint;
 mod path;
 mod shell;
+mod spec;
 
+pub use abbreviation::ExpandAbbreviation;
+pub use hint::AcceptSuggestion;
 pub use shell::ShellCompleter;
+pub use spec::{CompletionSpec, CompletionSpecs};
@@ -1,7 +1,16 @@
-use std::{borrow::Cow, collections::BTreeMap};
+use std::{
+    borrow::Cow,
+    collections::BTreeMap,
+    sync::{Arc, Mutex, MutexGuard},
+};
 
-use super::{command::CommandCompleter, path::PathCompleter};
+use super::{
+    abbreviation::ExpandAbbreviation, command::CommandCompleter, path::PathCompleter,
+    spec::CompletionSpecs,
+};
+use crate::core::config::ColorSettings;
 use crate::highlight::SyntaxHighlighter;
+use crate::input::{terminal, History};
 
 use rustyline::{
     completion::{Completer, Pair},
@@ -16,6 +25,85 @@ pub struct ShellCompleter {
     command_completer: CommandCompleter,
     path_completer: PathCompleter,
     highlighter: SyntaxHighlighter,
+    // Shared by every clone, so a prompt can be redrawn from another thread
+    display: Arc<Mutex<PromptDisplay>>,
+    // Where suggestions come from, once the shell has opened it
+    history: Option<Arc<Mutex<History>>>,
+    // Answers a Space that asks for an abbreviation's expansion
+    abbreviations: Option<ExpandAbbreviation>,
+    // What `complete` has set up for completing commands' arguments
+    specs: Option<Arc<Mutex<CompletionSpecs>>>,
+}
+
+#[derive(Default)]
+struct PromptDisplay {
+    // Bumped for each prompt so updates meant for an earlier one are dropped
+    generation: u64,
+    // The plain prompt handed to the editor, and the text printed for it
+    prompt: Option<(String, String)>,
+    right: Option<RightPrompt>,
+    // The suggestion last shown, which the right prompt also gives way to
+    hint: String,
+}
+
+// Columns kept clear between the input and the right prompt, and after it
+// so drawing in the last column can't wrap the line
+const RIGHT_PROMPT_GAP: usize = 1;
+const RIGHT_PROMPT_INDENT: usize = 1;
+
+/// A prompt drawn against the right edge of the first input row, given way
+/// to once the input reaches it.
+#[derive(Clone, PartialEq)]
+struct RightPrompt {
+    text: String,
+    width: usize,
+    // Columns taken by the last line of the left prompt
+    left_width: usize,
+}
+
+impl RightPrompt {
+    fn new(visible: &str, text: &str, left: &str) -> Option<Self> {
+        (!visible.is_empty()).then(|| RightPrompt {
+            text: text.to_string(),
+            width: visible.chars().count(),
+            left_width: left.rsplit('\n').next().unwrap_or_default().chars().count(),
+        })
+    }
+
+    // The escapes drawing it beside `line`, with the cursor saved and
+    // restored around them, or nothing when the line leaves no room
+    fn draw(&self, line: &str, columns: usize) -> Option<String> {
+        if line.contains('\n') {
+            return None;
+        }
+        let column = columns.checked_sub(self.width + RIGHT_PROMPT_INDENT)?;
+        let used = self.left_width + line.chars().count() + RIGHT_PROMPT_GAP;
+        (used <= column).then(|| format!("\x1b7\r\x1b[{}C{}\x1b8", column, self.text))
+    }
+}
+
+// The spaces that make `visible` as wide as `measured`, if it fits in the
+// same rows
+fn padding(measured: &str, visible: &str) -> Option<String> {
+    let measured: Vec<usize> = measured.split('\n').map(|l| l.chars().count()).collect();
+    let visible: Vec<usize> = visible.split('\n').map(|l| l.chars().count()).collect();
+    if measured.len() != visible.len() || visible.iter().zip(&measured).any(|(v, m)| v > m) {
+        return None;
+    }
+    let last = measured.len() - 1;
+    Some(" ".repeat(measured[last] - visible[last]))
+}
+
+// The rows `line` took after a prompt ending `left` columns in; a row
+// filled to the last column moves the cursor on to the next
+fn rows_taken(left: usize, line: &str, columns: usize) -> usize {
+    line.split('\n')
+        .enumerate()
+        .map(|(i, row)| {
+            let width = row.chars().count() + if i == 0 { left } else { 0 };
+            width / columns + 1
+        })
+        .sum()
 }
 
 impl Default for ShellCompleter {
@@ -30,6 +118,10 @@ impl ShellCompleter {
             command_completer: CommandCompleter::new(),
             path_completer: PathCompleter::new(),
             highlighter: SyntaxHighlighter::new(),
+            display: Arc::default(),
+            history: None,
+            abbreviations: None,
+            specs: None,
         }
     }
 
@@ -40,25 +132,178 @@ impl ShellCompleter {
     pub fn update_aliases(&mut self, aliases: BTreeMap<Cow<'_, str>, Cow<'_, str>>) {
         self.command_completer.update_aliases(aliases);
     }
+
+    /// Starts a new prompt: the editor prints `text` for the prompt it was
+    /// given as `visible`, which is all it measures. Returns the prompt's
+    /// generation for [`update_prompt`](Self::update_prompt).
+    pub fn set_prompt(&self, visible: &str, text: &str) -> u64 {
+        let mut display = self.display();
+        display.generation += 1;
+        display.prompt = Some((visible.to_string(), text.to_string()));
+        display.generation
+    }
+
+    /// Shows `text`, measured by `visible`, at the right edge of the row
+    /// that `left` (the visible left prompt) ends on. An empty `visible`
+    /// removes it.
+    pub fn set_right_prompt(&self, visible: &str, text: &str, left: &str) {
+        self.display().right = RightPrompt::new(visible, text, left);
+    }
+
+    /// Replaces the prompts of `generation` if it's still being shown,
+    /// returning whether that changed anything. The editor keeps the width
+    /// it measured, so a left prompt that has grown is left as it was and
+    /// a narrower one is padded out.
+    pub fn update_prompt(&self, generation: u64, left: (&str, &str), right: (&str, &str)) -> bool {
+        let mut display = self.display();
+        if display.generation != generation {
+            return false;
+        }
+        let Some((measured, printed)) = display.prompt.clone() else {
+            return false;
+        };
+        let printed = match padding(&measured, left.0) {
+            Some(padding) => format!("{}{}", left.1, padding),
+            None => printed,
+        };
+        let right = RightPrompt::new(right.0, right.1, &measured);
+        let prompt = Some((measured, printed));
+        let changed = display.prompt != prompt || display.right != right;
+        display.prompt = prompt;
+        display.right = right;
+        changed
+    }
+
+    /// Ends the current prompt once the editor has returned its line.
+    pub fn end_prompt(&self) {
+        self.display().generation += 1;
+    }
+
+    /// The escapes that, from the row after the accepted `line`, draw the
+    /// last prompt with `shown` over it, or nothing off a terminal.
+    pub fn redraw_accepted(&self, line: &str, shown: &str) -> Option<String> {
+        let (columns, _) = terminal::size()?;
+        let display = self.display();
+        let (measured, printed) = display.prompt.as_ref()?;
+        let left = measured.rsplit('\n').next().unwrap_or_default();
+        let rows = rows_taken(left.chars().count(), line, columns);
+        let mut redraw = format!(
+            "\x1b[{}A\r\x1b[J{}{}",
+            rows,
+            printed.rsplit('\n').next().unwrap_or_default(),
+            self.highlighter.highlight_command(shown)
+        );
+        if let Some(right) = &display.right {
+            redraw.extend(right.draw(shown, columns));
+        }
+        Some(redraw)
+    }
+
+    // Only offered with the cursor at the end of the line
+    fn suggest(&self, line: &str, pos: usize) -> Option<String> {
+        if pos < line.len() || line.trim().is_empty() {
+            return None;
+        }
+        let history = self.history.as_ref()?.lock().ok()?;
+        let cwd = std::env::current_dir().ok()?;
+        let command = history.suggest(line, &cwd.to_string_lossy())?;
+        Some(command[line.len()..].to_string())
+    }
+
+    fn display(&self) -> MutexGuard<'_, PromptDisplay> {
+        // The display is only ever replaced whole, so a poisoned lock is fine
+        self.display.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    /// Suggests commands from `history` as the user types.
+    pub fn set_history(&mut self, history: Arc<Mutex<History>>) {
+        self.history = Some(history);
+    }
+
+    /// Completes with the expansion when `abbreviations` asks to.
+    pub fn set_abbreviations(&mut self, abbreviations: ExpandAbbreviation) {
+        self.abbreviations = Some(abbreviations);
+    }
+
+    /// Completes the arguments of commands with a spec in `specs` by it.
+    pub fn set_specs(&mut self, specs: Arc<Mutex<CompletionSpecs>>) {
+        self.specs = Some(specs);
+    }
+
+    // The spec's candidates for the word at `pos`, when the command it's an
+    // argument of has one
+    fn complete_by_spec(&self, line: &str, pos: usize) -> Option<(usize, Vec<Pair>)> {
+        let before = &line[..pos];
+        // Only the command after the last `|`, `;` or `&` counts
+        let command = &before[before.rfind(['|', ';', '&']).map_or(0, |i| i + 1)..];
+        let mut words: Vec<&str> = command.split_whitespace().collect();
+        if command.ends_with(char::is_whitespace) {
+            words.push("");
+        }
+        if words.len() < 2 {
+            return None;
+        }
+        // A copy, so `complete` isn't kept waiting while a -C command runs
+        let spec = self.specs.as_ref()?.lock().ok()?.get(words[0])?.clone();
+        let word = words.last().copied().unwrap_or_default();
+        let matches = spec.complete(
+            line,
+            pos,
+            &words,
+            &self.command_completer,
+            &self.path_completer,
+        );
+        Some((pos - word.len(), matches))
+    }
+
+    pub fn set_colors(&mut self, colors: ColorSettings) {
+        self.highlighter = self.highlighter.with_colors(colors);
+    }
 }
 
 impl Helper for ShellCompleter {}
 impl Highlighter for ShellCompleter {
     fn highlight<'l>(&self, line: &'l str, _pos: usize) -> std::borrow::Cow<'l, str> {
-        Cow::Owned(self.highlighter.highlight_command(line))
+        let mut highlighted = self.highlighter.highlight_command(line);
+        let display = self.display();
+        if let Some(right) = &display.right {
+            let input = format!("{}{}", line, display.hint);
+            highlighted
+                .extend(terminal::size().and_then(|(columns, _)| right.draw(&input, columns)));
+        }
+        Cow::Owned(highlighted)
     }
 
     fn highlight_char(&self, _line: &str, _pos: usize, _kind: CmdKind) -> bool {
         true
     }
 
+    fn highlight_prompt<'b, 's: 'b, 'p: 'b>(
+        &'s self,
+        prompt: &'p str,
+        default: bool,
+    ) -> Cow<'b, str> {
+        match &self.display().prompt {
+            Some((visible, text)) if default && visible == prompt => Cow::Owned(text.clone()),
+            _ => Cow::Borrowed(prompt),
+        }
+    }
+
     fn highlight_hint<'h>(&self, hint: &'h str) -> std::borrow::Cow<'h, str> {
         Cow::Owned(self.highlighter.highlight_hint(hint))
     }
 }
 impl Hinter for ShellCompleter {
     type Hint = String;
+
+    // Fish-style: the rest of a past command that starts with the input
+    fn hint(&self, line: &str, pos: usize, _ctx: &Context<'_>) -> Option<String> {
+        let hint = self.suggest(line, pos);
+        self.display().hint = hint.clone().unwrap_or_default();
+        hint
+    }
 }
+
 impl Validator for ShellCompleter {}
 
 impl Completer for ShellCompleter {
@@ -70,6 +315,21 @@ impl Completer for ShellCompleter {
         pos: usize,
         _ctx: &Context<'_>,
     ) -> rustyline::Result<(usize, Vec<Pair>)> {
+        let expansion = self
+            .abbreviations
+            .as_ref()
+            .and_then(|abbreviations| abbreviations.take_expansion(line, pos));
+        if let Some((start, expansion)) = expansion {
+            let pair = Pair {
+                display: expansion.clone(),
+                replacement: expansion,
+            };
+            return Ok((start, vec![pair]));
+        }
+        if let Some(completion) = self.complete_by_spec(line, pos) {
+            return Ok(completion);
+        }
+
         let line_up_to_cursor = &line[..pos];
         let mut words: Vec<&str> = line_up_to_cursor.split_whitespace().collect();
 
@@ -98,3 +358,56 @@ impl Completer for ShellCompleter {
         Ok((start, matches))
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_right_prompt_gives_way_to_input() {
+        let completer = ShellCompleter::new();
+        completer.set_right_prompt("12:00", "\x1b[2m12:00\x1b[0m", "first\n~ > ");
+        let right = completer.display().right.clone().unwrap();
+        assert_eq!((right.width, right.left_width), (5, 4));
+
+        // 20 columns: the prompt starts at 14, so 9 input columns fit
+        let drawn = right.draw("echo", 20).unwrap();
+        assert_eq!(drawn, "\x1b7\r\x1b[14C\x1b[2m12:00\x1b[0m\x1b8");
+        assert!(right.draw("echo hiya", 20).is_some());
+        assert!(right.draw("echo hiya!", 20).is_none());
+        assert!(right.draw("echo 'a\nb'", 20).is_none());
+        assert!(right.draw("", 8).is_none());
+
+        completer.set_right_prompt("", "", "> ");
+        assert!(completer.display().right.is_none());
+    }
+
+    #[test]
+    fn test_update_prompt_keeps_measured_width() {
+        let completer = ShellCompleter::new();
+        let generation = completer.set_prompt("~ (main) > ", "~ (main) > ");
+
+        assert!(completer.update_prompt(generation, ("~ (ok) > ", "~ (ok) > "), ("", "")));
+        assert!(!completer.update_prompt(generation, ("~ (ok) > ", "~ (ok) > "), ("", "")));
+        let printed = completer.highlight_prompt("~ (main) > ", true).into_owned();
+        assert_eq!(printed, "~ (ok) >   ");
+
+        // Too wide to redraw without moving the cursor
+        completer.update_prompt(generation, ("~ (main ~2) > ", "~ (main ~2) > "), ("", ""));
+        let printed = completer.highlight_prompt("~ (main) > ", true).into_owned();
+        assert_eq!(printed, "~ (ok) >   ");
+
+        completer.end_prompt();
+        assert!(!completer.update_prompt(generation, ("x", "x"), ("", "")));
+    }
+
+    #[test]
+    fn test_rows_taken_by_accepted_line() {
+        // 4 prompt columns in 10: six more fit on the first row
+        assert_eq!(rows_taken(4, "gco", 10), 1);
+        assert_eq!(rows_taken(4, "12345", 10), 1);
+        assert_eq!(rows_taken(4, "123456", 10), 2);
+        assert_eq!(rows_taken(4, "ls \\\nabc", 10), 2);
+        assert_eq!(rows_taken(0, "", 10), 1);
+    }
+}
@@ -0,0 +1,324 @@
+use std::collections::BTreeMap;
+use std::fmt;
+use std::path::Path;
+use std::process::{Command, Stdio};
+
+use rustyline::completion::Pair;
+
+use super::{command::CommandCompleter, path::PathCompleter};
+use crate::path::glob;
+use crate::shell::expand::shell_quote;
+
+/// How to complete a command's arguments, as `complete` sets it up.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct CompletionSpec {
+    // -W: the words to offer
+    pub words: Vec<String>,
+    // -C: a command that prints candidates one per line, given the command
+    // being completed, the word and the one before it
+    pub command: Option<String>,
+    // -c, -d, -f: command names, directories, file names
+    pub commands: bool,
+    pub directories: bool,
+    pub files: bool,
+    // -X: candidates matching this are dropped, or with a leading `!` those
+    // that don't; directories stay so there's a way to reach the files
+    pub filter: Option<String>,
+    // -o nospace: no space after a completed word
+    pub nospace: bool,
+    // -o default: file names when nothing else matches
+    pub default: bool,
+}
+
+// The words of the command being completed, the last one the word at the
+// cursor, and the whole line
+struct Request<'a> {
+    line: &'a str,
+    pos: usize,
+    words: &'a [&'a str],
+}
+
+impl CompletionSpec {
+    /// Candidates for the last of `words`, the command line up to `pos` in
+    /// `line` split into words.
+    pub fn complete(
+        &self,
+        line: &str,
+        pos: usize,
+        words: &[&str],
+        commands: &CommandCompleter,
+        paths: &PathCompleter,
+    ) -> Vec<Pair> {
+        let request = Request { line, pos, words };
+        let word = words.last().copied().unwrap_or_default();
+        let mut candidates = self.generate(&request, word, commands, paths);
+        candidates.retain(|candidate| self.keeps(candidate));
+        if candidates.is_empty() && self.default {
+            candidates = path_candidates(paths.complete_path(word));
+        }
+        candidates.sort();
+        candidates.dedup();
+        candidates
+            .into_iter()
+            .map(|candidate| {
+                let replacement = if self.nospace || candidate.ends_with('/') {
+                    candidate.clone()
+                } else {
+                    format!("{} ", candidate)
+                };
+                Pair {
+                    display: candidate,
+                    replacement,
+                }
+            })
+            .collect()
+    }
+
+    // Everything the actions offer that starts with `word`
+    fn generate(
+        &self,
+        request: &Request<'_>,
+        word: &str,
+        commands: &CommandCompleter,
+        paths: &PathCompleter,
+    ) -> Vec<String> {
+        let mut candidates: Vec<String> = self
+            .words
+            .iter()
+            .filter(|candidate| candidate.starts_with(word))
+            .cloned()
+            .collect();
+        if let Some(command) = &self.command {
+            candidates.extend(run_command(command, request, word));
+        }
+        if self.commands {
+            let names = commands.complete_command(word);
+            candidates.extend(names.into_iter().map(|pair| pair.replacement));
+        }
+        if self.files || self.directories {
+            let found = path_candidates(paths.complete_path(word));
+            candidates.extend(
+                found
+                    .into_iter()
+                    .filter(|path| self.files || path.ends_with('/')),
+            );
+        }
+        candidates
+    }
+
+    fn keeps(&self, candidate: &str) -> bool {
+        let Some(filter) = &self.filter else {
+            return true;
+        };
+        if candidate.ends_with('/') {
+            return true;
+        }
+        match filter.strip_prefix('!') {
+            Some(pattern) => glob::matches(pattern, candidate),
+            None => !glob::matches(filter, candidate),
+        }
+    }
+}
+
+// Path completions without the space after files
+fn path_candidates(pairs: Vec<Pair>) -> Vec<String> {
+    pairs
+        .into_iter()
+        .map(|pair| pair.replacement.trim_end().to_string())
+        .collect()
+}
+
+// The lines `program` prints starting with `word`; it's told the line and
+// cursor as bash tells completion commands. It runs as the user types, so
+// callers mustn't hold the specs' lock while it does.
+fn run_command(program: &str, request: &Request<'_>, word: &str) -> Vec<String> {
+    let words = request.words;
+    let command = words.first().copied().unwrap_or_default();
+    let previous = words.len().checked_sub(2).map_or("", |i| words[i]);
+    let output = Command::new(program)
+        .args([command, word, previous])
+        .env("COMP_LINE", request.line)
+        .env("COMP_POINT", request.pos.to_string())
+        .env("COMP_CWORD", (words.len().saturating_sub(1)).to_string())
+        .stdin(Stdio::null())
+        .stderr(Stdio::null())
+        .output();
+    let Ok(output) = output else {
+        return Vec::new();
+    };
+    String::from_utf8_lossy(&output.stdout)
+        .lines()
+        .filter(|candidate| !candidate.is_empty() && candidate.starts_with(word))
+        .map(String::from)
+        .collect()
+}
+
+/// The options that set the spec up again, as `complete -p` prints them.
+impl fmt::Display for CompletionSpec {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut options = Vec::new();
+        if self.nospace {
+            options.push("-o nospace".to_string());
+        }
+        if self.default {
+            options.push("-o default".to_string());
+        }
+        let flags = [
+            (self.commands, "-c"),
+            (self.directories, "-d"),
+            (self.files, "-f"),
+        ];
+        options.extend(
+            flags
+                .into_iter()
+                .filter(|(set, _)| *set)
+                .map(|(_, flag)| flag.to_string()),
+        );
+        if !self.words.is_empty() {
+            options.push(format!("-W {}", shell_quote(&self.words.join(" "))));
+        }
+        if let Some(command) = &self.command {
+            options.push(format!("-C {}", shell_quote(command)));
+        }
+        if let Some(filter) = &self.filter {
+            options.push(format!("-X {}", shell_quote(filter)));
+        }
+        write!(f, "{}", options.join(" "))
+    }
+}
+
+/// The completion specs by command name.
+#[derive(Debug, Default)]
+pub struct CompletionSpecs {
+    specs: BTreeMap<String, CompletionSpec>,
+}
+
+impl CompletionSpecs {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn add(&mut self, command: &str, spec: CompletionSpec) {
+        self.specs.insert(command.to_string(), spec);
+    }
+
+    /// Removes a command's spec, reporting whether there was one.
+    pub fn remove(&mut self, command: &str) -> bool {
+        self.specs.remove(command).is_some()
+    }
+
+    pub fn clear(&mut self) {
+        self.specs.clear();
+    }
+
+    /// The spec for `command`, or for its file name when it's a path.
+    pub fn get(&self, command: &str) -> Option<&CompletionSpec> {
+        self.specs.get(command).or_else(|| {
+            let name = Path::new(command).file_name()?.to_str()?;
+            self.specs.get(name)
+        })
+    }
+
+    /// All of them, by command name.
+    pub fn iter(&self) -> impl Iterator<Item = (&str, &CompletionSpec)> {
+        self.specs.iter().map(|(name, spec)| (name.as_str(), spec))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+
+    fn complete(spec: &CompletionSpec, words: &[&str]) -> Vec<String> {
+        let line = words.join(" ");
+        let pairs = spec.complete(
+            &line,
+            line.len(),
+            words,
+            &CommandCompleter::new(),
+            &PathCompleter::new(),
+        );
+        pairs.into_iter().map(|pair| pair.replacement).collect()
+    }
+
+    #[test]
+    fn test_word_list() {
+        let mut spec = CompletionSpec {
+            words: vec!["start".into(), "stop".into(), "status".into()],
+            ..CompletionSpec::default()
+        };
+        assert_eq!(
+            complete(&spec, &["svc", "st"]),
+            ["start ", "status ", "stop "]
+        );
+        assert_eq!(complete(&spec, &["svc", "sto"]), ["stop "]);
+        assert!(complete(&spec, &["svc", "x"]).is_empty());
+
+        spec.nospace = true;
+        spec.filter = Some("sta*".into());
+        assert_eq!(complete(&spec, &["svc", ""]), ["stop"]);
+        assert_eq!(
+            spec.to_string(),
+            "-o nospace -W 'start stop status' -X 'sta*'"
+        );
+    }
+
+    #[test]
+    fn test_files_and_directories() {
+        let dir = std::env::temp_dir().join("aorta_complete_spec_test");
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(dir.join("src")).unwrap();
+        fs::write(dir.join("main.rs"), "").unwrap();
+        fs::write(dir.join("notes.txt"), "").unwrap();
+        let prefix = format!("{}/", dir.display());
+        let path = |name: &str| format!("{}{}", prefix, name);
+
+        let rust = CompletionSpec {
+            files: true,
+            filter: Some("!*.rs".into()),
+            ..CompletionSpec::default()
+        };
+        let found = complete(&rust, &["rustc", &prefix]);
+        assert_eq!(found, [path("main.rs "), path("src/")]);
+
+        let dirs = CompletionSpec {
+            directories: true,
+            ..CompletionSpec::default()
+        };
+        assert_eq!(complete(&dirs, &["cd", &prefix]), [path("src/")]);
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn test_command_output() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let dir = std::env::temp_dir().join("aorta_complete_command_test");
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        let program = dir.join("comp-svc");
+        fs::write(&program, "#!/bin/sh\nprintf '%s\\n' start stop \"$1:$3\"\n").unwrap();
+        fs::set_permissions(&program, fs::Permissions::from_mode(0o755)).unwrap();
+
+        let spec = CompletionSpec {
+            command: Some(program.display().to_string()),
+            ..CompletionSpec::default()
+        };
+        assert_eq!(complete(&spec, &["svc", "st"]), ["start ", "stop "]);
+        assert_eq!(complete(&spec, &["svc", "-v", "svc"]), ["svc:-v "]);
+        assert_eq!(spec.to_string(), format!("-C '{}'", program.display()));
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn test_lookup_by_name() {
+        let mut specs = CompletionSpecs::new();
+        specs.add("git", CompletionSpec::default());
+        assert!(specs.get("git").is_some());
+        assert!(specs.get("/usr/bin/git").is_some());
+        assert!(specs.get("gitk").is_none());
+        assert!(specs.remove("git"));
+        assert!(!specs.remove("git"));
+    }
+}
@@ -0,0 +1,429 @@
+use std::io::{self, Write};
+use std::sync::{Arc, Mutex};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use rustyline::{Cmd, ConditionalEventHandler, Event, EventContext, Movement, RepeatCount};
+
+use super::history::{relative_time, FuzzyResult, History, HistoryEntry, SearchFilter};
+use super::terminal;
+
+// The alternate screen: leaving it puts back whatever the editor had drawn
+const ENTER_SCREEN: &str = "\x1b[?1049h";
+const LEAVE_SCREEN: &str = "\x1b[?1049l";
+// How long the rest of an escape sequence may take before Esc counts alone
+const ESCAPE_TIMEOUT_MS: i32 = 25;
+// The query line and the status line above the list, the preview below
+const CHROME_ROWS: usize = 3;
+
+/// Ctrl-R: a full-screen fuzzy finder over the history. The chosen command
+/// replaces the input line for editing rather than being run.
+pub struct HistoryFinder {
+    history: Arc<Mutex<History>>,
+}
+
+impl HistoryFinder {
+    pub fn new(history: Arc<Mutex<History>>) -> Self {
+        Self { history }
+    }
+}
+
+impl ConditionalEventHandler for HistoryFinder {
+    fn handle(
+        &self,
+        _evt: &Event,
+        _n: RepeatCount,
+        _positive: bool,
+        ctx: &EventContext,
+    ) -> Option<Cmd> {
+        // Without a terminal to draw on, fall back to the usual search
+        terminal::size()?;
+        let cwd = std::env::current_dir()
+            .ok()
+            .map(|dir| dir.to_string_lossy().into_owned());
+        let mut finder = Finder {
+            query: ctx.line().to_string(),
+            filter: SearchFilter::default(),
+            cwd,
+            selected: 0,
+            scroll: 0,
+        };
+        // A failed read or write is as good as giving up
+        match finder.run(&self.history) {
+            Ok(Some(command)) => Some(accept(ctx, command)),
+            Ok(None) | Err(_) => Some(Cmd::Repaint),
+        }
+    }
+}
+
+// Replacing the line leaves the cursor at its start, so when the input is
+// already the start of the command the rest is typed after it instead
+fn accept(ctx: &EventContext, command: String) -> Cmd {
+    let line = ctx.line();
+    match command.strip_prefix(line) {
+        Some(rest) if ctx.pos() == line.len() && !rest.is_empty() => {
+            Cmd::Insert(1, rest.to_string())
+        }
+        Some("") => Cmd::Repaint,
+        _ => Cmd::Replace(Movement::WholeBuffer, Some(command)),
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Key {
+    Char(char),
+    Up,
+    Down,
+    Accept,
+    Backspace,
+    Cancel,
+    ClearQuery,
+    CycleStatus,
+    ToggleDir,
+    Ignored,
+}
+
+struct Finder {
+    query: String,
+    filter: SearchFilter,
+    // Where the shell is, for the directory filter
+    cwd: Option<String>,
+    selected: usize,
+    // The first result on screen
+    scroll: usize,
+}
+
+impl Finder {
+    fn run(&mut self, history: &Mutex<History>) -> io::Result<Option<String>> {
+        let mut stdout = io::stdout();
+        stdout.write_all(ENTER_SCREEN.as_bytes())?;
+        let chosen = self.interact(history, &mut stdout);
+        stdout.write_all(LEAVE_SCREEN.as_bytes())?;
+        stdout.flush()?;
+        chosen
+    }
+
+    fn interact(
+        &mut self,
+        history: &Mutex<History>,
+        stdout: &mut io::Stdout,
+    ) -> io::Result<Option<String>> {
+        let mut pending = Vec::new();
+        loop {
+            let (screen, selected, count) = {
+                let history = history.lock().unwrap_or_else(|e| e.into_inner());
+                let results = history.fuzzy_search(&self.query, &self.filter);
+                self.selected = self.selected.min(results.len().saturating_sub(1));
+                let (columns, rows) = terminal::size().unwrap_or((80, 24));
+                let selected = results.get(self.selected).map(|r| r.command.to_string());
+                (
+                    self.render(&results, columns, rows),
+                    selected,
+                    results.len(),
+                )
+            };
+            stdout.write_all(screen.as_bytes())?;
+            stdout.flush()?;
+
+            match read_key(&mut pending)? {
+                Key::Accept => return Ok(selected),
+                Key::Cancel => return Ok(None),
+                key => self.apply(key, count),
+            }
+        }
+    }
+
+    fn apply(&mut self, key: Key, count: usize) {
+        match key {
+            Key::Up => self.selected = self.selected.saturating_sub(1),
+            Key::Down => self.selected = (self.selected + 1).min(count.saturating_sub(1)),
+            Key::Char(c) => self.query.push(c),
+            Key::Backspace => {
+                self.query.pop();
+            }
+            Key::ClearQuery => self.query.clear(),
+            Key::CycleStatus => {
+                self.filter.succeeded = match self.filter.succeeded {
+                    None => Some(true),
+                    Some(true) => Some(false),
+                    Some(false) => None,
+                }
+            }
+            Key::ToggleDir => {
+                self.filter.cwd = match self.filter.cwd {
+                    Some(_) => None,
+                    None => self.cwd.clone(),
+                }
+            }
+            Key::Accept | Key::Cancel | Key::Ignored => return,
+        }
+        // Anything but moving starts again from the best match
+        if !matches!(key, Key::Up | Key::Down) {
+            self.selected = 0;
+        }
+    }
+
+    fn render(&mut self, results: &[FuzzyResult<'_>], columns: usize, rows: usize) -> String {
+        let list_rows = rows.saturating_sub(CHROME_ROWS).max(1);
+        if self.selected < self.scroll {
+            self.scroll = self.selected;
+        } else if self.selected >= self.scroll + list_rows {
+            self.scroll = self.selected + 1 - list_rows;
+        }
+
+        let mut screen = String::from("\x1b[H\x1b[2J");
+        screen.push_str(&format!(
+            "> {}",
+            truncate(&self.query, columns.saturating_sub(2))
+        ));
+        let status = format!(
+            "  {} commands{}  ^S status  ^D here  ^U clear",
+            results.len(),
+            self.filter_label()
+        );
+        screen.push_str(&format!(
+            "\x1b[2;1H\x1b[2m{}\x1b[0m",
+            truncate(&status, columns)
+        ));
+
+        for (row, (index, result)) in results
+            .iter()
+            .enumerate()
+            .skip(self.scroll)
+            .take(list_rows)
+            .enumerate()
+        {
+            let line = highlight(
+                result.command,
+                &result.found.positions,
+                columns.saturating_sub(2),
+            );
+            screen.push_str(&format!("\x1b[{};1H", row + 3));
+            if index == self.selected {
+                screen.push_str(&format!("\x1b[7m> {}\x1b[0m", line));
+            } else {
+                screen.push_str(&format!("  {}", line));
+            }
+        }
+
+        if let Some(result) = results.get(self.selected) {
+            let preview = truncate(&preview(result.entry), columns);
+            screen.push_str(&format!("\x1b[{};1H\x1b[2m{}\x1b[0m", rows, preview));
+        }
+        let cursor = 3 + self.query.chars().count().min(columns.saturating_sub(3));
+        screen.push_str(&format!("\x1b[1;{}H", cursor));
+        screen
+    }
+
+    fn filter_label(&self) -> String {
+        let mut label = String::new();
+        match self.filter.succeeded {
+            Some(true) => label.push_str(" [succeeded]"),
+            Some(false) => label.push_str(" [failed]"),
+            None => {}
+        }
+        if self.filter.cwd.is_some() {
+            label.push_str(" [this directory]");
+        }
+        label
+    }
+}
+
+// Draws a command on one row, matched characters in bold
+fn highlight(command: &str, positions: &[usize], width: usize) -> String {
+    let long = command.chars().count() > width;
+    let mut line = String::new();
+    for (shown, (offset, c)) in command.char_indices().enumerate() {
+        if long && shown + 1 >= width {
+            line.push('…');
+            break;
+        }
+        let c = match c {
+            '\n' => '↵',
+            c if c.is_control() => ' ',
+            c => c,
+        };
+        if positions.contains(&offset) {
+            line.push_str(&format!("\x1b[1m{}\x1b[22m", c));
+        } else {
+            line.push(c);
+        }
+    }
+    line
+}
+
+fn truncate(text: &str, width: usize) -> String {
+    text.chars().take(width).collect()
+}
+
+// When the command last ran, how it went and where
+fn preview(entry: &HistoryEntry) -> String {
+    let HistoryEntry::Command {
+        timestamp,
+        exit_code,
+        duration,
+        cwd,
+        ..
+    } = entry
+    else {
+        return String::new();
+    };
+    let now = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_secs();
+    let mut preview = format!(
+        "{} · exit {} · {}ms",
+        relative_time(*timestamp, now),
+        exit_code,
+        duration
+    );
+    if let Some(cwd) = cwd {
+        preview.push_str(&format!(" · {}", cwd));
+    }
+    preview
+}
+
+fn read_key(pending: &mut Vec<u8>) -> io::Result<Key> {
+    loop {
+        if let Some((key, used)) = decode(pending) {
+            pending.drain(..used);
+            return Ok(key);
+        }
+        let timeout = (pending.as_slice() == [0x1b]).then_some(ESCAPE_TIMEOUT_MS);
+        if !read_more(pending, timeout)? {
+            pending.clear();
+            return Ok(Key::Cancel);
+        }
+    }
+}
+
+// Reads what's available from the terminal; false if `timeout` ran out
+fn read_more(pending: &mut Vec<u8>, timeout: Option<i32>) -> io::Result<bool> {
+    if let Some(timeout) = timeout {
+        let mut poll = libc::pollfd {
+            fd: libc::STDIN_FILENO,
+            events: libc::POLLIN,
+            revents: 0,
+        };
+        if unsafe { libc::poll(&mut poll, 1, timeout) } == 0 {
+            return Ok(false);
+        }
+    }
+    let mut buffer = [0u8; 64];
+    let read = unsafe { libc::read(libc::STDIN_FILENO, buffer.as_mut_ptr().cast(), buffer.len()) };
+    match read {
+        0 => Err(io::ErrorKind::UnexpectedEof.into()),
+        n if n < 0 => {
+            let error = io::Error::last_os_error();
+            match error.kind() {
+                io::ErrorKind::Interrupted => Ok(true),
+                _ => Err(error),
+            }
+        }
+        n => {
+            pending.extend_from_slice(&buffer[..n as usize]);
+            Ok(true)
+        }
+    }
+}
+
+// The key at the start of `bytes` and how many bytes it took, or `None`
+// until the rest of it has been read
+fn decode(bytes: &[u8]) -> Option<(Key, usize)> {
+    let key = match bytes {
+        [] | [0x1b] | [0x1b, b'O'] => return None,
+        [0x1b, b'[', rest @ ..] => {
+            let end = rest.iter().position(|b| (0x40..=0x7e).contains(b))?;
+            return Some((arrow(rest[end]), end + 3));
+        }
+        [0x1b, b'O', c, ..] => return Some((arrow(*c), 3)),
+        // Alt with another key
+        [0x1b, ..] => return Some((Key::Ignored, 2)),
+        [b'\r' | b'\n' | b'\t', ..] => Key::Accept,
+        [0x7f | 0x08, ..] => Key::Backspace,
+        [0x03 | 0x07, ..] => Key::Cancel,
+        [0x10, ..] => Key::Up,
+        // Ctrl-R again moves on, as in the editor's own search
+        [0x0e | 0x12, ..] => Key::Down,
+        [0x13, ..] => Key::CycleStatus,
+        [0x04, ..] => Key::ToggleDir,
+        [0x15, ..] => Key::ClearQuery,
+        [first, ..] if *first < 0x20 => Key::Ignored,
+        [first, ..] => return decode_char(bytes, *first),
+    };
+    Some((key, 1))
+}
+
+fn decode_char(bytes: &[u8], first: u8) -> Option<(Key, usize)> {
+    let length = match first {
+        0xc0..=0xdf => 2,
+        0xe0..=0xef => 3,
+        0xf0..=0xf7 => 4,
+        _ => 1,
+    };
+    let encoded = bytes.get(..length)?;
+    let key = match std::str::from_utf8(encoded)
+        .ok()
+        .and_then(|s| s.chars().next())
+    {
+        Some(c) => Key::Char(c),
+        None => Key::Ignored,
+    };
+    Some((key, length))
+}
+
+fn arrow(last: u8) -> Key {
+    match last {
+        b'A' => Key::Up,
+        b'B' => Key::Down,
+        _ => Key::Ignored,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_decode_keys() {
+        assert_eq!(decode(b"a"), Some((Key::Char('a'), 1)));
+        assert_eq!(decode(b"\x1b[A"), Some((Key::Up, 3)));
+        assert_eq!(decode(b"\x1bOB"), Some((Key::Down, 3)));
+        assert_eq!(decode(b"\x1b[1;5C"), Some((Key::Ignored, 6)));
+        assert_eq!(decode("é".as_bytes()), Some((Key::Char('é'), 2)));
+        assert_eq!(decode(&"é".as_bytes()[..1]), None);
+        assert_eq!(decode(b"\x1b"), None);
+        assert_eq!(decode(b"\x12"), Some((Key::Down, 1)));
+        assert_eq!(decode(b"\r"), Some((Key::Accept, 1)));
+    }
+
+    #[test]
+    fn test_filters_cycle() {
+        let mut finder = Finder {
+            query: String::new(),
+            filter: SearchFilter::default(),
+            cwd: Some("/src".into()),
+            selected: 3,
+            scroll: 0,
+        };
+        finder.apply(Key::CycleStatus, 10);
+        finder.apply(Key::CycleStatus, 10);
+        finder.apply(Key::ToggleDir, 10);
+        assert_eq!(finder.filter.succeeded, Some(false));
+        assert_eq!(finder.filter.cwd.as_deref(), Some("/src"));
+        assert_eq!(finder.selected, 0);
+        assert_eq!(finder.filter_label(), " [failed] [this directory]");
+
+        finder.apply(Key::Down, 2);
+        finder.apply(Key::Down, 2);
+        assert_eq!(finder.selected, 1);
+    }
+
+    #[test]
+    fn test_highlight_and_age() {
+        assert_eq!(highlight("ls -la", &[0], 10), "\x1b[1ml\x1b[22ms -la");
+        assert_eq!(highlight("echo hello", &[], 6), "echo …");
+        assert_eq!(relative_time(970, 1000), "just now");
+        assert_eq!(relative_time(1000, 8200), "2h ago");
+    }
+}
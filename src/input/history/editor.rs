@@ -0,0 +1,184 @@
+use std::borrow::Cow;
+use std::path::Path;
+use std::sync::{Arc, Mutex, MutexGuard};
+
+use rustyline::history::{History as LineHistory, SearchDirection, SearchResult};
+
+use super::History;
+
+/// The history as the line editor sees it, for Up-arrow recall and its
+/// searches: the commands in the shared [`History`], oldest first.
+///
+/// Commands are recorded by the shell once they've run, with their status,
+/// so lines the editor adds are ignored; the shell's history settings
+/// decide what's kept and where it's saved.
+#[derive(Clone)]
+pub struct EditorHistory {
+    history: Arc<Mutex<History>>,
+}
+
+impl EditorHistory {
+    pub fn new(history: Arc<Mutex<History>>) -> Self {
+        Self { history }
+    }
+
+    fn history(&self) -> MutexGuard<'_, History> {
+        self.history.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    // The nearest command from `start` on in `dir` that `test` finds a
+    // position in
+    fn search_match(
+        &self,
+        term: &str,
+        start: usize,
+        dir: SearchDirection,
+        test: impl Fn(&str) -> Option<usize>,
+    ) -> Option<SearchResult<'static>> {
+        let history = self.history();
+        if term.is_empty() || start >= history.command_count() {
+            return None;
+        }
+        let matching = |idx: usize| {
+            let command = history.command(idx)?;
+            Some((idx, command, test(command)?))
+        };
+        let found = match dir {
+            SearchDirection::Reverse => (0..=start).rev().find_map(matching),
+            SearchDirection::Forward => (start..history.command_count()).find_map(matching),
+        };
+        found.map(|(idx, command, pos)| SearchResult {
+            entry: Cow::Owned(command.to_string()),
+            idx,
+            pos,
+        })
+    }
+}
+
+impl LineHistory for EditorHistory {
+    fn get(&self, index: usize, _: SearchDirection) -> rustyline::Result<Option<SearchResult<'_>>> {
+        let history = self.history();
+        Ok(history.command(index).map(|command| SearchResult {
+            entry: Cow::Owned(command.to_string()),
+            idx: index,
+            pos: 0,
+        }))
+    }
+
+    fn add(&mut self, _: &str) -> rustyline::Result<bool> {
+        Ok(false)
+    }
+
+    fn add_owned(&mut self, _: String) -> rustyline::Result<bool> {
+        Ok(false)
+    }
+
+    fn len(&self) -> usize {
+        self.history().command_count()
+    }
+
+    fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    // The settings size the history, filter it and keep its file
+    fn set_max_len(&mut self, _: usize) -> rustyline::Result<()> {
+        Ok(())
+    }
+
+    fn ignore_dups(&mut self, _: bool) -> rustyline::Result<()> {
+        Ok(())
+    }
+
+    fn ignore_space(&mut self, _: bool) {}
+
+    fn save(&mut self, _: &Path) -> rustyline::Result<()> {
+        Ok(())
+    }
+
+    fn append(&mut self, _: &Path) -> rustyline::Result<()> {
+        Ok(())
+    }
+
+    fn load(&mut self, _: &Path) -> rustyline::Result<()> {
+        Ok(())
+    }
+
+    fn clear(&mut self) -> rustyline::Result<()> {
+        self.history()
+            .clear()
+            .map_err(|e| rustyline::error::ReadlineError::Io(std::io::Error::other(e.to_string())))
+    }
+
+    fn search(
+        &self,
+        term: &str,
+        start: usize,
+        dir: SearchDirection,
+    ) -> rustyline::Result<Option<SearchResult<'_>>> {
+        Ok(self.search_match(term, start, dir, |command| command.find(term)))
+    }
+
+    fn starts_with(
+        &self,
+        term: &str,
+        start: usize,
+        dir: SearchDirection,
+    ) -> rustyline::Result<Option<SearchResult<'_>>> {
+        Ok(self.search_match(term, start, dir, |command| {
+            command.starts_with(term).then_some(term.len())
+        }))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_editor_sees_the_shared_history() {
+        let path = std::env::temp_dir().join("aorta_history_editor_test");
+        let _ = std::fs::remove_file(&path);
+        let shared = Arc::new(Mutex::new(History::new(path.clone(), 100).unwrap()));
+        let mut editor = EditorHistory::new(Arc::clone(&shared));
+        for command in ["make", "git status", "make test"] {
+            shared.lock().unwrap().add(command).unwrap();
+        }
+        assert!(!editor.add("typed").unwrap());
+
+        let entry = |result: Option<SearchResult<'_>>| result.map(|r| (r.idx, r.entry.to_string()));
+        assert_eq!(editor.len(), 3);
+        assert_eq!(
+            entry(editor.get(1, SearchDirection::Forward).unwrap()),
+            Some((1, "git status".into()))
+        );
+        assert_eq!(
+            entry(editor.search("make", 1, SearchDirection::Reverse).unwrap()),
+            Some((0, "make".into()))
+        );
+        assert_eq!(
+            entry(
+                editor
+                    .starts_with("make", 1, SearchDirection::Forward)
+                    .unwrap()
+            ),
+            Some((2, "make test".into()))
+        );
+        assert_eq!(
+            editor
+                .search("", 0, SearchDirection::Forward)
+                .unwrap()
+                .map(|r| r.idx),
+            None
+        );
+
+        shared.lock().unwrap().delete_at(0).unwrap();
+        assert_eq!(
+            entry(editor.get(0, SearchDirection::Forward).unwrap()),
+            Some((0, "git status".into()))
+        );
+        editor.clear().unwrap();
+        assert!(shared.lock().unwrap().get_recent(10).is_empty());
+        std::fs::remove_file(path).unwrap();
+    }
+}
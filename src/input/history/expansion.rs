@@ -0,0 +1,595 @@
+use std::iter::Peekable;
+use std::str::Chars;
+
+use super::{History, HistoryEntry, HistoryError};
+
+/// A line after history expansion.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Expansion {
+    pub line: String,
+    // Whether anything was expanded, so the line is worth showing
+    pub expanded: bool,
+    // A `:p` modifier asked for the line to be shown rather than run
+    pub print_only: bool,
+}
+
+/// Expands csh-style history references in `line`: events like `!!`,
+/// `!-2`, `!42`, `!git` and `!?main?`, each optionally followed by word
+/// designators (`:0`, `:1-3`, `:$`, `:*`) and modifiers (`:h :t :r :e :p
+/// :s/old/new/`), and `^old^new` at the start of the line. Nothing in
+/// single quotes or after a backslash is expanded.
+pub fn expand(line: &str, history: &History) -> Result<Expansion, HistoryError> {
+    let commands = history
+        .get_recent(usize::MAX)
+        .into_iter()
+        .filter_map(|entry| match entry {
+            HistoryEntry::Command { command, .. } => Some(command.as_ref()),
+            HistoryEntry::Event { .. } => None,
+        })
+        .collect();
+    let mut expander = Expander {
+        commands,
+        chars: line.chars().collect(),
+        pos: 0,
+        output: String::with_capacity(line.len()),
+        expanded: false,
+        print_only: false,
+        substitution: None,
+    };
+    expander.run()?;
+    Ok(Expansion {
+        line: expander.output,
+        expanded: expander.expanded,
+        print_only: expander.print_only,
+    })
+}
+
+// Where a word designator starts or ends
+#[derive(Debug, Clone, Copy)]
+enum Bound {
+    Word(usize),
+    Last,
+    // `x-` runs up to but not including the last word
+    BeforeLast,
+}
+
+struct Expander<'a> {
+    // Newest first
+    commands: Vec<&'a str>,
+    chars: Vec<char>,
+    pos: usize,
+    output: String,
+    expanded: bool,
+    print_only: bool,
+    // The last `:s` old and new text, for `:&` and an empty old text
+    substitution: Option<(String, String)>,
+}
+
+impl Expander<'_> {
+    fn run(&mut self) -> Result<(), HistoryError> {
+        if self.peek() == Some('^') {
+            self.quick_substitution()?;
+        }
+        let mut in_double = false;
+        while let Some(c) = self.peek() {
+            match c {
+                '\\' => {
+                    self.copy(2);
+                    continue;
+                }
+                '\'' if !in_double => {
+                    let length = self.chars[self.pos + 1..]
+                        .iter()
+                        .position(|&c| c == '\'')
+                        .map_or(self.chars.len() - self.pos, |end| end + 2);
+                    self.copy(length);
+                    continue;
+                }
+                '"' => in_double = !in_double,
+                '!' if self.starts_event(in_double) => {
+                    self.expand_event()?;
+                    continue;
+                }
+                _ => {}
+            }
+            self.copy(1);
+        }
+        Ok(())
+    }
+
+    fn peek(&self) -> Option<char> {
+        self.chars.get(self.pos).copied()
+    }
+
+    fn peek_at(&self, offset: usize) -> Option<char> {
+        self.chars.get(self.pos + offset).copied()
+    }
+
+    fn copy(&mut self, count: usize) {
+        let end = (self.pos + count).min(self.chars.len());
+        self.output.extend(&self.chars[self.pos..end]);
+        self.pos = end;
+    }
+
+    // Takes characters up to one of `ends` or the end of the line
+    fn take_until(&mut self, ends: &[char]) -> String {
+        let start = self.pos;
+        while self.peek().is_some_and(|c| !ends.contains(&c)) {
+            self.pos += 1;
+        }
+        self.chars[start..self.pos].iter().collect()
+    }
+
+    // A `!` that isn't followed by a blank, `=` or `(`, or part of `$!` or
+    // `${!name}`
+    fn starts_event(&self, in_double: bool) -> bool {
+        let Some(next) = self.peek_at(1) else {
+            return false;
+        };
+        let in_parameter = self.chars[..self.pos].ends_with(&['$'])
+            || self.chars[..self.pos].ends_with(&['$', '{']);
+        !(next.is_whitespace()
+            || matches!(next, '=' | '(')
+            || (in_double && next == '"')
+            || in_parameter)
+    }
+
+    // `^old^new^` is `!!:s/old/new/`
+    fn quick_substitution(&mut self) -> Result<(), HistoryError> {
+        self.pos = 1;
+        let old = self.take_until(&['^']);
+        self.pos += 1;
+        let new = self.take_until(&['^', '\n']);
+        if self.peek() == Some('^') {
+            self.pos += 1;
+        }
+        let previous = self.event(1, "^")?;
+        let substituted = self.substitute(&previous, old, new, false)?;
+        self.output.push_str(&substituted);
+        self.expanded = true;
+        Ok(())
+    }
+
+    fn expand_event(&mut self) -> Result<(), HistoryError> {
+        let start = self.pos;
+        self.pos += 1;
+        let text = self.event_text(start)?;
+        let text = match self.word_designator() {
+            Some((first, last)) => {
+                let spec: String = self.chars[start..self.pos].iter().collect();
+                select_words(&text, first, last).ok_or(HistoryError::BadWordSpecifier(spec))?
+            }
+            None => text,
+        };
+        let text = self.apply_modifiers(text)?;
+        self.output.push_str(&text);
+        self.expanded = true;
+        Ok(())
+    }
+
+    // The command an event designator refers to, the `!` already taken
+    fn event_text(&mut self, start: usize) -> Result<String, HistoryError> {
+        let spec =
+            |expander: &Self| -> String { expander.chars[start..expander.pos].iter().collect() };
+        match self.peek() {
+            Some('!') => {
+                self.pos += 1;
+                self.event(1, &spec(self))
+            }
+            // `!$`, `!^`, `!*` and `!:n` pick words of the previous command
+            Some('$' | '^' | '*' | ':') => self.event(1, &spec(self)),
+            Some('#') => {
+                self.pos += 1;
+                Ok(self.output.clone())
+            }
+            Some('-') if self.peek_at(1).is_some_and(|c| c.is_ascii_digit()) => {
+                self.pos += 1;
+                let back = self.number();
+                self.event(back, &spec(self))
+            }
+            Some(c) if c.is_ascii_digit() => {
+                let number = self.number();
+                let back = (self.commands.len() + 1).checked_sub(number);
+                self.event(back.unwrap_or(0), &spec(self))
+            }
+            Some('?') => {
+                self.pos += 1;
+                let needle = self.take_until(&['?', '\n']);
+                if self.peek() == Some('?') {
+                    self.pos += 1;
+                }
+                self.find(|command| command.contains(needle.as_str()), &spec(self))
+            }
+            _ => {
+                let prefix = self.take_until(&[
+                    ' ', '\t', '\n', ':', ';', '&', '|', '<', '>', '(', ')', '"', '\'', '`',
+                ]);
+                self.find(|command| command.starts_with(prefix.as_str()), &spec(self))
+            }
+        }
+    }
+
+    fn number(&mut self) -> usize {
+        let start = self.pos;
+        while self.peek().is_some_and(|c| c.is_ascii_digit()) {
+            self.pos += 1;
+        }
+        let digits: String = self.chars[start..self.pos].iter().collect();
+        digits.parse().unwrap_or(usize::MAX)
+    }
+
+    // The command `back` entries ago, 1 being the last one
+    fn event(&self, back: usize, spec: &str) -> Result<String, HistoryError> {
+        back.checked_sub(1)
+            .and_then(|index| self.commands.get(index))
+            .map(|command| command.to_string())
+            .ok_or_else(|| HistoryError::EventNotFound(spec.to_string()))
+    }
+
+    fn find(&self, matches: impl Fn(&str) -> bool, spec: &str) -> Result<String, HistoryError> {
+        self.commands
+            .iter()
+            .find(|command| matches(command))
+            .map(|command| command.to_string())
+            .ok_or_else(|| HistoryError::EventNotFound(spec.to_string()))
+    }
+
+    // `:n`, `:x-y`, `:x-`, `:-y`, `:x*`, `:^`, `:$` and `:*`; the colon can
+    // be left out before `^`, `$` and `*`
+    fn word_designator(&mut self) -> Option<(Bound, Bound)> {
+        let after_colon = self.peek_at(1);
+        match self.peek() {
+            Some(':') if after_colon.is_some_and(|c| c.is_ascii_digit() || "^$*-".contains(c)) => {
+                self.pos += 1;
+            }
+            Some('^' | '$' | '*') => {}
+            _ => return None,
+        }
+        let bounds = match self.peek() {
+            Some('^') => (Bound::Word(1), Bound::Word(1)),
+            Some('$') => (Bound::Last, Bound::Last),
+            Some('*') => (Bound::Word(1), Bound::Last),
+            Some('-') => {
+                self.pos += 1;
+                return Some((Bound::Word(0), self.range_end()));
+            }
+            _ => {
+                let first = Bound::Word(self.number());
+                let last = match self.peek() {
+                    Some('*') => Bound::Last,
+                    Some('-') => {
+                        self.pos += 1;
+                        return Some((first, self.range_end()));
+                    }
+                    _ => return Some((first, first)),
+                };
+                (first, last)
+            }
+        };
+        self.pos += 1;
+        Some(bounds)
+    }
+
+    // What follows the `-` of a range: a word number, `$`, or nothing
+    fn range_end(&mut self) -> Bound {
+        match self.peek() {
+            Some('$') => {
+                self.pos += 1;
+                Bound::Last
+            }
+            Some(c) if c.is_ascii_digit() => Bound::Word(self.number()),
+            _ => Bound::BeforeLast,
+        }
+    }
+
+    fn apply_modifiers(&mut self, mut text: String) -> Result<String, HistoryError> {
+        while self.peek() == Some(':') {
+            let Some(modifier) = self
+                .peek_at(1)
+                .filter(|c| c.is_ascii_alphabetic() || *c == '&')
+            else {
+                // `scp !$:/tmp` and the like
+                break;
+            };
+            self.pos += 2;
+            text = match modifier {
+                'h' => text
+                    .rfind('/')
+                    .map_or(text.clone(), |slash| text[..slash].to_string()),
+                't' => text
+                    .rfind('/')
+                    .map_or(text.clone(), |slash| text[slash + 1..].to_string()),
+                'r' => match suffix_start(&text) {
+                    Some(dot) => text[..dot].to_string(),
+                    None => text,
+                },
+                'e' => match suffix_start(&text) {
+                    Some(dot) => text[dot..].to_string(),
+                    None => String::new(),
+                },
+                'p' => {
+                    self.print_only = true;
+                    text
+                }
+                's' => self.substitution_modifier(&text, false)?,
+                'g' if self.peek() == Some('s') => {
+                    self.pos += 1;
+                    self.substitution_modifier(&text, true)?
+                }
+                '&' => match self.substitution.clone() {
+                    Some((old, new)) => self.substitute(&text, old, new, false)?,
+                    None => return Err(HistoryError::SubstitutionFailed),
+                },
+                _ => return Err(HistoryError::BadModifier(modifier)),
+            };
+        }
+        Ok(text)
+    }
+
+    // `s/old/new/`, with any delimiter; the last one can be left off at the
+    // end of the line
+    fn substitution_modifier(&mut self, text: &str, global: bool) -> Result<String, HistoryError> {
+        let Some(delimiter) = self.peek() else {
+            return Err(HistoryError::SubstitutionFailed);
+        };
+        self.pos += 1;
+        let old = self.take_until(&[delimiter]);
+        self.pos += 1;
+        let new = self.take_until(&[delimiter, '\n']);
+        if self.peek() == Some(delimiter) {
+            self.pos += 1;
+        }
+        self.substitute(text, old, new, global)
+    }
+
+    fn substitute(
+        &mut self,
+        text: &str,
+        old: String,
+        new: String,
+        global: bool,
+    ) -> Result<String, HistoryError> {
+        // An empty old text means the one from the last substitution
+        let old = match (old.is_empty(), &self.substitution) {
+            (false, _) => old,
+            (true, Some((last, _))) => last.clone(),
+            (true, None) => return Err(HistoryError::SubstitutionFailed),
+        };
+        if !text.contains(old.as_str()) {
+            return Err(HistoryError::SubstitutionFailed);
+        }
+        // `&` in the new text stands for the old one
+        let replacement = new
+            .replace("\\&", "\u{0}")
+            .replace('&', &old)
+            .replace('\u{0}', "&");
+        self.substitution = Some((old.clone(), new));
+        Ok(if global {
+            text.replace(old.as_str(), &replacement)
+        } else {
+            text.replacen(old.as_str(), &replacement, 1)
+        })
+    }
+}
+
+// The `.` starting a file name's suffix, if it has one
+fn suffix_start(text: &str) -> Option<usize> {
+    let name_start = text.rfind('/').map_or(0, |slash| slash + 1);
+    text[name_start..]
+        .rfind('.')
+        .filter(|&dot| dot > 0)
+        .map(|dot| name_start + dot)
+}
+
+// Words `first` to `last` of a command, joined with spaces
+fn select_words(command: &str, first: Bound, last: Bound) -> Option<String> {
+    let words = history_words(command);
+    let resolve = |bound: Bound| match bound {
+        Bound::Word(n) => Some(n),
+        Bound::Last => words.len().checked_sub(1),
+        Bound::BeforeLast => words.len().checked_sub(2),
+    };
+    let (first, last) = (resolve(first)?, resolve(last));
+    // `!*` of a command without arguments is empty, not an error
+    if first == words.len() && last == first.checked_sub(1) {
+        return Some(String::new());
+    }
+    let last = last?;
+    (first <= last && last < words.len()).then(|| words[first..=last].join(" "))
+}
+
+// A command's words as typed, quotes kept; runs of `|&;<>()` are words of
+// their own
+fn history_words(command: &str) -> Vec<String> {
+    let mut words = Vec::new();
+    let mut word = String::new();
+    let mut chars = command.chars().peekable();
+    while let Some(c) = chars.next() {
+        if c.is_whitespace() || OPERATORS.contains(c) {
+            if !word.is_empty() {
+                words.push(std::mem::take(&mut word));
+            }
+            if !c.is_whitespace() {
+                words.push(operator(c, &mut chars));
+            }
+            continue;
+        }
+        word.push(c);
+        match c {
+            '\\' => word.extend(chars.next()),
+            '\'' | '"' => quoted(c, &mut chars, &mut word),
+            _ => {}
+        }
+    }
+    if !word.is_empty() {
+        words.push(word);
+    }
+    words
+}
+
+const OPERATORS: &str = "|&;<>()";
+
+fn operator(first: char, chars: &mut Peekable<Chars<'_>>) -> String {
+    let mut operator = first.to_string();
+    while let Some(next) = chars.next_if(|&next| OPERATORS.contains(next)) {
+        operator.push(next);
+    }
+    operator
+}
+
+// The rest of a quoted string, up to and including the closing quote
+fn quoted(quote: char, chars: &mut Peekable<Chars<'_>>, word: &mut String) {
+    for c in chars.by_ref() {
+        word.push(c);
+        if c == quote {
+            break;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn history_of(name: &str, commands: &[&str]) -> History {
+        let path = std::env::temp_dir().join(name);
+        let _ = std::fs::remove_file(&path);
+        let mut history = History::new(path, 100).unwrap();
+        for command in commands {
+            history.add(command).unwrap();
+        }
+        history
+    }
+
+    fn assert_expands(history: &History, cases: &[(&str, &str)]) {
+        for (line, expected) in cases {
+            let expansion = expand(line, history).unwrap();
+            assert_eq!(&expansion.line, expected, "expanding {:?}", line);
+            assert_eq!(expansion.expanded, line != expected, "expanding {:?}", line);
+        }
+    }
+
+    #[test]
+    fn test_event_designators() {
+        let history = history_of(
+            "aorta_history_expand_events_test",
+            &["make all", "vim src/main.rs", "git commit -m 'a b'"],
+        );
+        assert_expands(
+            &history,
+            &[
+                ("sudo !!", "sudo git commit -m 'a b'"),
+                ("!-3", "make all"),
+                ("!2; !1", "vim src/main.rs; make all"),
+                ("!vi --clean", "vim src/main.rs --clean"),
+                ("!?main?", "vim src/main.rs"),
+                // Left alone
+                ("echo 'a!!'", "echo 'a!!'"),
+                ("echo \\!!", "echo \\!!"),
+                ("[ a != b ]", "[ a != b ]"),
+                ("! true", "! true"),
+                ("${!name}", "${!name}"),
+                ("hi!", "hi!"),
+            ],
+        );
+        assert!(matches!(
+            expand("!nope", &history),
+            Err(HistoryError::EventNotFound(spec)) if spec == "!nope"
+        ));
+        assert!(expand("!9", &history).is_err());
+        std::fs::remove_file(history.path()).unwrap();
+    }
+
+    #[test]
+    fn test_background_pid_is_literal() {
+        let history = history_of("aorta_history_expand_pid_test", &["sleep 5 &"]);
+        assert_expands(
+            &history,
+            &[
+                ("echo $!;", "echo $!;"),
+                ("kill $!x", "kill $!x"),
+                ("wait \"$!\" && !!", "wait \"$!\" && sleep 5 &"),
+            ],
+        );
+        std::fs::remove_file(history.path()).unwrap();
+    }
+
+    #[test]
+    fn test_word_designators() {
+        let history = history_of(
+            "aorta_history_expand_words_test",
+            &["cp 'my file' /tmp/dest.txt extra|wc -l"],
+        );
+        assert_expands(
+            &history,
+            &[
+                ("!!:0", "cp"),
+                ("ls !^", "ls 'my file'"),
+                ("cat !$", "cat -l"),
+                ("!!:1-2", "'my file' /tmp/dest.txt"),
+                ("!!:3*", "extra | wc -l"),
+                ("!!:2-", "/tmp/dest.txt extra | wc"),
+                ("!!:-1", "cp 'my file'"),
+                ("echo !:2", "echo /tmp/dest.txt"),
+            ],
+        );
+        assert!(matches!(
+            expand("!!:9", &history),
+            Err(HistoryError::BadWordSpecifier(_))
+        ));
+        std::fs::remove_file(history.path()).unwrap();
+
+        let history = history_of("aorta_history_expand_star_test", &["ls"]);
+        assert_expands(&history, &[("echo !*", "echo ")]);
+        std::fs::remove_file(history.path()).unwrap();
+    }
+
+    #[test]
+    fn test_modifiers() {
+        let history = history_of(
+            "aorta_history_expand_modifiers_test",
+            &["vim src/input/main.rs"],
+        );
+        assert_expands(
+            &history,
+            &[
+                ("cd !$:h", "cd src/input"),
+                ("echo !$:t", "echo main.rs"),
+                ("echo !$:r", "echo src/input/main"),
+                ("echo !$:t:e", "echo .rs"),
+                ("!!:s/main/lib/", "vim src/input/lib.rs"),
+                ("!!:gs/i/I", "vIm src/Input/maIn.rs"),
+                ("!!:s/vim/& -R/", "vim -R src/input/main.rs"),
+                ("!!:s/i/I/:&", "vIm src/Input/main.rs"),
+                ("scp !$:/tmp", "scp src/input/main.rs:/tmp"),
+            ],
+        );
+
+        let printed = expand("!!:p", &history).unwrap();
+        assert!(printed.print_only);
+        assert_eq!(printed.line, "vim src/input/main.rs");
+        assert!(matches!(
+            expand("!!:s/nope/x/", &history),
+            Err(HistoryError::SubstitutionFailed)
+        ));
+        assert!(matches!(
+            expand("!!:z", &history),
+            Err(HistoryError::BadModifier('z'))
+        ));
+        std::fs::remove_file(history.path()).unwrap();
+    }
+
+    #[test]
+    fn test_quick_substitution() {
+        let history = history_of("aorta_history_expand_quick_test", &["cargo tset --lib"]);
+        assert_expands(
+            &history,
+            &[
+                ("^tset^test", "cargo test --lib"),
+                ("^tset^test^ -q", "cargo test --lib -q"),
+            ],
+        );
+        assert!(expand("^nope^x", &history).is_err());
+        std::fs::remove_file(history.path()).unwrap();
+    }
+}
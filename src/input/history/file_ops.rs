@@ -1,97 +1,489 @@
 use std::{
     borrow::Cow,
-    fs::{File, OpenOptions},
-    io::{BufRead, BufReader, Write},
+    fs::{self, File, OpenOptions},
+    io::{self, BufWriter, Read, Seek, SeekFrom, Write},
+    os::unix::fs::{MetadataExt, OpenOptionsExt},
+    os::unix::io::AsRawFd,
     path::PathBuf,
 };
 
 use super::types::HistoryEntry;
 use super::HistoryError;
 
+// The first line of a history file in the current format. Each line after
+// it is a record of tab-separated fields, with tabs, newlines and
+// backslashes in them escaped:
+//   c <start> <end> <exit code> <duration ms> <session> <host> <cwd> <command>
+//   e <timestamp> <description>
+// A missing session, host or directory is an empty field.
+const HEADER: &str = "#aorta-history 2";
+
 pub struct FileOps {
     file_path: PathBuf,
+    // The file as last read, to pick up only what's been added since
+    read: Option<ReadPosition>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct ReadPosition {
+    device: u64,
+    inode: u64,
+    offset: u64,
+}
+
+/// Holds the file locked while it's rewritten.
+pub struct RewriteLock {
+    _file: File,
+}
+
+/// What other sessions did to the file since it was last read.
+#[derive(Debug)]
+pub enum FileChanges {
+    Appended(Vec<HistoryEntry>),
+    // It was replaced, by a deletion say: these are all of its entries
+    Rewritten(Vec<HistoryEntry>),
 }
 
 impl FileOps {
     pub fn new(file_path: PathBuf) -> Self {
-        Self { file_path }
+        Self {
+            file_path,
+            read: None,
+        }
     }
 
     pub fn get_path(&self) -> &PathBuf {
         &self.file_path
     }
 
-    pub fn load_entries(&self) -> Result<Vec<HistoryEntry>, HistoryError> {
-        let mut entries = Vec::new();
+    /// Reads every entry in the file. A file in the old one-line format is
+    /// rewritten in the current one; records that can't be read, say from
+    /// a newer version, are skipped.
+    pub fn load_entries(&mut self) -> Result<Vec<HistoryEntry>, HistoryError> {
+        self.read = None;
+        if !self.file_path.exists() {
+            return Ok(Vec::new());
+        }
+        // Exclusive, so no one appends to an old file while it's migrated
+        let mut file = self.open_locked(libc::LOCK_EX)?;
+        self.load_locked(&mut file)
+    }
 
-        if self.file_path.exists() {
-            let file = File::open(&self.file_path).map_err(HistoryError::IoError)?;
-            let reader = BufReader::new(file);
-
-            for line in reader.lines() {
-                let line = line.map_err(HistoryError::IoError)?;
-                if !line.trim().is_empty() {
-                    let parts: Vec<&str> = line.split('\x1F').collect();
-                    match parts.as_slice() {
-                        [command, timestamp, exit_code, duration] => {
-                            let timestamp = timestamp.parse().map_err(|_| {
-                                HistoryError::FileOperationError("Invalid timestamp".into())
-                            })?;
-                            let exit_code = exit_code.parse().map_err(|_| {
-                                HistoryError::FileOperationError("Invalid exit code".into())
-                            })?;
-                            let duration = duration.parse().map_err(|_| {
-                                HistoryError::FileOperationError("Invalid duration".into())
-                            })?;
-
-                            entries.push(HistoryEntry::Command {
-                                command: Cow::Owned(command.to_string()),
-                                timestamp,
-                                exit_code,
-                                duration,
-                            });
-                        }
-                        _ => {
-                            entries.push(HistoryEntry::new_command(line, 0, 0));
-                        }
-                    }
-                }
+    fn load_locked(&mut self, file: &mut File) -> Result<Vec<HistoryEntry>, HistoryError> {
+        let mut bytes = Vec::new();
+        file.seek(SeekFrom::Start(0))
+            .and_then(|_| file.read_to_end(&mut bytes))
+            .map_err(HistoryError::IoError)?;
+        self.read = Some(position(file, bytes.len() as u64)?);
+        let contents = String::from_utf8_lossy(&bytes);
+
+        let mut lines = contents.lines();
+        match lines.next() {
+            None => Ok(Vec::new()),
+            Some(HEADER) => Ok(lines.filter_map(parse_record).collect()),
+            Some(_) => {
+                let entries: Vec<_> = contents.lines().filter_map(parse_legacy).collect();
+                self.replace(&entries)?;
+                Ok(entries)
             }
         }
+    }
 
-        Ok(entries)
+    /// Entries other sessions wrote since the file was last read or
+    /// written here.
+    pub fn read_changes(&mut self) -> Result<FileChanges, HistoryError> {
+        if !self.file_path.exists() {
+            return Ok(FileChanges::Appended(Vec::new()));
+        }
+        let mut file = self.open_locked(libc::LOCK_SH)?;
+        self.changes_locked(&mut file)
+    }
+
+    fn changes_locked(&mut self, file: &mut File) -> Result<FileChanges, HistoryError> {
+        let current = position(file, 0)?;
+        let length = file.metadata().map_err(HistoryError::IoError)?.len();
+        let start = match self.read {
+            // It didn't exist when last read
+            None => 0,
+            Some(read)
+                if (read.device, read.inode) == (current.device, current.inode)
+                    && read.offset <= length =>
+            {
+                read.offset
+            }
+            Some(_) => return self.load_locked(file).map(FileChanges::Rewritten),
+        };
+
+        let (contents, offset) = read_from(file, start)?;
+        self.read = Some(ReadPosition { offset, ..current });
+        let entries = contents
+            .lines()
+            .filter(|line| *line != HEADER)
+            .filter_map(parse_record)
+            .collect();
+        Ok(FileChanges::Appended(entries))
     }
 
     pub fn append_entry(&self, entry: &HistoryEntry) -> Result<(), HistoryError> {
-        let mut file = OpenOptions::new()
-            .create(true)
-            .append(true)
-            .open(&self.file_path)
-            .map_err(HistoryError::IoError)?;
+        let mut file = self.open_locked(libc::LOCK_EX)?;
 
-        match entry {
-            HistoryEntry::Command {
-                command,
-                timestamp,
-                exit_code,
-                duration,
-            } => {
-                writeln!(
-                    file,
-                    "{}\x1F{}\x1F{}\x1F{}",
-                    command, timestamp, exit_code, duration
-                )
+        let mut record = String::new();
+        if file.metadata().map_err(HistoryError::IoError)?.len() == 0 {
+            record.push_str(HEADER);
+            record.push('\n');
+        }
+        record.push_str(&format_record(entry));
+        record.push('\n');
+        file.write_all(record.as_bytes())
+            .map_err(HistoryError::IoError)
+    }
+
+    /// Locks the file to rewrite it, returning what other sessions wrote
+    /// since it was last read so they can be kept. No one else can change
+    /// it until [`FileOps::finish_rewrite`].
+    pub fn begin_rewrite(&mut self) -> Result<(RewriteLock, FileChanges), HistoryError> {
+        let mut file = self.open_locked(libc::LOCK_EX)?;
+        let changes = self.changes_locked(&mut file)?;
+        Ok((RewriteLock { _file: file }, changes))
+    }
+
+    /// Replaces the file's contents with `entries`. Readers see either the
+    /// old file or the new one, never a partly written one.
+    pub fn finish_rewrite(
+        &mut self,
+        lock: RewriteLock,
+        entries: &[HistoryEntry],
+    ) -> Result<(), HistoryError> {
+        let result = self.replace(entries);
+        drop(lock);
+        result
+    }
+
+    // Writes a new file beside the old one and renames it over it, with the
+    // old one locked
+    fn replace(&mut self, entries: &[HistoryEntry]) -> Result<(), HistoryError> {
+        let mut name = self.file_path.as_os_str().to_owned();
+        name.push(format!(".{}.tmp", std::process::id()));
+        let temp_path = PathBuf::from(name);
+
+        let written = (|| {
+            let file = OpenOptions::new()
+                .write(true)
+                .create(true)
+                .truncate(true)
+                .mode(0o600)
+                .open(&temp_path)?;
+            let mut writer = BufWriter::new(file);
+            writeln!(writer, "{}", HEADER)?;
+            for entry in entries {
+                writeln!(writer, "{}", format_record(entry))?;
+            }
+            let file = writer.into_inner().map_err(|e| e.into_error())?;
+            file.sync_all()?;
+            let offset = file.metadata()?.len();
+            fs::rename(&temp_path, &self.file_path)?;
+            position(&file, offset).map_err(|_| io::Error::other("lost the new file"))
+        })();
+        match written {
+            Ok(read) => {
+                self.read = Some(read);
+                Ok(())
+            }
+            Err(e) => {
+                let _ = fs::remove_file(&temp_path);
+                Err(HistoryError::IoError(e))
+            }
+        }
+    }
+
+    // Opens the file for appending and locks it. If someone renamed a new
+    // file into place while this waited for the lock, it opens that one.
+    fn open_locked(&self, operation: libc::c_int) -> Result<File, HistoryError> {
+        loop {
+            let file = OpenOptions::new()
+                .read(true)
+                .append(true)
+                .create(true)
+                .mode(0o600)
+                .open(&self.file_path)
                 .map_err(HistoryError::IoError)?;
+            if unsafe { libc::flock(file.as_raw_fd(), operation) } != 0 {
+                return Err(HistoryError::LockError(
+                    io::Error::last_os_error().to_string(),
+                ));
             }
-            HistoryEntry::Event {
-                description,
+            let opened = file.metadata().map_err(HistoryError::IoError)?;
+            match fs::metadata(&self.file_path) {
+                Ok(current) if current.dev() == opened.dev() && current.ino() == opened.ino() => {
+                    return Ok(file);
+                }
+                Ok(_) => continue,
+                Err(e) => return Err(HistoryError::IoError(e)),
+            }
+        }
+    }
+}
+
+// The complete lines from `offset` on, and where they end
+fn read_from(file: &mut File, offset: u64) -> Result<(String, u64), HistoryError> {
+    let mut bytes = Vec::new();
+    file.seek(SeekFrom::Start(offset))
+        .and_then(|_| file.read_to_end(&mut bytes))
+        .map_err(HistoryError::IoError)?;
+    // A record still being written is left for next time
+    let complete = bytes.iter().rposition(|&b| b == b'\n').map_or(0, |i| i + 1);
+    bytes.truncate(complete);
+    let contents = String::from_utf8_lossy(&bytes).into_owned();
+    Ok((contents, offset + complete as u64))
+}
+
+fn position(file: &File, offset: u64) -> Result<ReadPosition, HistoryError> {
+    let metadata = file.metadata().map_err(HistoryError::IoError)?;
+    Ok(ReadPosition {
+        device: metadata.dev(),
+        inode: metadata.ino(),
+        offset,
+    })
+}
+
+fn format_record(entry: &HistoryEntry) -> String {
+    match entry {
+        HistoryEntry::Command {
+            command,
+            timestamp,
+            end_time,
+            exit_code,
+            duration,
+            cwd,
+            session,
+            hostname,
+        } => {
+            let optional = |field: &Option<String>| escape(field.as_deref().unwrap_or_default());
+            format!(
+                "c\t{}\t{}\t{}\t{}\t{}\t{}\t{}\t{}",
                 timestamp,
-            } => {
-                writeln!(file, "{}\x1F{}\x1F0\x1F0", description, timestamp)
-                    .map_err(HistoryError::IoError)?;
+                end_time,
+                exit_code,
+                duration,
+                optional(session),
+                optional(hostname),
+                optional(cwd),
+                escape(command)
+            )
+        }
+        HistoryEntry::Event {
+            description,
+            timestamp,
+        } => format!("e\t{}\t{}", timestamp, escape(description)),
+    }
+}
+
+fn parse_record(line: &str) -> Option<HistoryEntry> {
+    let fields: Vec<&str> = line.split('\t').collect();
+    match fields.as_slice() {
+        ["c", timestamp, end_time, exit_code, duration, session, hostname, cwd, command] => {
+            let optional = |field: &str| (!field.is_empty()).then(|| unescape(field));
+            Some(HistoryEntry::Command {
+                command: Cow::Owned(unescape(command)),
+                timestamp: timestamp.parse().ok()?,
+                end_time: end_time.parse().ok()?,
+                exit_code: exit_code.parse().ok()?,
+                duration: duration.parse().ok()?,
+                cwd: optional(cwd),
+                session: optional(session),
+                hostname: optional(hostname),
+            })
+        }
+        ["e", timestamp, description] => Some(HistoryEntry::Event {
+            description: Cow::Owned(unescape(description)),
+            timestamp: timestamp.parse().ok()?,
+        }),
+        _ => None,
+    }
+}
+
+// The old format: `command \x1F finished \x1F exit code \x1F duration`, or
+// a bare command. Events were written the same way, so they come back as
+// commands.
+fn parse_legacy(line: &str) -> Option<HistoryEntry> {
+    if line.trim().is_empty() {
+        return None;
+    }
+    let parsed = match line.split('\x1F').collect::<Vec<_>>().as_slice() {
+        [command, end_time, exit_code, duration] => (|| {
+            let end_time: u64 = end_time.parse().ok()?;
+            let duration: u64 = duration.parse().ok()?;
+            Some(HistoryEntry::Command {
+                command: Cow::Owned(command.to_string()),
+                timestamp: end_time.saturating_sub(duration / 1000),
+                end_time,
+                exit_code: exit_code.parse().ok()?,
+                duration,
+                cwd: None,
+                session: None,
+                hostname: None,
+            })
+        })(),
+        _ => None,
+    };
+    Some(parsed.unwrap_or_else(|| HistoryEntry::new_command(line.to_string(), 0, 0)))
+}
+
+fn escape(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '\\' => escaped.push_str("\\\\"),
+            '\t' => escaped.push_str("\\t"),
+            '\n' => escaped.push_str("\\n"),
+            '\r' => escaped.push_str("\\r"),
+            c => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+fn unescape(text: &str) -> String {
+    let mut unescaped = String::with_capacity(text.len());
+    let mut chars = text.chars();
+    while let Some(c) = chars.next() {
+        if c != '\\' {
+            unescaped.push(c);
+            continue;
+        }
+        match chars.next() {
+            Some('t') => unescaped.push('\t'),
+            Some('n') => unescaped.push('\n'),
+            Some('r') => unescaped.push('\r'),
+            Some(other) => unescaped.push(other),
+            None => unescaped.push('\\'),
+        }
+    }
+    unescaped
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn temp_file(name: &str) -> FileOps {
+        let path = std::env::temp_dir().join(name);
+        let _ = fs::remove_file(&path);
+        FileOps::new(path)
+    }
+
+    #[test]
+    fn test_records_round_trip() {
+        let mut file = temp_file("aorta_history_round_trip_test");
+        let command = HistoryEntry::new_command("for f in *\ndo\techo \\$f\ndone", 1, 2500)
+            .with_cwd("/tmp/a dir")
+            .with_origin("s1", "host");
+        file.append_entry(&command).unwrap();
+        file.append_entry(&HistoryEntry::new_event("cleared\nhistory"))
+            .unwrap();
+
+        let entries = file.load_entries().unwrap();
+        assert_eq!(entries.len(), 2);
+        let HistoryEntry::Command {
+            command,
+            timestamp,
+            end_time,
+            cwd,
+            session,
+            hostname,
+            ..
+        } = &entries[0]
+        else {
+            panic!("expected a command, got {:?}", entries[0]);
+        };
+        assert_eq!(command, "for f in *\ndo\techo \\$f\ndone");
+        assert_eq!(end_time - timestamp, 2);
+        assert_eq!(cwd.as_deref(), Some("/tmp/a dir"));
+        assert_eq!(session.as_deref(), Some("s1"));
+        assert_eq!(hostname.as_deref(), Some("host"));
+        assert!(
+            matches!(&entries[1], HistoryEntry::Event { description, .. }
+            if description == "cleared\nhistory")
+        );
+        fs::remove_file(file.get_path()).unwrap();
+    }
+
+    #[test]
+    fn test_legacy_file_is_migrated() {
+        let mut file = temp_file("aorta_history_migrate_test");
+        fs::write(
+            file.get_path(),
+            "ls -la\x1F1700000010\x1F0\x1F3000\nbad\x1Fline\n\nmake\n",
+        )
+        .unwrap();
+
+        let entries = file.load_entries().unwrap();
+        let commands: Vec<_> = entries
+            .iter()
+            .map(|entry| match entry {
+                HistoryEntry::Command {
+                    command, timestamp, ..
+                } => (command.to_string(), *timestamp),
+                HistoryEntry::Event { .. } => panic!("unexpected event"),
+            })
+            .collect();
+        assert_eq!(commands[0], ("ls -la".to_string(), 1700000007));
+        assert_eq!(commands[1].0, "bad\x1Fline");
+        assert_eq!(commands[2].0, "make");
+
+        let rewritten = fs::read_to_string(file.get_path()).unwrap();
+        assert!(rewritten.starts_with(HEADER));
+        assert_eq!(file.load_entries().unwrap().len(), 3);
+        fs::remove_file(file.get_path()).unwrap();
+    }
+
+    #[test]
+    fn test_unreadable_records_are_skipped() {
+        let mut file = temp_file("aorta_history_skip_test");
+        let contents = format!("{HEADER}\nx\tfuture\nc\tnot a number\ne\t5\tkept\n");
+        fs::write(file.get_path(), contents).unwrap();
+        assert_eq!(file.load_entries().unwrap().len(), 1);
+        assert_eq!(unescape(&escape("a\\tb\\")), "a\\tb\\");
+        fs::remove_file(file.get_path()).unwrap();
+    }
+
+    #[test]
+    fn test_concurrent_appends_and_rewrites() {
+        let path = std::env::temp_dir().join("aorta_history_concurrent_test");
+        let _ = fs::remove_file(&path);
+        let writers: Vec<_> = (0..4)
+            .map(|n| {
+                let path = path.clone();
+                std::thread::spawn(move || {
+                    let file = FileOps::new(path);
+                    for i in 0..50 {
+                        let command = format!("echo {n} {i}\nline two");
+                        file.append_entry(&HistoryEntry::new_command(command, 0, 0))
+                            .unwrap();
+                    }
+                })
+            })
+            .collect();
+        let mut rewriter = FileOps::new(path.clone());
+        let mut entries = Vec::new();
+        for _ in 0..20 {
+            let (lock, changes) = rewriter.begin_rewrite().unwrap();
+            match changes {
+                FileChanges::Appended(added) => entries.extend(added),
+                FileChanges::Rewritten(all) => entries = all,
             }
+            rewriter.finish_rewrite(lock, &entries).unwrap();
+        }
+        for writer in writers {
+            writer.join().unwrap();
         }
 
-        Ok(())
+        let entries = FileOps::new(path.clone()).load_entries().unwrap();
+        assert_eq!(entries.len(), 200);
+        assert!(
+            !std::path::Path::new(&format!("{}.{}.tmp", path.display(), std::process::id()))
+                .exists()
+        );
+        fs::remove_file(path).unwrap();
     }
 }
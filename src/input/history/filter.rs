@@ -0,0 +1,126 @@
+use std::borrow::Cow;
+
+use super::redact::redact_secrets;
+use crate::path::glob;
+
+/// Which commands make it into the history, and in what form, like bash's
+/// HISTCONTROL and HISTIGNORE.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct HistoryFilter {
+    // Commands starting with a space aren't recorded
+    pub ignore_space: bool,
+    // Nor ones the same as the command before
+    pub ignore_dups: bool,
+    // Recording a command drops its earlier copies
+    pub erase_dups: bool,
+    // Commands matching one of these as a whole aren't recorded; `&` stands
+    // for the command before
+    pub ignore: Vec<String>,
+    pub redact_secrets: bool,
+}
+
+impl Default for HistoryFilter {
+    fn default() -> Self {
+        Self {
+            ignore_space: false,
+            ignore_dups: false,
+            erase_dups: false,
+            ignore: Vec::new(),
+            redact_secrets: true,
+        }
+    }
+}
+
+impl HistoryFilter {
+    /// Takes the HISTCONTROL options `ignorespace`, `ignoredups`,
+    /// `ignoreboth` and `erasedups`. Others are skipped, as bash does.
+    pub fn with_control<'a>(mut self, options: impl IntoIterator<Item = &'a str>) -> Self {
+        for option in options {
+            match option {
+                "ignorespace" => self.ignore_space = true,
+                "ignoredups" => self.ignore_dups = true,
+                "ignoreboth" => {
+                    self.ignore_space = true;
+                    self.ignore_dups = true;
+                }
+                "erasedups" => self.erase_dups = true,
+                _ => {}
+            }
+        }
+        self
+    }
+
+    pub fn with_ignore(mut self, patterns: Vec<String>) -> Self {
+        self.ignore = patterns;
+        self
+    }
+
+    pub fn with_redaction(mut self, redact_secrets: bool) -> Self {
+        self.redact_secrets = redact_secrets;
+        self
+    }
+
+    /// Whether to record `command` after `previous`, once redacted.
+    pub fn admits(&self, command: &str, previous: Option<&str>) -> bool {
+        if self.ignore_space && command.starts_with(char::is_whitespace) {
+            return false;
+        }
+        if self.ignore_dups && previous == Some(command) {
+            return false;
+        }
+        !self.ignore.iter().any(|pattern| match pattern.as_str() {
+            "&" => previous == Some(command),
+            pattern => glob::matches(pattern, command),
+        })
+    }
+
+    /// `command` as it's written to the history.
+    pub fn redact<'a>(&self, command: &'a str) -> Cow<'a, str> {
+        if self.redact_secrets {
+            redact_secrets(command)
+        } else {
+            Cow::Borrowed(command)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_control_and_ignore_patterns() {
+        let filter = HistoryFilter::default()
+            .with_control("ignoreboth:bogus".split(':'))
+            .with_ignore(vec!["ls".into(), "cd *".into(), "&".into()]);
+        assert!(filter.ignore_space && filter.ignore_dups && !filter.erase_dups);
+
+        let cases = [
+            ("make", None, true),
+            (" secret thing", None, false),
+            ("make", Some("make"), false),
+            ("make test", Some("make"), true),
+            ("ls", None, false),
+            ("ls -la", None, true),
+            ("cd /tmp", None, false),
+            ("cdrecord", None, true),
+        ];
+        for (command, previous, admitted) in cases {
+            assert_eq!(
+                filter.admits(command, previous),
+                admitted,
+                "{:?} after {:?}",
+                command,
+                previous
+            );
+        }
+
+        let everything = HistoryFilter::default().with_redaction(false);
+        assert!(everything.admits(" make", Some(" make")));
+        assert_eq!(everything.redact("TOKEN=abc"), "TOKEN=abc");
+        assert_eq!(
+            HistoryFilter::default().redact("TOKEN=abc"),
+            "TOKEN=REDACTED"
+        );
+    }
+}
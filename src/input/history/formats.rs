@@ -0,0 +1,445 @@
+use std::borrow::Cow;
+use std::fmt::Write;
+use std::path::PathBuf;
+
+use super::{HistoryEntry, HistoryStats};
+
+/// Another shell's history file.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ForeignHistory {
+    Bash,
+    Zsh,
+    Fish,
+}
+
+impl ForeignHistory {
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "bash" => Some(Self::Bash),
+            "zsh" => Some(Self::Zsh),
+            "fish" => Some(Self::Fish),
+            _ => None,
+        }
+    }
+
+    /// Where the shell keeps its history by default.
+    pub fn default_path(self) -> Option<PathBuf> {
+        match self {
+            Self::Bash => dirs::home_dir().map(|home| home.join(".bash_history")),
+            Self::Zsh => dirs::home_dir().map(|home| home.join(".zsh_history")),
+            Self::Fish => dirs::data_dir().map(|data| data.join("fish").join("fish_history")),
+        }
+    }
+
+    /// The commands in a history file's contents, oldest first. What a
+    /// format doesn't record, like exit codes, is left at its default.
+    pub fn parse(self, contents: &[u8]) -> Vec<HistoryEntry> {
+        match self {
+            Self::Bash => parse_bash(&String::from_utf8_lossy(contents)),
+            Self::Zsh => parse_zsh(&String::from_utf8_lossy(&unmetafy(contents))),
+            Self::Fish => parse_fish(&String::from_utf8_lossy(contents)),
+        }
+    }
+}
+
+// An imported command: the format's start time and duration, if any
+fn imported(command: &str, timestamp: u64, duration_secs: u64) -> HistoryEntry {
+    HistoryEntry::Command {
+        command: Cow::Owned(command.to_string()),
+        timestamp,
+        end_time: timestamp + duration_secs,
+        exit_code: 0,
+        duration: duration_secs * 1000,
+        cwd: None,
+        session: None,
+        hostname: None,
+    }
+}
+
+// One command per line, or with HISTTIMEFORMAT set a `#<seconds>` line
+// before each; the lines between two of those are one multi-line command
+fn parse_bash(contents: &str) -> Vec<HistoryEntry> {
+    let mut entries = Vec::new();
+    let mut timestamp = None;
+    let mut lines: Vec<&str> = Vec::new();
+    let mut flush = |timestamp: Option<u64>, lines: &mut Vec<&str>| {
+        let command = lines.join("\n");
+        if !command.trim().is_empty() {
+            entries.push(imported(&command, timestamp.unwrap_or(0), 0));
+        }
+        lines.clear();
+    };
+    for line in contents.lines() {
+        let stamp = line
+            .strip_prefix('#')
+            .filter(|digits| !digits.is_empty() && digits.bytes().all(|b| b.is_ascii_digit()))
+            .and_then(|digits| digits.parse().ok());
+        match stamp {
+            Some(stamp) => {
+                flush(timestamp, &mut lines);
+                timestamp = Some(stamp);
+            }
+            None if timestamp.is_some() => lines.push(line),
+            None => {
+                lines.push(line);
+                flush(None, &mut lines);
+            }
+        }
+    }
+    flush(timestamp, &mut lines);
+    entries
+}
+
+// `: <start>:<seconds>;<command>` with EXTENDED_HISTORY, otherwise just the
+// command; a line ending in a backslash goes on to the next
+fn parse_zsh(contents: &str) -> Vec<HistoryEntry> {
+    let mut entries = Vec::new();
+    let mut lines = contents.lines();
+    while let Some(first) = lines.next() {
+        let mut record = first.to_string();
+        while record.ends_with('\\') {
+            record.pop();
+            record.push('\n');
+            match lines.next() {
+                Some(next) => record.push_str(next),
+                None => break,
+            }
+        }
+        let extended = record.strip_prefix(": ").and_then(|rest| {
+            let (times, command) = rest.split_once(';')?;
+            let (start, duration) = times.split_once(':')?;
+            Some((start.parse().ok()?, duration.parse().ok()?, command))
+        });
+        match extended {
+            Some((start, duration, command)) => entries.push(imported(command, start, duration)),
+            None if !record.trim().is_empty() => entries.push(imported(&record, 0, 0)),
+            None => {}
+        }
+    }
+    entries
+}
+
+// zsh writes bytes that clash with its own tokens as 0x83 followed by the
+// byte xor 32
+fn unmetafy(contents: &[u8]) -> Vec<u8> {
+    const META: u8 = 0x83;
+    let mut bytes = Vec::with_capacity(contents.len());
+    let mut iter = contents.iter();
+    while let Some(&byte) = iter.next() {
+        match byte {
+            META => bytes.extend(iter.next().map(|next| next ^ 32)),
+            byte => bytes.push(byte),
+        }
+    }
+    bytes
+}
+
+// A YAML-like list:
+//   - cmd: echo hello\nworld
+//     when: 1700000000
+//     paths:
+//       - hello
+fn parse_fish(contents: &str) -> Vec<HistoryEntry> {
+    let mut entries = Vec::new();
+    let mut current: Option<(String, u64)> = None;
+    for line in contents.lines() {
+        if let Some(command) = line.strip_prefix("- cmd: ") {
+            if let Some((command, when)) = current.take() {
+                entries.push(imported(&command, when, 0));
+            }
+            current = Some((unescape_fish(command), 0));
+        } else if let Some(when) = line.trim_start().strip_prefix("when: ") {
+            if let Some((_, timestamp)) = current.as_mut() {
+                *timestamp = when.trim().parse().unwrap_or(0);
+            }
+        }
+    }
+    if let Some((command, when)) = current {
+        entries.push(imported(&command, when, 0));
+    }
+    entries
+}
+
+fn unescape_fish(command: &str) -> String {
+    let mut unescaped = String::with_capacity(command.len());
+    let mut chars = command.chars();
+    while let Some(c) = chars.next() {
+        match (c, chars.clone().next()) {
+            ('\\', Some('n')) => {
+                chars.next();
+                unescaped.push('\n');
+            }
+            ('\\', Some('\\')) => {
+                chars.next();
+                unescaped.push('\\');
+            }
+            (c, _) => unescaped.push(c),
+        }
+    }
+    unescaped
+}
+
+/// `entries` as a JSON array, one object per line.
+pub fn to_json<'a>(entries: impl IntoIterator<Item = &'a HistoryEntry>) -> String {
+    let records: Vec<String> = entries.into_iter().map(json_record).collect();
+    if records.is_empty() {
+        return "[]\n".to_string();
+    }
+    format!("[\n  {}\n]\n", records.join(",\n  "))
+}
+
+fn json_record(entry: &HistoryEntry) -> String {
+    let optional = |value: &Option<String>| value.as_deref().map_or("null".into(), json_string);
+    match entry {
+        HistoryEntry::Command {
+            command,
+            timestamp,
+            end_time,
+            exit_code,
+            duration,
+            cwd,
+            session,
+            hostname,
+        } => format!(
+            "{{\"command\": {}, \"timestamp\": {}, \"end_time\": {}, \"exit_code\": {}, \
+             \"duration_ms\": {}, \"cwd\": {}, \"session\": {}, \"hostname\": {}}}",
+            json_string(command),
+            timestamp,
+            end_time,
+            exit_code,
+            duration,
+            optional(cwd),
+            optional(session),
+            optional(hostname)
+        ),
+        HistoryEntry::Event {
+            description,
+            timestamp,
+        } => format!(
+            "{{\"event\": {}, \"timestamp\": {}}}",
+            json_string(description),
+            timestamp
+        ),
+    }
+}
+
+/// `stats` as a JSON object.
+pub fn stats_to_json(stats: &HistoryStats) -> String {
+    let counts = |counts: &[(String, usize)]| -> String {
+        let objects: Vec<String> = counts
+            .iter()
+            .map(|(name, count)| {
+                format!(
+                    "{{\"command\": {}, \"count\": {}}}",
+                    json_string(name),
+                    count
+                )
+            })
+            .collect();
+        format!("[{}]", objects.join(", "))
+    };
+    let by_hour: Vec<String> = stats.by_hour.iter().map(usize::to_string).collect();
+    let by_day: Vec<String> = stats
+        .by_day
+        .iter()
+        .map(|(date, count)| format!("{{\"date\": {}, \"count\": {}}}", json_string(date), count))
+        .collect();
+    let slowest: Vec<String> = stats
+        .slowest
+        .iter()
+        .map(|(command, ms)| {
+            format!(
+                "{{\"command\": {}, \"duration_ms\": {}}}",
+                json_string(command),
+                ms
+            )
+        })
+        .collect();
+    let failure_rates: Vec<String> = stats
+        .failure_rates
+        .iter()
+        .map(|(command, runs, failures)| {
+            format!(
+                "{{\"command\": {}, \"runs\": {}, \"failures\": {}}}",
+                json_string(command),
+                runs,
+                failures
+            )
+        })
+        .collect();
+    let by_directory: Vec<String> = stats
+        .by_directory
+        .iter()
+        .map(|(dir, commands)| {
+            format!(
+                "{{\"cwd\": {}, \"commands\": {}}}",
+                json_string(dir),
+                counts(commands)
+            )
+        })
+        .collect();
+
+    let fields = [
+        ("total_commands", stats.total_commands.to_string()),
+        ("unique_commands", stats.unique_commands.to_string()),
+        ("failed_commands", stats.failed_commands.to_string()),
+        ("average_duration_ms", stats.average_duration.to_string()),
+        ("active_days", stats.active_days.to_string()),
+        ("longest_streak", stats.longest_streak.to_string()),
+        ("current_streak", stats.current_streak.to_string()),
+        ("most_used", counts(&stats.most_used)),
+        ("slowest", format!("[{}]", slowest.join(", "))),
+        ("failure_rates", format!("[{}]", failure_rates.join(", "))),
+        ("by_directory", format!("[{}]", by_directory.join(", "))),
+        ("by_hour", format!("[{}]", by_hour.join(", "))),
+        ("by_day", format!("[{}]", by_day.join(", "))),
+    ];
+    let fields: Vec<String> = fields
+        .iter()
+        .map(|(name, value)| format!("\"{}\": {}", name, value))
+        .collect();
+    format!("{{\n  {}\n}}\n", fields.join(",\n  "))
+}
+
+fn json_string(text: &str) -> String {
+    let mut json = String::with_capacity(text.len() + 2);
+    json.push('"');
+    for c in text.chars() {
+        match c {
+            '"' => json.push_str("\\\""),
+            '\\' => json.push_str("\\\\"),
+            '\n' => json.push_str("\\n"),
+            '\r' => json.push_str("\\r"),
+            '\t' => json.push_str("\\t"),
+            c if u32::from(c) < 0x20 => {
+                let _ = write!(json, "\\u{:04x}", u32::from(c));
+            }
+            c => json.push(c),
+        }
+    }
+    json.push('"');
+    json
+}
+
+/// The commands in `entries` as a bash history file with timestamps,
+/// which bash reads back multi-line commands and all.
+pub fn to_bash<'a>(entries: impl IntoIterator<Item = &'a HistoryEntry>) -> String {
+    let mut bash = String::new();
+    for entry in entries {
+        if let HistoryEntry::Command {
+            command, timestamp, ..
+        } = entry
+        {
+            let _ = writeln!(bash, "#{}\n{}", timestamp, command);
+        }
+    }
+    bash
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn commands(entries: &[HistoryEntry]) -> Vec<(String, u64, u64)> {
+        entries
+            .iter()
+            .map(|entry| match entry {
+                HistoryEntry::Command {
+                    command,
+                    timestamp,
+                    duration,
+                    ..
+                } => (command.to_string(), *timestamp, *duration),
+                HistoryEntry::Event { .. } => panic!("unexpected event"),
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_parse_bash() {
+        let plain = ForeignHistory::Bash.parse(b"ls\n\ncd /tmp\n");
+        assert_eq!(
+            commands(&plain),
+            [("ls".into(), 0, 0), ("cd /tmp".into(), 0, 0)]
+        );
+
+        let stamped = ForeignHistory::Bash
+            .parse(b"#1700000000\nmake\n#1700000005\nfor f in *\ndo echo $f\ndone\n");
+        assert_eq!(
+            commands(&stamped),
+            [
+                ("make".into(), 1700000000, 0),
+                ("for f in *\ndo echo $f\ndone".into(), 1700000005, 0)
+            ]
+        );
+    }
+
+    #[test]
+    fn test_parse_zsh() {
+        let mut contents =
+            b": 1700000000:3;cargo build\n: 1700000010:0;echo a\\\nb\nplain\n".to_vec();
+        // "é" metafied: 0xc3 0xa9 -> 0xc3 0x83 0x89
+        contents.extend_from_slice(b": 1700000020:0;echo caf\xc3\x83\x89\n");
+        assert_eq!(
+            commands(&ForeignHistory::Zsh.parse(&contents)),
+            [
+                ("cargo build".into(), 1700000000, 3000),
+                ("echo a\nb".into(), 1700000010, 0),
+                ("plain".into(), 0, 0),
+                ("echo café".into(), 1700000020, 0),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_parse_fish() {
+        let contents = b"- cmd: echo hello\\nworld\n  when: 1700000000\n  paths:\n    - hello\n- cmd: ls C:\\\\\n  when: 1700000009\n";
+        assert_eq!(
+            commands(&ForeignHistory::Fish.parse(contents)),
+            [
+                ("echo hello\nworld".into(), 1700000000, 0),
+                ("ls C:\\".into(), 1700000009, 0),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_export() {
+        let entries = [
+            imported("echo \"hi\"\n\tthere", 5, 2),
+            HistoryEntry::Event {
+                description: Cow::Borrowed("cleared"),
+                timestamp: 9,
+            },
+        ];
+        assert_eq!(
+            to_json(&entries),
+            "[\n  {\"command\": \"echo \\\"hi\\\"\\n\\tthere\", \"timestamp\": 5, \"end_time\": 7, \
+             \"exit_code\": 0, \"duration_ms\": 2000, \"cwd\": null, \"session\": null, \
+             \"hostname\": null},\n  {\"event\": \"cleared\", \"timestamp\": 9}\n]\n"
+        );
+        assert_eq!(to_json(&[]), "[]\n");
+
+        let stats = HistoryStats {
+            total_commands: 2,
+            most_used: vec![("make".into(), 2)],
+            by_day: vec![("2023-11-14".into(), 2)],
+            failure_rates: vec![("make".into(), 2, 1)],
+            ..HistoryStats::default()
+        };
+        let json = stats_to_json(&stats);
+        assert!(json.starts_with("{\n  \"total_commands\": 2,\n"));
+        assert!(json.contains("\"most_used\": [{\"command\": \"make\", \"count\": 2}]"));
+        assert!(json.contains(
+            "\"failure_rates\": [{\"command\": \"make\", \"runs\": 2, \"failures\": 1}]"
+        ));
+        assert!(json.contains("\"by_day\": [{\"date\": \"2023-11-14\", \"count\": 2}]"));
+
+        let bash = to_bash(&entries);
+        assert_eq!(bash, "#5\necho \"hi\"\n\tthere\n");
+        assert_eq!(
+            commands(&ForeignHistory::Bash.parse(bash.as_bytes())),
+            [("echo \"hi\"\n\tthere".into(), 5, 0)]
+        );
+    }
+}
@@ -0,0 +1,98 @@
+// Points for each matched character, with bonuses for runs and for
+// characters that start a word, and a point off for each one skipped
+const MATCH: i64 = 16;
+const CONSECUTIVE: i64 = 8;
+const WORD_START: i64 = 8;
+const GAP: i64 = 1;
+
+/// Where a query matched inside a command.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FuzzyMatch {
+    pub score: i64,
+    // Byte offsets of the matched characters
+    pub positions: Vec<usize>,
+}
+
+/// Matches `query` against `text` as a subsequence, case-insensitively
+/// unless the query has capitals. Of the places it matches, the shortest
+/// ending at the first complete match is scored.
+pub fn fuzzy_match(query: &str, text: &str) -> Option<FuzzyMatch> {
+    let case_sensitive = query.chars().any(char::is_uppercase);
+    let fold = |c: char| {
+        if case_sensitive {
+            c
+        } else {
+            c.to_ascii_lowercase()
+        }
+    };
+    let query: Vec<char> = query.chars().map(fold).collect();
+    let text: Vec<(usize, char)> = text.char_indices().map(|(i, c)| (i, fold(c))).collect();
+    if query.is_empty() {
+        return Some(FuzzyMatch {
+            score: 0,
+            positions: Vec::new(),
+        });
+    }
+
+    // Forward to where the whole query has matched, then back from there
+    // to the latest start that still matches it
+    let mut wanted = query.iter().peekable();
+    let end = text.iter().position(|(_, c)| {
+        wanted.next_if(|q| **q == *c);
+        wanted.peek().is_none()
+    })?;
+    let mut wanted = query.iter().rev().peekable();
+    let start = (0..=end).rev().find(|&i| {
+        wanted.next_if(|q| **q == text[i].1);
+        wanted.peek().is_none()
+    })?;
+
+    let mut wanted = query.iter().peekable();
+    let indices: Vec<usize> = (start..=end)
+        .filter(|&i| wanted.next_if(|q| **q == text[i].1).is_some())
+        .collect();
+    Some(FuzzyMatch {
+        score: score(&text, &indices),
+        positions: indices.iter().map(|&i| text[i].0).collect(),
+    })
+}
+
+fn score(text: &[(usize, char)], indices: &[usize]) -> i64 {
+    let mut score = 0;
+    for (n, &i) in indices.iter().enumerate() {
+        score += MATCH;
+        if n > 0 && indices[n - 1] + 1 == i {
+            score += CONSECUTIVE;
+        }
+        if i == 0 || !text[i - 1].1.is_alphanumeric() {
+            score += WORD_START;
+        }
+    }
+    let span = indices.last().map_or(0, |last| last - indices[0] + 1);
+    score - GAP * (span - indices.len()) as i64
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_fuzzy_match_positions() {
+        let found = fuzzy_match("gco", "git checkout main").unwrap();
+        assert_eq!(found.positions, vec![0, 4, 9]);
+        assert!(fuzzy_match("xyz", "git checkout").is_none());
+        assert!(fuzzy_match("GIT", "git status").is_none());
+        assert!(fuzzy_match("git", "GIT status").is_some());
+    }
+
+    #[test]
+    fn test_tighter_matches_score_higher() {
+        let run = fuzzy_match("test", "cargo test").unwrap();
+        let spread = fuzzy_match("test", "the best").unwrap();
+        assert!(run.score > spread.score);
+
+        // The match ending first is tightened to its latest start
+        let found = fuzzy_match("ab", "a a ab").unwrap();
+        assert_eq!(found.positions, vec![4, 5]);
+    }
+}
@@ -1,12 +1,31 @@
+mod editor;
+mod expansion;
 mod file_ops;
+mod filter;
+mod formats;
+mod fuzzy;
+mod redact;
+mod stats;
+mod time;
 pub mod types;
 
-use self::file_ops::FileOps;
+pub use self::editor::EditorHistory;
+pub use self::expansion::{expand, Expansion};
+use self::file_ops::{FileChanges, FileOps};
+pub use self::filter::HistoryFilter;
+pub use self::formats::{stats_to_json, to_bash, to_json, ForeignHistory};
+use self::fuzzy::fuzzy_match;
+pub use self::fuzzy::FuzzyMatch;
+pub use self::time::{parse_duration, parse_time, relative_time};
 pub use self::types::{HistoryEntry, HistorySearchMode, HistoryStats};
+use crate::sys::host_name;
+use regex::Regex;
 use std::{
-    collections::{HashMap, VecDeque},
+    borrow::Cow,
+    collections::{HashMap, HashSet, VecDeque},
     fmt,
     path::PathBuf,
+    time::{SystemTime, UNIX_EPOCH},
 };
 
 #[derive(Debug)]
@@ -16,6 +35,12 @@ pub enum HistoryError {
     LockError(String),
     FileOperationError(String),
     EmptyCommand,
+    // History expansion: the designator that matched nothing, or the bad
+    // word range or modifier
+    EventNotFound(String),
+    BadWordSpecifier(String),
+    BadModifier(char),
+    SubstitutionFailed,
 }
 
 impl From<std::io::Error> for HistoryError {
@@ -32,58 +57,273 @@ impl fmt::Display for HistoryError {
             HistoryError::LockError(msg) => write!(f, "Lock error: {}", msg),
             HistoryError::FileOperationError(msg) => write!(f, "File operation error: {}", msg),
             HistoryError::EmptyCommand => write!(f, "Empty command"),
+            HistoryError::EventNotFound(spec) => write!(f, "{}: event not found", spec),
+            HistoryError::BadWordSpecifier(spec) => write!(f, "{}: bad word specifier", spec),
+            HistoryError::BadModifier(c) => write!(f, ":{}: unrecognized history modifier", c),
+            HistoryError::SubstitutionFailed => write!(f, "substitution failed"),
         }
     }
 }
 
+/// Which commands the fuzzy finder and `history search` offer, beyond
+/// matching the query.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct SearchFilter {
+    // `Some(true)` for commands that succeeded, `Some(false)` for failures
+    pub succeeded: Option<bool>,
+    pub exit_code: Option<i32>,
+    // Only commands run in this directory
+    pub cwd: Option<String>,
+    // Started within these times, in seconds since the epoch
+    pub since: Option<u64>,
+    pub until: Option<u64>,
+    // Ran for at least this many milliseconds
+    pub min_duration: Option<u64>,
+}
+
+impl SearchFilter {
+    // Events have none of what's asked of commands but the time, so they
+    // only pass a filter on that
+    fn accepts(&self, entry: &HistoryEntry) -> bool {
+        let in_time = |timestamp: u64| {
+            self.since.is_none_or(|since| timestamp >= since)
+                && self.until.is_none_or(|until| timestamp <= until)
+        };
+        match entry {
+            HistoryEntry::Command {
+                timestamp,
+                exit_code,
+                duration,
+                cwd,
+                ..
+            } => {
+                self.succeeded
+                    .is_none_or(|succeeded| succeeded == (*exit_code == 0))
+                    && self.exit_code.is_none_or(|code| code == *exit_code)
+                    && self
+                        .cwd
+                        .as_deref()
+                        .is_none_or(|dir| cwd.as_deref() == Some(dir))
+                    && self.min_duration.is_none_or(|min| *duration >= min)
+                    && in_time(*timestamp)
+            }
+            HistoryEntry::Event { timestamp, .. } => {
+                self.succeeded.is_none()
+                    && self.exit_code.is_none()
+                    && self.cwd.is_none()
+                    && self.min_duration.is_none()
+                    && in_time(*timestamp)
+            }
+        }
+    }
+}
+
+/// How a command, or an event's description, has to match `history search`.
+#[derive(Debug, Clone)]
+pub enum TextMatch {
+    Prefix(String),
+    Contains(String),
+    Regex(Regex),
+}
+
+impl TextMatch {
+    fn matches(&self, text: &str) -> bool {
+        match self {
+            TextMatch::Prefix(prefix) => text.starts_with(prefix.as_str()),
+            TextMatch::Contains(substring) => text.contains(substring.as_str()),
+            TextMatch::Regex(regex) => regex.is_match(text),
+        }
+    }
+}
+
+/// How [`History::reload`] changed the entries.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Reload {
+    // This many entries from other sessions were added at the end
+    Appended(usize),
+    // The file was rewritten, so the entries were read again from scratch
+    Replaced,
+}
+
+/// A command found by [`History::fuzzy_search`], with its latest entry.
+#[derive(Debug, Clone)]
+pub struct FuzzyResult<'a> {
+    pub entry: &'a HistoryEntry,
+    pub command: &'a str,
+    pub found: FuzzyMatch,
+    // The match score with the recency and frequency bonuses added
+    pub rank: i64,
+}
+
 pub struct History {
     entries: VecDeque<HistoryEntry>,
+    // Where each command is in `entries`, so the line editor can step
+    // through them by position without walking the events between
+    command_positions: Vec<usize>,
     command_frequencies: HashMap<String, usize>,
     file_ops: FileOps,
     max_entries: usize,
+    // Stamped on the commands recorded here
+    session: String,
+    hostname: String,
+    filter: HistoryFilter,
 }
 
 impl History {
     pub fn new(history_file: PathBuf, max_entries: usize) -> Result<Self, HistoryError> {
-        let file_ops = FileOps::new(history_file);
+        let mut file_ops = FileOps::new(history_file);
         let raw_entries = file_ops
             .load_entries()
             .map_err(|e| HistoryError::FileOperationError(e.to_string()))?;
 
-        let mut command_frequencies = HashMap::new();
-        let entries: VecDeque<_> = raw_entries
-            .into_iter()
-            .inspect(|entry| {
-                if let HistoryEntry::Command { command, .. } = entry {
-                    *command_frequencies.entry(command.to_string()).or_insert(0) += 1;
-                }
-            })
-            .collect();
-
-        Ok(History {
-            entries,
-            command_frequencies,
+        let mut history = History {
+            entries: raw_entries.into(),
+            command_positions: Vec::new(),
+            command_frequencies: HashMap::new(),
             file_ops,
             max_entries,
-        })
+            session: new_session_id(),
+            hostname: host_name(),
+            filter: HistoryFilter::default(),
+        };
+        history.count_commands();
+        history.index_commands();
+        Ok(history)
+    }
+
+    // Entries were added or removed; find the commands again
+    fn index_commands(&mut self) {
+        self.command_positions.clear();
+        self.command_positions.extend(
+            self.entries
+                .iter()
+                .enumerate()
+                .filter(|(_, entry)| matches!(entry, HistoryEntry::Command { .. }))
+                .map(|(position, _)| position),
+        );
+    }
+
+    fn count_commands(&mut self) {
+        self.command_frequencies.clear();
+        for entry in &self.entries {
+            if let HistoryEntry::Command { command, .. } = entry {
+                *self
+                    .command_frequencies
+                    .entry(command.to_string())
+                    .or_insert(0) += 1;
+            }
+        }
+    }
+
+    /// Takes in what other sessions sharing the file recorded since it was
+    /// last read here.
+    pub fn reload(&mut self) -> Result<Reload, HistoryError> {
+        let changes = self
+            .file_ops
+            .read_changes()
+            .map_err(|e| HistoryError::FileOperationError(e.to_string()))?;
+        Ok(self.take_in(changes))
+    }
+
+    fn take_in(&mut self, changes: FileChanges) -> Reload {
+        let entries = match changes {
+            FileChanges::Appended(entries) => entries,
+            FileChanges::Rewritten(entries) => {
+                self.entries = entries.into();
+                self.count_commands();
+                self.trim_entries();
+                self.index_commands();
+                return Reload::Replaced;
+            }
+        };
+
+        let mut added = 0;
+        for entry in entries {
+            // This session's commands are already here
+            if let HistoryEntry::Command {
+                command, session, ..
+            } = &entry
+            {
+                if session.as_deref() == Some(self.session.as_str()) {
+                    continue;
+                }
+                *self
+                    .command_frequencies
+                    .entry(command.to_string())
+                    .or_insert(0) += 1;
+            }
+            self.entries.push_back(entry);
+            added += 1;
+        }
+        self.trim_entries();
+        self.index_commands();
+        Reload::Appended(added.min(self.entries.len()))
+    }
+
+    /// Identifies this shell's commands among those of others sharing the
+    /// file.
+    pub fn session(&self) -> &str {
+        &self.session
     }
 
     pub fn add(&mut self, command: &str) -> Result<(), HistoryError> {
         self.add_with_details(command, 0, 0)
     }
 
+    /// Changes which commands are recorded from now on.
+    pub fn set_filter(&mut self, filter: HistoryFilter) {
+        self.filter = filter;
+    }
+
+    pub fn path(&self) -> &PathBuf {
+        self.file_ops.get_path()
+    }
+
+    /// Changes how many entries are kept in memory, dropping the oldest.
+    pub fn set_max_entries(&mut self, max_entries: usize) {
+        self.max_entries = max_entries;
+        self.trim_entries();
+        self.index_commands();
+    }
+
+    /// The recorded commands, oldest first, without the events between.
+    pub fn commands(&self) -> impl DoubleEndedIterator<Item = &str> {
+        self.entries.iter().filter_map(|entry| match entry {
+            HistoryEntry::Command { command, .. } => Some(command.as_ref()),
+            HistoryEntry::Event { .. } => None,
+        })
+    }
+
+    /// How many commands there are, as [`commands`](Self::commands) yields.
+    pub fn command_count(&self) -> usize {
+        self.command_positions.len()
+    }
+
+    /// The command at `index` among [`commands`](Self::commands).
+    pub fn command(&self, index: usize) -> Option<&str> {
+        match self.entries.get(*self.command_positions.get(index)?)? {
+            HistoryEntry::Command { command, .. } => Some(command),
+            HistoryEntry::Event { .. } => None,
+        }
+    }
+
     pub fn get_recent(&self, count: usize) -> Vec<&HistoryEntry> {
         self.entries.iter().rev().take(count).collect()
     }
 
     pub fn clear(&mut self) -> Result<(), HistoryError> {
-        self.entries.clear();
-        self.command_frequencies.clear();
-        self.file_ops = FileOps::new(self.file_ops.get_path().to_path_buf());
-        Ok(())
+        self.rewrite_history_file(|history| {
+            history.entries.clear();
+            history.command_frequencies.clear();
+            Ok(())
+        })
     }
 
     pub fn delete_at(&mut self, index: usize) -> Result<(), HistoryError> {
+        self.rewrite_history_file(|history| history.remove_at(index))
+    }
+
+    fn remove_at(&mut self, index: usize) -> Result<(), HistoryError> {
         if index >= self.entries.len() {
             return Err(HistoryError::InvalidIndex(index));
         }
@@ -97,19 +337,50 @@ impl History {
                 }
             }
         }
-
-        self.rewrite_history_file()?;
         Ok(())
     }
 
-    fn rewrite_history_file(&mut self) -> Result<(), HistoryError> {
-        self.file_ops = FileOps::new(self.file_ops.get_path().to_path_buf());
-        for entry in &self.entries {
-            self.file_ops
-                .append_entry(entry)
-                .map_err(|e| HistoryError::FileOperationError(e.to_string()))?;
-        }
-        Ok(())
+    // Makes `edit` to the entries and writes them back, with the file
+    // locked throughout so what other sessions added isn't lost
+    fn rewrite_history_file(
+        &mut self,
+        edit: impl FnOnce(&mut Self) -> Result<(), HistoryError>,
+    ) -> Result<(), HistoryError> {
+        let (lock, changes) = self
+            .file_ops
+            .begin_rewrite()
+            .map_err(|e| HistoryError::FileOperationError(e.to_string()))?;
+        self.take_in(changes);
+        let edited = edit(self);
+        self.index_commands();
+        edited?;
+        let entries: Vec<_> = self.entries.iter().cloned().collect();
+        self.file_ops
+            .finish_rewrite(lock, &entries)
+            .map_err(|e| HistoryError::FileOperationError(e.to_string()))
+    }
+
+    /// Adds commands from another shell's history, in time order among the
+    /// ones here, skipping any already imported. Returns how many it added.
+    pub fn import(&mut self, entries: Vec<HistoryEntry>) -> Result<usize, HistoryError> {
+        let mut added = 0;
+        self.rewrite_history_file(|history| {
+            let mut seen: HashSet<(u64, String)> =
+                history.entries.iter().filter_map(import_key).collect();
+            let new: Vec<_> = entries
+                .into_iter()
+                .filter(|entry| import_key(entry).is_some_and(|key| seen.insert(key)))
+                .collect();
+            added = new.len();
+
+            let mut merged: Vec<_> = history.entries.drain(..).chain(new).collect();
+            merged.sort_by_key(HistoryEntry::timestamp);
+            history.entries = merged.into();
+            history.count_commands();
+            history.trim_entries();
+            Ok(())
+        })?;
+        Ok(added)
     }
 
     pub fn search(&self, mode: HistorySearchMode, query: &str) -> Vec<&HistoryEntry> {
@@ -121,6 +392,26 @@ impl History {
         }
     }
 
+    /// The entries matching `text`, if given, and `filter`, oldest first,
+    /// with the indices `delete_at` takes.
+    pub fn find(
+        &self,
+        text: Option<&TextMatch>,
+        filter: &SearchFilter,
+    ) -> Vec<(usize, &HistoryEntry)> {
+        self.entries
+            .iter()
+            .enumerate()
+            .filter(|(_, entry)| {
+                let content = match entry {
+                    HistoryEntry::Command { command, .. } => command,
+                    HistoryEntry::Event { description, .. } => description,
+                };
+                text.is_none_or(|text| text.matches(content)) && filter.accepts(entry)
+            })
+            .collect()
+    }
+
     fn search_by_prefix(&self, prefix: &str) -> Vec<&HistoryEntry> {
         self.entries
             .iter()
@@ -172,11 +463,41 @@ impl History {
         exit_code: i32,
         duration: u64,
     ) -> Result<(), HistoryError> {
+        self.add_entry(HistoryEntry::new_command(
+            command.to_string(),
+            exit_code,
+            duration,
+        ))
+    }
+
+    /// Records a command entry, like one built with its directory, as run
+    /// in this session. Commands the filter turns away are dropped quietly.
+    pub fn add_entry(&mut self, entry: HistoryEntry) -> Result<(), HistoryError> {
+        let mut entry = entry.with_origin(&self.session, &self.hostname);
+        let HistoryEntry::Command { command, .. } = &mut entry else {
+            return Err(HistoryError::EmptyCommand);
+        };
         if command.trim().is_empty() {
             return Err(HistoryError::EmptyCommand);
         }
-
-        let entry = HistoryEntry::new_command(command.to_string(), exit_code, duration);
+        if let Cow::Owned(redacted) = self.filter.redact(command) {
+            *command = Cow::Owned(redacted);
+        }
+        let command = command.to_string();
+        if !self.filter.admits(&command, self.commands().next_back()) {
+            return Ok(());
+        }
+        if self.filter.erase_dups && self.command_frequencies.contains_key(&command) {
+            return self.rewrite_history_file(|history| {
+                history.entries.retain(|entry| {
+                    !matches!(entry, HistoryEntry::Command { command: c, .. } if *c == command)
+                });
+                history.command_frequencies.insert(command, 1);
+                history.entries.push_back(entry);
+                history.trim_entries();
+                Ok(())
+            });
+        }
 
         // Save to file first
         self.file_ops
@@ -184,60 +505,375 @@ impl History {
             .map_err(|e| HistoryError::FileOperationError(e.to_string()))?;
 
         // Update frequency counter
-        *self
-            .command_frequencies
-            .entry(command.to_string())
-            .or_insert(0) += 1;
+        *self.command_frequencies.entry(command).or_insert(0) += 1;
 
         // Then update memory
         self.entries.push_back(entry);
         self.trim_entries();
+        self.index_commands();
 
         Ok(())
     }
 
-    pub fn calculate_stats(&self) -> HistoryStats {
-        let mut stats = HistoryStats::default();
-        let mut total_duration = 0u64;
-
-        stats.total_commands = self
-            .entries
-            .iter()
-            .filter(|entry| matches!(entry, HistoryEntry::Command { .. }))
-            .count();
-
-        stats.unique_commands = self.command_frequencies.len();
-
-        for entry in &self.entries {
-            if let HistoryEntry::Command {
+    /// The most recent command that extends `prefix`, for an inline
+    /// suggestion. Commands that ran in `cwd` and ones that succeeded win
+    /// over more recent ones.
+    pub fn suggest(&self, prefix: &str, cwd: &str) -> Option<&str> {
+        let mut best: Option<(u8, &str)> = None;
+        for entry in self.entries.iter().rev() {
+            let HistoryEntry::Command {
+                command,
                 exit_code,
-                duration,
+                cwd: dir,
                 ..
             } = entry
+            else {
+                continue;
+            };
+            // A suggestion is shown on the input line, so it can't span lines
+            if command.len() <= prefix.len()
+                || !command.starts_with(prefix)
+                || command.contains('\n')
             {
-                if *exit_code != 0 {
-                    stats.failed_commands += 1;
-                }
-                total_duration += duration;
+                continue;
+            }
+            let rank = 2 * u8::from(dir.as_deref() == Some(cwd)) + u8::from(*exit_code == 0);
+            if best.is_none_or(|(best_rank, _)| rank > best_rank) {
+                best = Some((rank, command));
+            }
+            if rank == 3 {
+                break;
             }
         }
+        best.map(|(_, command)| command)
+    }
+
+    /// Commands fuzzily matching `query`, once each, best first. The match
+    /// score is what counts most; recent and frequent commands get a bonus
+    /// that settles close calls and orders everything for an empty query.
+    pub fn fuzzy_search(&self, query: &str, filter: &SearchFilter) -> Vec<FuzzyResult<'_>> {
+        let mut seen = HashSet::new();
+        let mut results = Vec::new();
+        for entry in self.entries.iter().rev() {
+            let HistoryEntry::Command { command, .. } = entry else {
+                continue;
+            };
+            if !filter.accepts(entry) || !seen.insert(command.as_ref()) {
+                continue;
+            }
+            let Some(found) = fuzzy_match(query, command) else {
+                continue;
+            };
+            let age = seen.len() as u32;
+            let count = self.command_frequencies.get(command.as_ref()).copied();
+            let rank = found.score + recency_bonus(age) + frequency_bonus(count.unwrap_or(1));
+            results.push(FuzzyResult {
+                entry,
+                command,
+                found,
+                rank,
+            });
+        }
+        // Stable, so ties stay newest first
+        results.sort_by_key(|result| std::cmp::Reverse(result.rank));
+        results
+    }
+
+    /// Statistics over the commands `filter` accepts, like those of the
+    /// last week.
+    pub fn calculate_stats(&self, filter: &SearchFilter) -> HistoryStats {
+        let now = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_secs();
+        stats::calculate(&self.entries, filter, now)
+    }
+}
+
+// Imported commands are the same if they started at the same time
+fn import_key(entry: &HistoryEntry) -> Option<(u64, String)> {
+    match entry {
+        HistoryEntry::Command {
+            command, timestamp, ..
+        } => Some((*timestamp, command.to_string())),
+        HistoryEntry::Event { .. } => None,
+    }
+}
+
+// Unique enough to tell apart shells sharing a history file
+fn new_session_id() -> String {
+    let started = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default();
+    format!("{:x}-{:x}", started.as_secs(), std::process::id())
+}
+
+// 40 for the newest command, falling off with each doubling of its age
+fn recency_bonus(age: u32) -> i64 {
+    40_i64.saturating_sub(4 * i64::from(age.ilog2()))
+}
+
+fn frequency_bonus(count: usize) -> i64 {
+    4 * i64::from((count + 1).ilog2())
+}
 
-        stats.average_duration = if stats.total_commands > 0 {
-            total_duration / (stats.total_commands as u64)
-        } else {
-            0
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_suggest_prefers_cwd_and_success() {
+        let path = std::env::temp_dir().join("aorta_history_suggest_test");
+        let _ = std::fs::remove_file(&path);
+        let mut history = History::new(path.clone(), 100).unwrap();
+        let add = |history: &mut History, command: &str, exit_code, cwd: &str| {
+            let entry = HistoryEntry::new_command(command.to_string(), exit_code, 0);
+            history.add_entry(entry.with_cwd(cwd)).unwrap();
         };
+        add(&mut history, "cargo build", 0, "/src/a");
+        add(&mut history, "cargo test", 0, "/src/b");
+        add(&mut history, "cargo check", 101, "/src/a");
+        add(&mut history, "cargo", 0, "/src/a");
+
+        assert_eq!(history.suggest("cargo", "/src/b"), Some("cargo test"));
+        assert_eq!(history.suggest("cargo", "/src/a"), Some("cargo build"));
+        assert_eq!(history.suggest("car", "/elsewhere"), Some("cargo"));
+        assert_eq!(
+            history.suggest("cargo c", "/elsewhere"),
+            Some("cargo check")
+        );
+        assert_eq!(history.suggest("cargo build", "/src/a"), None);
+        std::fs::remove_file(path).unwrap();
+    }
 
-        let mut commands: Vec<_> = self
-            .command_frequencies
-            .iter()
-            .filter(|(_, &count)| count > 0)
-            .map(|(cmd, &count)| (cmd.clone(), count))
+    #[test]
+    fn test_fuzzy_search_ranks_and_filters() {
+        let path = std::env::temp_dir().join("aorta_history_fuzzy_test");
+        let _ = std::fs::remove_file(&path);
+        let mut history = History::new(path.clone(), 100).unwrap();
+        for (command, exit_code, cwd) in [
+            ("git status", 0, "/repo"),
+            ("grep -r todo src", 1, "/repo"),
+            ("git status", 0, "/repo"),
+            ("make test", 2, "/build"),
+        ] {
+            let entry = HistoryEntry::new_command(command.to_string(), exit_code, 0);
+            history.add_entry(entry.with_cwd(cwd)).unwrap();
+        }
+
+        let commands = |results: Vec<FuzzyResult<'_>>| -> Vec<String> {
+            results.iter().map(|r| r.command.to_string()).collect()
+        };
+        let all = SearchFilter::default();
+        assert_eq!(
+            commands(history.fuzzy_search("", &all)),
+            ["make test", "git status", "grep -r todo src"]
+        );
+        assert_eq!(commands(history.fuzzy_search("gst", &all)), ["git status"]);
+
+        let failed = SearchFilter {
+            succeeded: Some(false),
+            cwd: Some("/repo".into()),
+            ..SearchFilter::default()
+        };
+        assert_eq!(
+            commands(history.fuzzy_search("", &failed)),
+            ["grep -r todo src"]
+        );
+        std::fs::remove_file(path).unwrap();
+    }
+
+    #[test]
+    fn test_entries_keep_origin_across_rewrites() {
+        let path = std::env::temp_dir().join("aorta_history_origin_test");
+        let _ = std::fs::remove_file(&path);
+        let mut history = History::new(path.clone(), 100).unwrap();
+        history.add("echo 'first\nline'").unwrap();
+        history.add("make").unwrap();
+        history.delete_at(1).unwrap();
+
+        let reloaded = History::new(path.clone(), 100).unwrap();
+        let entries = reloaded.get_recent(10);
+        assert_eq!(entries.len(), 1);
+        let HistoryEntry::Command {
+            command, session, ..
+        } = entries[0]
+        else {
+            panic!("expected a command");
+        };
+        assert_eq!(command, "echo 'first\nline'");
+        assert_eq!(session.as_deref(), Some(history.session()));
+        std::fs::remove_file(path).unwrap();
+    }
+
+    #[test]
+    fn test_reload_takes_in_other_sessions() {
+        let path = std::env::temp_dir().join("aorta_history_reload_test");
+        let _ = std::fs::remove_file(&path);
+        let mut first = History::new(path.clone(), 100).unwrap();
+        let mut second = History::new(path.clone(), 100).unwrap();
+        // Tell the sessions apart, as separate processes would be
+        second.session.push_str("-second");
+
+        first.add("make").unwrap();
+        second.add("cargo test").unwrap();
+        assert_eq!(second.reload().unwrap(), Reload::Appended(1));
+        assert_eq!(second.reload().unwrap(), Reload::Appended(0));
+        assert_eq!(second.get_recent(10).len(), 2);
+        assert_eq!(first.reload().unwrap(), Reload::Appended(1));
+
+        first.delete_at(0).unwrap();
+        assert_eq!(second.reload().unwrap(), Reload::Replaced);
+        let commands: Vec<_> = second
+            .get_recent(10)
+            .into_iter()
+            .map(|entry| match entry {
+                HistoryEntry::Command { command, .. } => command.to_string(),
+                HistoryEntry::Event { .. } => panic!("unexpected event"),
+            })
             .collect();
+        assert_eq!(commands, ["cargo test"]);
+        std::fs::remove_file(path).unwrap();
+    }
+
+    #[test]
+    fn test_command_positions_follow_changes() {
+        let path = std::env::temp_dir().join("aorta_history_positions_test");
+        let _ = std::fs::remove_file(&path);
+        let mut history = History::new(path.clone(), 3).unwrap();
+        let by_position = |history: &History| -> Vec<String> {
+            let count = history.command_count();
+            (0..=count)
+                .map_while(|index| history.command(index).map(String::from))
+                .collect()
+        };
+        for command in ["a", "b", "c", "d"] {
+            history.add(command).unwrap();
+        }
+        assert_eq!(by_position(&history), ["b", "c", "d"]);
+
+        history.delete_at(1).unwrap();
+        assert_eq!(by_position(&history), ["b", "d"]);
+        let mut other = History::new(path.clone(), 3).unwrap();
+        other.session.push_str("-other");
+        other.add("e").unwrap();
+        history.reload().unwrap();
+        assert_eq!(by_position(&history), ["b", "d", "e"]);
+        assert_eq!(history.command(3), None);
+        std::fs::remove_file(path).unwrap();
+    }
+
+    #[test]
+    fn test_import_merges_in_time_order() {
+        let path = std::env::temp_dir().join("aorta_history_import_test");
+        let _ = std::fs::remove_file(&path);
+        let mut history = History::new(path.clone(), 100).unwrap();
+        history.add("aorta command").unwrap();
 
-        commands.sort_by(|a, b| b.1.cmp(&a.1).then(a.0.cmp(&b.0)));
-        stats.most_used = commands.into_iter().take(10).collect();
+        let bash = ForeignHistory::Bash.parse(b"#1000\nold one\n#2000\nold two\n");
+        assert_eq!(history.import(bash.clone()).unwrap(), 2);
+        assert_eq!(history.import(bash).unwrap(), 0);
+
+        let reloaded = History::new(path.clone(), 100).unwrap();
+        let commands: Vec<_> = reloaded
+            .get_recent(10)
+            .into_iter()
+            .rev()
+            .map(|entry| match entry {
+                HistoryEntry::Command { command, .. } => command.to_string(),
+                HistoryEntry::Event { .. } => panic!("unexpected event"),
+            })
+            .collect();
+        assert_eq!(commands, ["old one", "old two", "aorta command"]);
+        std::fs::remove_file(path).unwrap();
+    }
+
+    #[test]
+    fn test_filter_decides_what_is_written() {
+        let path = std::env::temp_dir().join("aorta_history_filter_test");
+        let _ = std::fs::remove_file(&path);
+        let mut history = History::new(path.clone(), 100).unwrap();
+        history.set_filter(
+            HistoryFilter::default()
+                .with_control(["ignorespace", "erasedups"])
+                .with_ignore(vec!["ls".into()]),
+        );
+        for command in ["make", " rm -rf secrets", "ls", "cargo test", "make"] {
+            history.add(command).unwrap();
+        }
+        history.add("export API_TOKEN=hunter2").unwrap();
+
+        let reloaded = History::new(path.clone(), 100).unwrap();
+        let commands: Vec<_> = reloaded
+            .get_recent(10)
+            .into_iter()
+            .rev()
+            .map(|entry| match entry {
+                HistoryEntry::Command { command, .. } => command.to_string(),
+                HistoryEntry::Event { .. } => panic!("unexpected event"),
+            })
+            .collect();
+        assert_eq!(
+            commands,
+            ["cargo test", "make", "export API_TOKEN=REDACTED"]
+        );
+        std::fs::remove_file(path).unwrap();
+    }
+
+    #[test]
+    fn test_find_with_text_and_filter() {
+        let path = std::env::temp_dir().join("aorta_history_find_test");
+        let _ = std::fs::remove_file(&path);
+        let mut history = History::new(path.clone(), 100).unwrap();
+        let commands = [
+            ("cargo build", 0, 40_000, "/repo"),
+            ("cargo test", 101, 9_000, "/repo"),
+            ("git push", 1, 800, "/repo"),
+            ("cargo test", 0, 12_000, "/tmp"),
+        ];
+        for (command, exit_code, duration, cwd) in commands {
+            let entry = HistoryEntry::new_command(command, exit_code, duration);
+            history.add_entry(entry.with_cwd(cwd)).unwrap();
+        }
+        history
+            .entries
+            .push_back(HistoryEntry::new_event("cleared"));
+
+        let indices = |text: Option<TextMatch>, filter: SearchFilter| -> Vec<usize> {
+            let found = history.find(text.as_ref(), &filter);
+            found.into_iter().map(|(index, _)| index).collect()
+        };
+        let regex = || {
+            Some(TextMatch::Regex(
+                Regex::new("^cargo (build|test)$").unwrap(),
+            ))
+        };
+        let prefix = || Some(TextMatch::Prefix("git".into()));
+        let failed = SearchFilter {
+            succeeded: Some(false),
+            ..SearchFilter::default()
+        };
+        let slow_in_repo = SearchFilter {
+            cwd: Some("/repo".into()),
+            min_duration: Some(5_000),
+            ..SearchFilter::default()
+        };
+        let exit_101 = SearchFilter {
+            exit_code: Some(101),
+            ..SearchFilter::default()
+        };
+        let future = SearchFilter {
+            since: Some(u64::MAX),
+            ..SearchFilter::default()
+        };
 
-        stats
+        assert_eq!(indices(None, SearchFilter::default()), [0, 1, 2, 3, 4]);
+        assert_eq!(indices(regex(), SearchFilter::default()), [0, 1, 3]);
+        assert_eq!(indices(regex(), failed.clone()), [1]);
+        assert_eq!(indices(prefix(), failed), [2]);
+        assert_eq!(indices(None, slow_in_repo), [0, 1]);
+        assert_eq!(indices(None, exit_101), [1]);
+        assert_eq!(indices(None, future), [] as [usize; 0]);
+        std::fs::remove_file(path).unwrap();
     }
 }
@@ -0,0 +1,247 @@
+use std::collections::{BTreeMap, HashMap};
+
+use super::time::LocalTime;
+use super::{HistoryEntry, HistoryStats, SearchFilter};
+
+// How many commands each list in the stats holds
+const TOP: usize = 10;
+const TOP_DIRECTORIES: usize = 5;
+const TOP_PER_DIRECTORY: usize = 3;
+
+// What's gathered about one command line
+#[derive(Default)]
+struct Runs {
+    count: usize,
+    failures: usize,
+    longest: u64,
+}
+
+/// Statistics over the commands in `entries` that `filter` accepts, with
+/// streaks counted up to `now`.
+pub(super) fn calculate<'a>(
+    entries: impl IntoIterator<Item = &'a HistoryEntry>,
+    filter: &SearchFilter,
+    now: u64,
+) -> HistoryStats {
+    let mut stats = HistoryStats::default();
+    let mut total_duration = 0u64;
+    let mut runs: HashMap<&str, Runs> = HashMap::new();
+    let mut directories: HashMap<&str, HashMap<&str, usize>> = HashMap::new();
+    let mut days: BTreeMap<i64, (String, usize)> = BTreeMap::new();
+
+    for entry in entries {
+        let HistoryEntry::Command {
+            command,
+            timestamp,
+            exit_code,
+            duration,
+            cwd,
+            ..
+        } = entry
+        else {
+            continue;
+        };
+        if !filter.accepts(entry) {
+            continue;
+        }
+        stats.total_commands += 1;
+        stats.failed_commands += usize::from(*exit_code != 0);
+        total_duration += duration;
+
+        let command_runs = runs.entry(command).or_default();
+        command_runs.count += 1;
+        command_runs.failures += usize::from(*exit_code != 0);
+        command_runs.longest = command_runs.longest.max(*duration);
+        if let Some(cwd) = cwd {
+            *directories
+                .entry(cwd)
+                .or_default()
+                .entry(command)
+                .or_default() += 1;
+        }
+        if let Some(local) = LocalTime::at(*timestamp) {
+            stats.by_hour[local.hour as usize] += 1;
+            days.entry(local.day_number)
+                .or_insert_with(|| (local.date(), 0))
+                .1 += 1;
+        }
+    }
+
+    if stats.total_commands > 0 {
+        stats.average_duration = total_duration / stats.total_commands as u64;
+    }
+    stats.unique_commands = runs.len();
+    stats.most_used = top(
+        runs.iter().map(|(command, runs)| (*command, runs.count)),
+        TOP,
+    );
+    stats.slowest = slowest(&runs);
+    stats.failure_rates = failure_rates(&runs);
+    stats.by_directory = by_directory(directories);
+    let today = LocalTime::at(now).map(|local| local.day_number);
+    streaks(&mut stats, days.keys().copied().collect(), today);
+    stats.by_day = days.into_values().collect();
+    stats
+}
+
+// The `count` highest, ties in alphabetical order
+fn top<'a>(counts: impl Iterator<Item = (&'a str, usize)>, count: usize) -> Vec<(String, usize)> {
+    let mut counts: Vec<_> = counts.collect();
+    counts.sort_by(|a, b| b.1.cmp(&a.1).then(a.0.cmp(b.0)));
+    counts
+        .into_iter()
+        .take(count)
+        .map(|(name, n)| (name.to_string(), n))
+        .collect()
+}
+
+fn slowest(runs: &HashMap<&str, Runs>) -> Vec<(String, u64)> {
+    let mut slowest: Vec<_> = runs
+        .iter()
+        .filter(|(_, runs)| runs.longest > 0)
+        .map(|(command, runs)| (*command, runs.longest))
+        .collect();
+    slowest.sort_by(|a, b| b.1.cmp(&a.1).then(a.0.cmp(b.0)));
+    slowest
+        .into_iter()
+        .take(TOP)
+        .map(|(command, longest)| (command.to_string(), longest))
+        .collect()
+}
+
+fn failure_rates(runs: &HashMap<&str, Runs>) -> Vec<(String, usize, usize)> {
+    let mut failing: Vec<_> = runs.iter().filter(|(_, runs)| runs.failures > 0).collect();
+    // a/b > c/d as a*d > c*b, then the more runs the surer the rate
+    failing.sort_by(|(a_command, a), (b_command, b)| {
+        (b.failures * a.count)
+            .cmp(&(a.failures * b.count))
+            .then(b.count.cmp(&a.count))
+            .then(a_command.cmp(b_command))
+    });
+    failing
+        .into_iter()
+        .take(TOP)
+        .map(|(command, runs)| (command.to_string(), runs.count, runs.failures))
+        .collect()
+}
+
+fn by_directory(
+    directories: HashMap<&str, HashMap<&str, usize>>,
+) -> Vec<(String, Vec<(String, usize)>)> {
+    let totals = directories
+        .iter()
+        .map(|(dir, commands)| (*dir, commands.values().sum()));
+    top(totals, TOP_DIRECTORIES)
+        .into_iter()
+        .map(|(dir, _)| {
+            let commands = directories[dir.as_str()]
+                .iter()
+                .map(|(command, count)| (*command, *count));
+            let commands = top(commands, TOP_PER_DIRECTORY);
+            (dir, commands)
+        })
+        .collect()
+}
+
+// `days` are sorted local day numbers
+fn streaks(stats: &mut HistoryStats, days: Vec<i64>, today: Option<i64>) {
+    stats.active_days = days.len();
+    let mut run = 0;
+    let mut previous = None;
+    for &day in &days {
+        run = if previous == Some(day - 1) {
+            run + 1
+        } else {
+            1
+        };
+        stats.longest_streak = stats.longest_streak.max(run);
+        previous = Some(day);
+    }
+    // A streak still counts before today's first command
+    let last = days.last().copied();
+    let ongoing = today.is_some_and(|today| last == Some(today) || last == Some(today - 1));
+    stats.current_streak = if ongoing { run } else { 0 };
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const DAY: u64 = 24 * 60 * 60;
+
+    fn command(command: &str, timestamp: u64, exit_code: i32, duration: u64) -> HistoryEntry {
+        let cwd = if command.starts_with("cargo") {
+            "/repo"
+        } else {
+            "/tmp"
+        };
+        HistoryEntry::Command {
+            command: command.to_string().into(),
+            timestamp,
+            end_time: timestamp + duration / 1000,
+            exit_code,
+            duration,
+            cwd: Some(cwd.into()),
+            session: None,
+            hostname: None,
+        }
+    }
+
+    // Noon, so a time zone can't move a command to another day, and a
+    // week's commands up to then
+    fn week() -> (u64, [HistoryEntry; 6]) {
+        let noon = LocalTime::at(1_700_000_000)
+            .map(|local| 1_700_000_000 - u64::from(local.hour) * 3600 + 12 * 3600)
+            .unwrap();
+        let entries = [
+            command("ls", noon - 5 * DAY, 0, 5),
+            command("cargo test", noon - 2 * DAY, 101, 30_000),
+            command("cargo test", noon - DAY, 0, 20_000),
+            command("cargo build", noon - DAY, 0, 60_000),
+            command("ls", noon, 2, 3),
+            command("cargo test", noon, 101, 25_000),
+        ];
+        (noon, entries)
+    }
+
+    #[test]
+    fn test_calculate_commands() {
+        let (noon, entries) = week();
+        let stats = calculate(&entries, &SearchFilter::default(), noon + 60);
+
+        assert_eq!((stats.total_commands, stats.unique_commands), (6, 3));
+        assert_eq!(stats.failed_commands, 3);
+        assert_eq!(stats.most_used[0], ("cargo test".to_string(), 3));
+        assert_eq!(stats.slowest[0], ("cargo build".to_string(), 60_000));
+        assert_eq!(
+            stats.failure_rates,
+            [("cargo test".to_string(), 3, 2), ("ls".to_string(), 2, 1)]
+        );
+        assert_eq!(stats.by_directory[0].0, "/repo");
+        assert_eq!(stats.by_directory[0].1[0], ("cargo test".to_string(), 3));
+    }
+
+    #[test]
+    fn test_calculate_activity() {
+        let (noon, entries) = week();
+        let stats = calculate(&entries, &SearchFilter::default(), noon + 60);
+        assert_eq!(stats.by_hour[12], 6);
+        assert_eq!(stats.by_day.len(), 4);
+        assert_eq!(stats.by_day[3].1, 2);
+        assert_eq!(
+            (
+                stats.active_days,
+                stats.longest_streak,
+                stats.current_streak
+            ),
+            (4, 3, 3)
+        );
+
+        let recent = SearchFilter {
+            since: Some(noon - DAY - 60),
+            ..SearchFilter::default()
+        };
+        let stats = calculate(&entries, &recent, noon + 3 * DAY);
+        assert_eq!((stats.total_commands, stats.current_streak), (4, 0));
+    }
+}
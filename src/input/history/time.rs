@@ -0,0 +1,245 @@
+use std::mem::MaybeUninit;
+
+use crate::sys::local_time;
+
+const DAY: u64 = 24 * 60 * 60;
+
+/// A length of time like `90` (seconds), `500ms`, `5s`, `2m` or `1h30m`,
+/// in milliseconds. `d` and `w` are days and weeks.
+pub fn parse_duration(spec: &str) -> Option<u64> {
+    if !spec.is_empty() && spec.bytes().all(|b| b.is_ascii_digit()) {
+        return spec.parse::<u64>().ok()?.checked_mul(1000);
+    }
+    let mut total: u64 = 0;
+    let mut rest = spec;
+    while !rest.is_empty() {
+        let digits = rest.bytes().take_while(u8::is_ascii_digit).count();
+        let letters = rest[digits..]
+            .bytes()
+            .take_while(u8::is_ascii_alphabetic)
+            .count();
+        if digits == 0 || letters == 0 {
+            return None;
+        }
+        let amount: u64 = rest[..digits].parse().ok()?;
+        let unit = match &rest[digits..digits + letters] {
+            "ms" => 1,
+            "s" => 1000,
+            "m" => 60 * 1000,
+            "h" => 60 * 60 * 1000,
+            "d" => DAY * 1000,
+            "w" => 7 * DAY * 1000,
+            _ => return None,
+        };
+        total = total.checked_add(amount.checked_mul(unit)?)?;
+        rest = &rest[digits + letters..];
+    }
+    (!spec.is_empty()).then_some(total)
+}
+
+/// A point in time, in seconds since the epoch: a duration ago like `2h`,
+/// `now`, `today` or `yesterday` (local midnight), a local
+/// `YYYY-MM-DD [HH:MM[:SS]]`, or `@seconds`.
+pub fn parse_time(spec: &str, now: u64) -> Option<u64> {
+    let spec = spec.trim();
+    if let Some(seconds) = spec.strip_prefix('@') {
+        return seconds.parse().ok();
+    }
+    match spec {
+        "now" => return Some(now),
+        "today" => return local_midnight(now, 0),
+        "yesterday" => return local_midnight(now, 1),
+        _ => {}
+    }
+    if let Some(ago) = parse_duration(spec) {
+        return Some(now.saturating_sub(ago / 1000));
+    }
+    parse_date(spec)
+}
+
+// `YYYY-MM-DD`, then optionally a space or `T` and `HH:MM[:SS]`
+fn parse_date(spec: &str) -> Option<u64> {
+    let (date, time) = match spec.split_once([' ', 'T']) {
+        Some((date, time)) => (date, Some(time)),
+        None => (spec, None),
+    };
+    let numbers = |text: &str, separator: char| -> Option<Vec<i32>> {
+        text.split(separator).map(|n| n.parse().ok()).collect()
+    };
+    let [year, month, day] = numbers(date, '-')?[..] else {
+        return None;
+    };
+    let (hour, minute, second) = match time.map(|time| numbers(time, ':')) {
+        None => (0, 0, 0),
+        Some(Some(parts)) => match parts[..] {
+            [hour, minute] => (hour, minute, 0),
+            [hour, minute, second] => (hour, minute, second),
+            _ => return None,
+        },
+        Some(None) => return None,
+    };
+    let valid = (1..=12).contains(&month)
+        && (1..=31).contains(&day)
+        && (0..24).contains(&hour)
+        && (0..60).contains(&minute)
+        && (0..=60).contains(&second);
+    if !valid {
+        return None;
+    }
+    // SAFETY: an all-zero `tm` is valid, and mktime only reads and
+    // normalises the struct it's given
+    let mut tm: libc::tm = unsafe { MaybeUninit::zeroed().assume_init() };
+    tm.tm_year = year - 1900;
+    tm.tm_mon = month - 1;
+    tm.tm_mday = day;
+    tm.tm_hour = hour;
+    tm.tm_min = minute;
+    tm.tm_sec = second;
+    tm.tm_isdst = -1;
+    make_time(&mut tm)
+}
+
+/// A time broken down in the local time zone.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct LocalTime {
+    pub year: i32,
+    pub month: u32,
+    pub day: u32,
+    pub hour: u32,
+    pub minute: u32,
+    pub second: u32,
+    // Days since the epoch, counted in local time, to tell days apart
+    pub day_number: i64,
+}
+
+impl LocalTime {
+    pub fn at(timestamp: u64) -> Option<Self> {
+        let tm = local_time(timestamp)?;
+        // `tm_gmtoff` is a C long, only 32 bits on some targets
+        #[allow(clippy::useless_conversion)]
+        let local = i64::try_from(timestamp).ok()? + i64::from(tm.tm_gmtoff);
+        Some(Self {
+            year: tm.tm_year + 1900,
+            month: u32::try_from(tm.tm_mon + 1).ok()?,
+            day: u32::try_from(tm.tm_mday).ok()?,
+            hour: u32::try_from(tm.tm_hour).ok()?,
+            minute: u32::try_from(tm.tm_min).ok()?,
+            second: u32::try_from(tm.tm_sec).ok()?,
+            day_number: local.div_euclid(DAY as i64),
+        })
+    }
+
+    /// `YYYY-MM-DD`
+    pub fn date(&self) -> String {
+        format!("{:04}-{:02}-{:02}", self.year, self.month, self.day)
+    }
+}
+
+// The local midnight starting the day `days_ago` days before `now`
+fn local_midnight(now: u64, days_ago: i32) -> Option<u64> {
+    let mut tm = local_time(now)?;
+    tm.tm_hour = 0;
+    tm.tm_min = 0;
+    tm.tm_sec = 0;
+    tm.tm_mday -= days_ago;
+    tm.tm_isdst = -1;
+    make_time(&mut tm)
+}
+
+/// How long before `now` `timestamp` was, like `3m ago`.
+pub fn relative_time(timestamp: u64, now: u64) -> String {
+    let seconds = now.saturating_sub(timestamp);
+    match seconds {
+        0..60 => "just now".to_string(),
+        60..3600 => format!("{}m ago", seconds / 60),
+        3600..DAY => format!("{}h ago", seconds / 3600),
+        _ => format!("{}d ago", seconds / DAY),
+    }
+}
+
+fn make_time(tm: &mut libc::tm) -> Option<u64> {
+    // SAFETY: `tm` is a valid, initialised struct
+    let time = unsafe { libc::mktime(tm) };
+    u64::try_from(time).ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::sys::format_time;
+
+    #[test]
+    fn test_parse_duration() {
+        let cases = [
+            ("90", Some(90_000)),
+            ("500ms", Some(500)),
+            ("5s", Some(5000)),
+            ("1h30m", Some(5_400_000)),
+            ("2d", Some(2 * DAY * 1000)),
+            ("", None),
+            ("5", Some(5000)),
+            ("5x", None),
+            ("h", None),
+            ("1.5s", None),
+        ];
+        for (spec, expected) in cases {
+            assert_eq!(parse_duration(spec), expected, "{:?}", spec);
+        }
+    }
+
+    #[test]
+    fn test_parse_time() {
+        let now = 1_700_000_000;
+        assert_eq!(parse_time("2h", now), Some(now - 7200));
+        assert_eq!(parse_time("@1600000000", now), Some(1_600_000_000));
+        assert_eq!(parse_time("now", now), Some(now));
+
+        let today = parse_time("today", now).unwrap();
+        let yesterday = parse_time("yesterday", now).unwrap();
+        assert!(today <= now && now - today < DAY);
+        assert!((DAY - 3600..=DAY + 3600).contains(&(today - yesterday)));
+
+        let date = parse_time("2023-11-14", now).unwrap();
+        assert_eq!(parse_time("2023-11-14 00:00", now), Some(date));
+        assert_eq!(parse_time("2023-11-14T01:02:03", now), Some(date + 3723));
+        for bad in ["2023-13-01", "2023-11", "2023-11-14 25:00", "soon"] {
+            assert_eq!(parse_time(bad, now), None, "{:?}", bad);
+        }
+    }
+
+    #[test]
+    fn test_relative_time_boundaries() {
+        let cases = [
+            (0, "just now"),
+            (59, "just now"),
+            (60, "1m ago"),
+            (3599, "59m ago"),
+            (3600, "1h ago"),
+            (DAY - 1, "23h ago"),
+            (DAY, "1d ago"),
+            (45 * DAY, "45d ago"),
+        ];
+        for (age, expected) in cases {
+            assert_eq!(relative_time(1_000_000, 1_000_000 + age), expected);
+        }
+        // A clock that went backwards doesn't make it the future
+        assert_eq!(relative_time(1_000_100, 1_000_000), "just now");
+    }
+
+    #[test]
+    fn test_local_time() {
+        let date = parse_time("2023-11-14", 0).unwrap();
+        let local = LocalTime::at(date + 3723).unwrap();
+        assert_eq!(local.date(), "2023-11-14");
+        assert_eq!((local.hour, local.minute, local.second), (1, 2, 3));
+        let next_day = LocalTime::at(date + DAY + 7200).unwrap();
+        assert_eq!(next_day.day_number, local.day_number + 1);
+
+        assert_eq!(format_time(date + 3723, "%F %T"), "2023-11-14 01:02:03");
+        assert_eq!(format_time(date + 3723, "%d/%m %H:%M "), "14/11 01:02 ");
+        assert_eq!(format_time(date, ""), "");
+        assert_eq!(relative_time(date, date + 45), "just now");
+        assert_eq!(relative_time(date, date + 180), "3m ago");
+        assert_eq!(relative_time(date, date + 3 * DAY), "3d ago");
+    }
+}
@@ -5,9 +5,16 @@ use std::time::{SystemTime, UNIX_EPOCH};
 pub enum HistoryEntry {
     Command {
         command: Cow<'static, str>,
+        // When it started and finished, in seconds since the epoch
         timestamp: u64,
+        end_time: u64,
         exit_code: i32,
         duration: u64,
+        // The directory it ran in, unknown for commands from old files
+        cwd: Option<String>,
+        // The shell session that ran it, and on which host
+        session: Option<String>,
+        hostname: Option<String>,
     },
     Event {
         description: Cow<'static, str>,
@@ -16,21 +23,57 @@ pub enum HistoryEntry {
 }
 
 impl HistoryEntry {
+    /// A command that just finished after running for `duration`
+    /// milliseconds.
     pub fn new_command(
         command: impl Into<Cow<'static, str>>,
         exit_code: i32,
         duration: u64,
     ) -> Self {
-        let timestamp = SystemTime::now()
+        let end_time = SystemTime::now()
             .duration_since(UNIX_EPOCH)
             .unwrap_or_default()
             .as_secs();
 
         HistoryEntry::Command {
             command: command.into(),
-            timestamp,
+            timestamp: end_time.saturating_sub(duration / 1000),
+            end_time,
             exit_code,
             duration,
+            cwd: None,
+            session: None,
+            hostname: None,
+        }
+    }
+
+    /// Records the directory a command ran in.
+    pub fn with_cwd(mut self, dir: impl Into<String>) -> Self {
+        if let HistoryEntry::Command { cwd, .. } = &mut self {
+            *cwd = Some(dir.into());
+        }
+        self
+    }
+
+    /// Records the session and host a command ran in, unless it already
+    /// has them.
+    pub fn with_origin(mut self, session_id: &str, host: &str) -> Self {
+        if let HistoryEntry::Command {
+            session, hostname, ..
+        } = &mut self
+        {
+            session.get_or_insert_with(|| session_id.to_string());
+            hostname.get_or_insert_with(|| host.to_string());
+        }
+        self
+    }
+
+    /// When it happened, or for a command when it started.
+    pub fn timestamp(&self) -> u64 {
+        match self {
+            HistoryEntry::Command { timestamp, .. } | HistoryEntry::Event { timestamp, .. } => {
+                *timestamp
+            }
         }
     }
 
@@ -62,4 +105,21 @@ pub struct HistoryStats {
     pub failed_commands: usize,
     pub average_duration: u64,
     pub most_used: Vec<(String, usize)>,
+    // Commands started in each hour of the day, local time
+    pub by_hour: [usize; 24],
+    // Commands per local date, `YYYY-MM-DD`, oldest first; idle days are
+    // left out
+    pub by_day: Vec<(String, usize)>,
+    // The longest runs of each command, in milliseconds, slowest first
+    pub slowest: Vec<(String, u64)>,
+    // Commands that failed, with how many times they ran and failed, the
+    // highest share of failures first
+    pub failure_rates: Vec<(String, usize, usize)>,
+    // The directories most commands ran in, with their most used commands
+    pub by_directory: Vec<(String, Vec<(String, usize)>)>,
+    // Days with at least one command, and the longest run of those in a
+    // row, overall and ending today or yesterday
+    pub active_days: usize,
+    pub longest_streak: usize,
+    pub current_streak: usize,
 }
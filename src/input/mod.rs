@@ -1,6 +1,11 @@
 mod completer;
+mod finder;
 pub mod history;
+mod terminal;
 
-pub use completer::ShellCompleter;
+pub use completer::{
+    AcceptSuggestion, CompletionSpec, CompletionSpecs, ExpandAbbreviation, ShellCompleter,
+};
+pub use finder::HistoryFinder;
 pub use history::types::{HistoryEntry, HistorySearchMode, HistoryStats};
-pub use history::History;
+pub use history::{EditorHistory, History, HistoryFilter, Reload};
@@ -7,3 +7,4 @@ pub mod highlight;
 pub mod input;
 pub mod path;
 pub mod process;
+pub mod sys;
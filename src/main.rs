@@ -4,9 +4,16 @@ use std::env;
 
 fn main() -> Result<(), aorta::error::ShellError> {
     let mut flags = Flags::new();
-    let args: Vec<String> = env::args().skip(1).collect();
+    let mut args = env::args();
+    let argv0 = args.next().unwrap_or_default();
+    let args: Vec<String> = args.collect();
     flags.parse(&args)?;
 
+    // login(1) and sshd start login shells with a leading dash, like `-aorta`
+    if argv0.starts_with('-') {
+        flags.set("login");
+    }
+
     if flags.is_set("help") {
         flags.print_help();
         return Ok(());
@@ -17,11 +24,6 @@ fn main() -> Result<(), aorta::error::ShellError> {
         return Ok(());
     }
 
-    if !flags.is_set("quiet") {
-        // FEAT: TODO: Add Support of useing .motd or .aorta_motd to display a message
-        // | or maybe use a .config/aorta/aorta.toml and direct the motd file to display a message
-    }
-
     let mut shell = Shell::new(flags)?;
     shell.run()
 }
@@ -0,0 +1,206 @@
+use std::fs;
+use std::path::Path;
+
+pub fn has_glob_chars(pattern: &str) -> bool {
+    let mut chars = pattern.chars();
+    while let Some(c) = chars.next() {
+        match c {
+            '\\' => {
+                chars.next();
+            }
+            '*' | '?' | '[' => return true,
+            _ => {}
+        }
+    }
+    false
+}
+
+/// Matches `text` against a shell pattern supporting `*`, `?`, `[...]`
+/// (with `!`/`^` negation and ranges) and backslash escapes.
+pub fn matches(pattern: &str, text: &str) -> bool {
+    let pattern: Vec<char> = pattern.chars().collect();
+    let text: Vec<char> = text.chars().collect();
+    match_from(&pattern, &text)
+}
+
+fn match_from(pattern: &[char], text: &[char]) -> bool {
+    let Some((&first, rest)) = pattern.split_first() else {
+        return text.is_empty();
+    };
+
+    match first {
+        '*' => (0..=text.len()).any(|skip| match_from(rest, &text[skip..])),
+        '?' => !text.is_empty() && match_from(rest, &text[1..]),
+        '[' => match (text.first(), match_class(rest)) {
+            (Some(&c), Some((class, remaining))) => {
+                class.contains(c) && match_from(remaining, &text[1..])
+            }
+            (Some(&c), None) => c == '[' && match_from(rest, &text[1..]),
+            (None, _) => false,
+        },
+        '\\' if !rest.is_empty() => {
+            text.first() == Some(&rest[0]) && match_from(&rest[1..], &text[1..])
+        }
+        c => text.first() == Some(&c) && match_from(rest, &text[1..]),
+    }
+}
+
+struct CharClass {
+    negated: bool,
+    items: Vec<(char, char)>,
+}
+
+impl CharClass {
+    fn contains(&self, c: char) -> bool {
+        let found = self.items.iter().any(|(lo, hi)| *lo <= c && c <= *hi);
+        found != self.negated
+    }
+}
+
+// Parses the body of a `[...]` class; returns None when it is not closed
+fn match_class(pattern: &[char]) -> Option<(CharClass, &[char])> {
+    let mut idx = 0;
+    let negated = matches!(pattern.first(), Some('!') | Some('^'));
+    if negated {
+        idx += 1;
+    }
+
+    let mut items = Vec::new();
+    let mut first = true;
+    while idx < pattern.len() {
+        let c = pattern[idx];
+        if c == ']' && !first {
+            return Some((CharClass { negated, items }, &pattern[idx + 1..]));
+        }
+        first = false;
+        if pattern.get(idx + 1) == Some(&'-') && pattern.get(idx + 2).is_some_and(|&hi| hi != ']') {
+            items.push((c, pattern[idx + 2]));
+            idx += 3;
+        } else {
+            items.push((c, c));
+            idx += 1;
+        }
+    }
+    None
+}
+
+/// Expands a pathname pattern against the filesystem. Returns the sorted
+/// matches; hidden files only match when `dotglob` is set or the pattern
+/// component starts with a dot.
+pub fn expand(pattern: &str, dotglob: bool) -> Vec<String> {
+    let (mut candidates, rest) = match pattern.strip_prefix('/') {
+        Some(rest) => (vec!["/".to_string()], rest),
+        None => (vec![String::new()], pattern),
+    };
+
+    for component in rest.split('/').filter(|c| !c.is_empty()) {
+        candidates = candidates
+            .into_iter()
+            .flat_map(|base| expand_component(&base, component, dotglob))
+            .collect();
+        if candidates.is_empty() {
+            break;
+        }
+    }
+
+    if pattern.ends_with('/') {
+        candidates.retain(|c| Path::new(c).is_dir());
+        candidates.iter_mut().for_each(|c| c.push('/'));
+    }
+    candidates.sort();
+    candidates
+}
+
+fn expand_component(base: &str, component: &str, dotglob: bool) -> Vec<String> {
+    let join = |name: &str| {
+        if base.is_empty() || base.ends_with('/') {
+            format!("{}{}", base, name)
+        } else {
+            format!("{}/{}", base, name)
+        }
+    };
+
+    if !has_glob_chars(component) {
+        let literal = join(&unescape(component));
+        return if Path::new(&literal).exists() || Path::new(&literal).is_symlink() {
+            vec![literal]
+        } else {
+            Vec::new()
+        };
+    }
+
+    let dir = if base.is_empty() { "." } else { base };
+    let Ok(entries) = fs::read_dir(dir) else {
+        return Vec::new();
+    };
+
+    let show_hidden = dotglob || component.starts_with('.');
+    entries
+        .filter_map(Result::ok)
+        .filter_map(|entry| entry.file_name().into_string().ok())
+        .filter(|name| show_hidden || !name.starts_with('.'))
+        .filter(|name| matches(component, name))
+        .map(|name| join(&name))
+        .collect()
+}
+
+fn unescape(component: &str) -> String {
+    let mut result = String::with_capacity(component.len());
+    let mut chars = component.chars();
+    while let Some(c) = chars.next() {
+        match c {
+            '\\' => result.extend(chars.next()),
+            c => result.push(c),
+        }
+    }
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::env;
+
+    #[test]
+    fn test_matches() {
+        assert!(matches("*.rs", "main.rs"));
+        assert!(!matches("*.rs", "main.rso"));
+        assert!(matches("cd *", "cd /tmp"));
+        assert!(matches("?a[bc]", "xab"));
+        assert!(matches("[!a]*", "bcd"));
+        assert!(!matches("[!a]*", "abc"));
+        assert!(matches("file[0-9]", "file7"));
+        assert!(matches("\\*", "*"));
+        assert!(!matches("\\*", "x"));
+    }
+
+    #[test]
+    fn test_has_glob_chars() {
+        assert!(has_glob_chars("src/*.rs"));
+        assert!(!has_glob_chars("plain"));
+        assert!(!has_glob_chars("escaped\\*"));
+    }
+
+    #[test]
+    fn test_expand() {
+        let dir = env::temp_dir().join("aorta_glob_test");
+        fs::create_dir_all(&dir).unwrap();
+        for name in ["a.rs", "b.rs", "c.txt", ".hidden.rs"] {
+            fs::write(dir.join(name), "").unwrap();
+        }
+
+        let pattern = format!("{}/*.rs", dir.display());
+        let found = expand(&pattern, false);
+        assert_eq!(
+            found,
+            vec![
+                format!("{}/a.rs", dir.display()),
+                format!("{}/b.rs", dir.display())
+            ]
+        );
+        assert_eq!(expand(&pattern, true).len(), 3);
+        assert!(expand(&format!("{}/*.md", dir.display()), false).is_empty());
+
+        fs::remove_dir_all(dir).unwrap();
+    }
+}
@@ -1,3 +1,4 @@
 mod expander;
+pub mod glob;
 
 pub use expander::PathExpander;
@@ -1,4 +1,5 @@
-use std::process::{Command, Stdio};
+use std::os::unix::process::ExitStatusExt;
+use std::process::{Command, ExitStatus, Stdio};
 
 use super::{signal, ProcessError};
 use crate::flags::Flags;
@@ -18,7 +19,8 @@ impl CommandExecutor {
         })
     }
 
-    pub fn spawn_process(&self, args: &[&str]) -> Result<(), ProcessError> {
+    /// Runs an external command to completion and returns its exit status.
+    pub fn spawn_process(&self, args: &[&str]) -> Result<i32, ProcessError> {
         let expanded_args: Vec<String> = args
             .iter()
             .map(|&arg| {
@@ -41,6 +43,7 @@ impl CommandExecutor {
             .stderr(Stdio::inherit())
             .env_clear()
             .envs(std::env::vars());
+        signal::prepare_child(&mut command);
 
         let mut child = match command.spawn() {
             Ok(child) => child,
@@ -49,21 +52,18 @@ impl CommandExecutor {
                     if !self.quiet_mode {
                         eprintln!("aorta: command not found: {}", args[0]);
                     }
-                    return Ok(());
+                    return Ok(127);
                 }
                 return Err(e.into());
             }
         };
 
-        let _pid = child.id();
-        signal::setup_signal_handlers()?;
-
         match child.wait() {
             Ok(status) => {
                 if !status.success() && !self.quiet_mode {
                     println!("Process exited with status: {}", status);
                 }
-                Ok(())
+                Ok(exit_code(status))
             }
             Err(e) => {
                 if e.kind() == std::io::ErrorKind::NotFound {
@@ -75,3 +75,11 @@ impl CommandExecutor {
         }
     }
 }
+
+/// Maps an exit status to the shell convention: the exit code, or 128 plus
+/// the signal number for children killed by a signal.
+pub fn exit_code(status: ExitStatus) -> i32 {
+    status
+        .code()
+        .unwrap_or_else(|| 128 + status.signal().unwrap_or(0))
+}
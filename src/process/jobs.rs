@@ -0,0 +1,209 @@
+use std::collections::BTreeMap;
+
+use libc::{c_int, pid_t};
+
+/// A background pipeline started with `&`.
+#[derive(Debug, Clone)]
+pub struct Job {
+    pub id: usize,
+    pub pgid: pid_t,
+    pub command: String,
+    // Each process of the pipeline with its status once it has exited
+    processes: Vec<(pid_t, Option<i32>)>,
+}
+
+impl Job {
+    pub fn pids(&self) -> impl Iterator<Item = pid_t> + '_ {
+        self.processes.iter().map(|(pid, _)| *pid)
+    }
+
+    pub fn is_done(&self) -> bool {
+        self.processes.iter().all(|(_, status)| status.is_some())
+    }
+
+    /// The job's status: that of its last process, like a pipeline.
+    pub fn status(&self) -> Option<i32> {
+        self.processes.last().and_then(|(_, status)| *status)
+    }
+}
+
+/// The shell's table of background jobs, addressed by `%n` specs.
+#[derive(Debug, Default)]
+pub struct Jobs {
+    jobs: BTreeMap<usize, Job>,
+    // Statuses of processes reaped after their job was reported
+    finished: BTreeMap<pid_t, i32>,
+}
+
+impl Jobs {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn add(&mut self, pids: Vec<pid_t>, command: &str) -> usize {
+        let id = self.jobs.keys().next_back().map_or(1, |last| last + 1);
+        let pgid = pids.first().copied().unwrap_or(0);
+        self.jobs.insert(
+            id,
+            Job {
+                id,
+                pgid,
+                command: command.to_string(),
+                processes: pids.into_iter().map(|pid| (pid, None)).collect(),
+            },
+        );
+        id
+    }
+
+    pub fn get(&self, id: usize) -> Option<&Job> {
+        self.jobs.get(&id)
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = &Job> {
+        self.jobs.values()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.jobs.is_empty()
+    }
+
+    /// Resolves a job spec: `%n`, `%%`/`%+` (current), `%-` (previous),
+    /// `%prefix` or `%?substring` of the command.
+    pub fn find(&self, spec: &str) -> Option<&Job> {
+        let spec = spec.strip_prefix('%')?;
+        match spec {
+            "" | "%" | "+" => self.jobs.values().next_back(),
+            "-" => self.jobs.values().rev().nth(1),
+            _ => {
+                if let Ok(id) = spec.parse::<usize>() {
+                    return self.jobs.get(&id);
+                }
+                match spec.strip_prefix('?') {
+                    Some(part) => self.jobs.values().find(|j| j.command.contains(part)),
+                    None => self.jobs.values().find(|j| j.command.starts_with(spec)),
+                }
+            }
+        }
+    }
+
+    pub fn job_of(&self, pid: pid_t) -> Option<usize> {
+        self.jobs
+            .values()
+            .find(|job| job.pids().any(|p| p == pid))
+            .map(|job| job.id)
+    }
+
+    /// Records a process status; returns the job id if this finished it.
+    pub fn record(&mut self, pid: pid_t, status: i32) -> Option<usize> {
+        let Some(id) = self.job_of(pid) else {
+            self.finished.insert(pid, status);
+            return None;
+        };
+        let job = self.jobs.get_mut(&id)?;
+        for (p, s) in job.processes.iter_mut() {
+            if *p == pid {
+                *s = Some(status);
+            }
+        }
+        job.is_done().then_some(id)
+    }
+
+    /// Removes a finished job, remembering its processes' statuses.
+    pub fn remove(&mut self, id: usize) -> Option<Job> {
+        let job = self.jobs.remove(&id)?;
+        for (pid, status) in &job.processes {
+            if let Some(status) = status {
+                self.finished.insert(*pid, *status);
+            }
+        }
+        Some(job)
+    }
+
+    pub fn take_finished(&mut self, pid: pid_t) -> Option<i32> {
+        self.finished.remove(&pid)
+    }
+
+    /// Collects exited processes without blocking and returns the jobs that
+    /// are now complete, removed from the table.
+    pub fn reap(&mut self) -> Vec<Job> {
+        let pids: Vec<pid_t> = self
+            .jobs
+            .values()
+            .flat_map(|job| job.processes.iter())
+            .filter(|(_, status)| status.is_none())
+            .map(|(pid, _)| *pid)
+            .collect();
+
+        let mut done = Vec::new();
+        for pid in pids {
+            if let Some(status) = wait_pid(pid, libc::WNOHANG) {
+                done.extend(self.record(pid, status));
+            }
+        }
+        done.into_iter().filter_map(|id| self.remove(id)).collect()
+    }
+}
+
+/// Waits for `pid` (or any child for -1) and returns its shell status, or
+/// None if nothing was reaped.
+pub fn wait_pid(pid: pid_t, flags: c_int) -> Option<i32> {
+    wait_any(pid, flags).map(|(_, status)| status)
+}
+
+pub fn wait_any(pid: pid_t, flags: c_int) -> Option<(pid_t, i32)> {
+    let mut raw: c_int = 0;
+    loop {
+        let result = unsafe { libc::waitpid(pid, &mut raw, flags) };
+        if result > 0 {
+            return Some((result, decode_status(raw)));
+        }
+        // Retry when a trapped signal interrupted the wait
+        if result < 0 && std::io::Error::last_os_error().kind() == std::io::ErrorKind::Interrupted {
+            continue;
+        }
+        return None;
+    }
+}
+
+fn decode_status(raw: c_int) -> i32 {
+    if libc::WIFEXITED(raw) {
+        libc::WEXITSTATUS(raw)
+    } else if libc::WIFSIGNALED(raw) {
+        128 + libc::WTERMSIG(raw)
+    } else {
+        0
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_job_specs() {
+        let mut jobs = Jobs::new();
+        let first = jobs.add(vec![100, 101], "sleep 10 | cat");
+        let second = jobs.add(vec![200], "make all");
+
+        assert_eq!(jobs.find("%1").map(|j| j.id), Some(first));
+        assert_eq!(jobs.find("%%").map(|j| j.id), Some(second));
+        assert_eq!(jobs.find("%-").map(|j| j.id), Some(first));
+        assert_eq!(jobs.find("%make").map(|j| j.id), Some(second));
+        assert_eq!(jobs.find("%?cat").map(|j| j.id), Some(first));
+        assert!(jobs.find("%9").is_none());
+        assert!(jobs.find("1").is_none());
+    }
+
+    #[test]
+    fn test_record_completes_job() {
+        let mut jobs = Jobs::new();
+        let id = jobs.add(vec![100, 101], "a | b");
+
+        assert_eq!(jobs.record(100, 1), None);
+        assert_eq!(jobs.record(101, 0), Some(id));
+        let job = jobs.remove(id).unwrap();
+        assert_eq!(job.status(), Some(0));
+        assert_eq!(jobs.take_finished(100), Some(1));
+        assert!(jobs.is_empty());
+    }
+}
@@ -1,9 +1,11 @@
 use std::fmt;
 
 mod executor;
+pub mod jobs;
 pub mod signal;
 
-pub use executor::CommandExecutor as ProcessExecutor;
+pub use executor::{exit_code, CommandExecutor as ProcessExecutor};
+pub use jobs::{Job, Jobs};
 
 #[derive(Debug)]
 pub enum ProcessError {
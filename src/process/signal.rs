@@ -1,14 +1,250 @@
 use crate::process::ProcessError;
 
-use libc::{sighandler_t, signal, SIGINT};
+use libc::c_int;
+use std::os::unix::process::CommandExt;
+use std::process::Command;
+use std::sync::atomic::{AtomicBool, AtomicU8, Ordering};
 
-pub extern "C" fn handle_sigint(_: i32) {
-    // Do nothing, let the child process handle the signal
+// Highest signal number on Linux is 64 (SIGRTMAX)
+const NSIG: usize = 65;
+
+pub const SIGNALS: &[(&str, c_int)] = &[
+    ("HUP", libc::SIGHUP),
+    ("INT", libc::SIGINT),
+    ("QUIT", libc::SIGQUIT),
+    ("ILL", libc::SIGILL),
+    ("TRAP", libc::SIGTRAP),
+    ("ABRT", libc::SIGABRT),
+    ("BUS", libc::SIGBUS),
+    ("FPE", libc::SIGFPE),
+    ("KILL", libc::SIGKILL),
+    ("USR1", libc::SIGUSR1),
+    ("SEGV", libc::SIGSEGV),
+    ("USR2", libc::SIGUSR2),
+    ("PIPE", libc::SIGPIPE),
+    ("ALRM", libc::SIGALRM),
+    ("TERM", libc::SIGTERM),
+    ("CHLD", libc::SIGCHLD),
+    ("CONT", libc::SIGCONT),
+    ("STOP", libc::SIGSTOP),
+    ("TSTP", libc::SIGTSTP),
+    ("TTIN", libc::SIGTTIN),
+    ("TTOU", libc::SIGTTOU),
+    ("URG", libc::SIGURG),
+    ("XCPU", libc::SIGXCPU),
+    ("XFSZ", libc::SIGXFSZ),
+    ("VTALRM", libc::SIGVTALRM),
+    ("PROF", libc::SIGPROF),
+    ("WINCH", libc::SIGWINCH),
+    ("IO", libc::SIGIO),
+    ("SYS", libc::SIGSYS),
+];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Disposition {
+    Default,
+    Ignore,
+    Catch,
 }
 
-pub fn setup_signal_handlers() -> Result<(), ProcessError> {
-    unsafe {
-        signal(SIGINT, handle_sigint as sighandler_t);
+impl Disposition {
+    fn from_u8(value: u8) -> Self {
+        match value {
+            1 => Disposition::Ignore,
+            2 => Disposition::Catch,
+            _ => Disposition::Default,
+        }
+    }
+
+    fn as_u8(self) -> u8 {
+        match self {
+            Disposition::Default => 0,
+            Disposition::Ignore => 1,
+            Disposition::Catch => 2,
+        }
+    }
+}
+
+// Signal dispositions are process-wide, so the bookkeeping is too
+static PENDING: [AtomicBool; NSIG] = [const { AtomicBool::new(false) }; NSIG];
+static CURRENT: [AtomicU8; NSIG] = [const { AtomicU8::new(0) }; NSIG];
+static BASE: [AtomicU8; NSIG] = [const { AtomicU8::new(0) }; NSIG];
+static USER_IGNORED: [AtomicBool; NSIG] = [const { AtomicBool::new(false) }; NSIG];
+
+extern "C" fn record_signal(sig: c_int) {
+    if let Some(flag) = PENDING.get(sig as usize) {
+        flag.store(true, Ordering::SeqCst);
+    }
+}
+
+/// Looks up a signal by name (`INT`, `SIGINT`, case-insensitive) or number.
+pub fn from_name(name: &str) -> Option<c_int> {
+    if let Ok(number) = name.parse::<c_int>() {
+        return (number > 0 && (number as usize) < NSIG).then_some(number);
+    }
+
+    let upper = name.to_ascii_uppercase();
+    let short = upper.strip_prefix("SIG").unwrap_or(&upper);
+    SIGNALS
+        .iter()
+        .find(|(signal_name, _)| *signal_name == short)
+        .map(|(_, number)| *number)
+}
+
+/// Returns the short name (without the `SIG` prefix) of a signal number.
+pub fn name(sig: c_int) -> Option<&'static str> {
+    SIGNALS
+        .iter()
+        .find(|(_, number)| *number == sig)
+        .map(|(signal_name, _)| *signal_name)
+}
+
+fn index(sig: c_int) -> Result<usize, ProcessError> {
+    if sig == libc::SIGKILL || sig == libc::SIGSTOP {
+        return Err(ProcessError::SignalError(format!(
+            "SIG{} cannot be caught or ignored",
+            name(sig).unwrap_or("?")
+        )));
+    }
+    match usize::try_from(sig) {
+        Ok(idx) if idx > 0 && idx < NSIG => Ok(idx),
+        _ => Err(ProcessError::SignalError(format!(
+            "invalid signal number: {}",
+            sig
+        ))),
+    }
+}
+
+fn install(sig: c_int, disposition: Disposition) -> Result<(), ProcessError> {
+    let handler = match disposition {
+        Disposition::Default => libc::SIG_DFL,
+        Disposition::Ignore => libc::SIG_IGN,
+        Disposition::Catch => record_signal as *const () as libc::sighandler_t,
+    };
+
+    // SAFETY: the action is fully initialised and the handler only touches atomics
+    let result = unsafe {
+        let mut action: libc::sigaction = std::mem::zeroed();
+        action.sa_sigaction = handler;
+        action.sa_flags = libc::SA_RESTART;
+        libc::sigemptyset(&mut action.sa_mask);
+        libc::sigaction(sig, &action, std::ptr::null_mut())
+    };
+
+    if result != 0 {
+        return Err(ProcessError::SignalError(
+            std::io::Error::last_os_error().to_string(),
+        ));
+    }
+
+    CURRENT[sig as usize].store(disposition.as_u8(), Ordering::SeqCst);
+    Ok(())
+}
+
+/// Installs the dispositions an interactive shell runs with: SIGINT is caught
+/// so Ctrl-C only interrupts the foreground command, SIGQUIT and SIGTERM are
+/// ignored. Children get the defaults back through [`prepare_child`].
+pub fn init_interactive() -> Result<(), ProcessError> {
+    set_base(libc::SIGINT, Disposition::Catch)?;
+    set_base(libc::SIGQUIT, Disposition::Ignore)?;
+    set_base(libc::SIGTERM, Disposition::Ignore)?;
+    Ok(())
+}
+
+fn set_base(sig: c_int, disposition: Disposition) -> Result<(), ProcessError> {
+    let idx = index(sig)?;
+    BASE[idx].store(disposition.as_u8(), Ordering::SeqCst);
+    if !USER_IGNORED[idx].load(Ordering::SeqCst) {
+        install(sig, disposition)?;
     }
     Ok(())
 }
+
+/// Catches `sig` so a trap handler can run at the next command boundary.
+pub fn catch(sig: c_int) -> Result<(), ProcessError> {
+    let idx = index(sig)?;
+    USER_IGNORED[idx].store(false, Ordering::SeqCst);
+    install(sig, Disposition::Catch)
+}
+
+/// Ignores `sig` in the shell and in every child it spawns (`trap '' SIG`).
+pub fn ignore(sig: c_int) -> Result<(), ProcessError> {
+    let idx = index(sig)?;
+    USER_IGNORED[idx].store(true, Ordering::SeqCst);
+    install(sig, Disposition::Ignore)
+}
+
+/// Drops any trap on `sig` and goes back to the shell's own disposition.
+pub fn restore(sig: c_int) -> Result<(), ProcessError> {
+    let idx = index(sig)?;
+    USER_IGNORED[idx].store(false, Ordering::SeqCst);
+    PENDING[idx].store(false, Ordering::SeqCst);
+    install(sig, Disposition::from_u8(BASE[idx].load(Ordering::SeqCst)))
+}
+
+pub fn disposition(sig: c_int) -> Disposition {
+    CURRENT
+        .get(sig as usize)
+        .map(|d| Disposition::from_u8(d.load(Ordering::SeqCst)))
+        .unwrap_or(Disposition::Default)
+}
+
+/// Returns and clears the signals received since the last call.
+pub fn take_pending() -> Vec<c_int> {
+    (1..NSIG)
+        .filter(|&idx| PENDING[idx].swap(false, Ordering::SeqCst))
+        .map(|idx| idx as c_int)
+        .collect()
+}
+
+/// Makes `command` start with default signal dispositions, except for the
+/// signals the user explicitly ignored with `trap '' SIG`.
+pub fn prepare_child(command: &mut Command) {
+    // SAFETY: only async-signal-safe calls (sigaction, sigprocmask) run after fork
+    unsafe {
+        command.pre_exec(|| {
+            for idx in 1..NSIG {
+                let current = Disposition::from_u8(CURRENT[idx].load(Ordering::SeqCst));
+                if current != Disposition::Default && !USER_IGNORED[idx].load(Ordering::SeqCst) {
+                    libc::signal(idx as c_int, libc::SIG_DFL);
+                }
+            }
+            let mut mask: libc::sigset_t = std::mem::zeroed();
+            libc::sigemptyset(&mut mask);
+            libc::sigprocmask(libc::SIG_SETMASK, &mask, std::ptr::null_mut());
+            Ok(())
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_signal_names() {
+        assert_eq!(from_name("INT"), Some(libc::SIGINT));
+        assert_eq!(from_name("sigterm"), Some(libc::SIGTERM));
+        assert_eq!(from_name("9"), Some(libc::SIGKILL));
+        assert_eq!(from_name("NOPE"), None);
+        assert_eq!(name(libc::SIGHUP), Some("HUP"));
+    }
+
+    #[test]
+    fn test_uncatchable_signals() {
+        assert!(catch(libc::SIGKILL).is_err());
+        assert!(ignore(libc::SIGSTOP).is_err());
+    }
+
+    #[test]
+    fn test_catch_records_pending() {
+        catch(libc::SIGUSR2).unwrap();
+        unsafe {
+            libc::raise(libc::SIGUSR2);
+        }
+        // Checked directly so concurrent tests draining `take_pending` don't race
+        assert!(PENDING[libc::SIGUSR2 as usize].swap(false, Ordering::SeqCst));
+        restore(libc::SIGUSR2).unwrap();
+        assert_eq!(disposition(libc::SIGUSR2), Disposition::Default);
+    }
+}
@@ -1,7 +1,7 @@
-use super::environment::EnvironmentHandler;
-use super::pipeline::Pipeline;
+use super::pipeline::{Pipeline, PipelineError};
+use crate::core::commands::TrapCondition;
 use crate::error::ShellError;
-use std::collections::HashMap;
+use crate::input::HistoryEntry;
 
 pub(crate) trait CommandHandler {
     fn execute_command(&mut self, command: &str) -> Result<(), ShellError>;
@@ -14,36 +14,62 @@ impl CommandHandler for super::Shell {
             return Ok(());
         }
 
-        // Record start time for duration tracking
-        let start_time = std::time::Instant::now();
-
-        // First expand environment variables in the command
-        let expanded_command = self.expand_env_vars(command);
+        // `set -n` reads a script's commands without running them, to check
+        // its syntax; an interactive shell ignores it
+        if !self.interactive && self.executor.options().noexec() {
+            return Pipeline::parse(command)
+                .map(|_| ())
+                .map_err(ShellError::PipelineError);
+        }
 
-        // Parse pipeline with the expanded command
-        let pipeline = Pipeline::parse(&expanded_command).map_err(ShellError::PipelineError)?;
+        // Record where and when it started for history
+        let cwd = self.current_dir.clone();
+        let start_time = std::time::Instant::now();
 
-        // Create environment variables HashMap with expanded values
-        let env_vars: HashMap<String, String> = std::env::vars()
-            .map(|(k, v)| (k, self.expand_env_vars(&v)))
-            .collect();
+        // Parse pipeline; words are expanded per stage when it runs. A line
+        // that doesn't parse is still recorded, to be fixed with Up
+        let pipeline = match Pipeline::parse(command) {
+            Ok(pipeline) => pipeline,
+            Err(e) => {
+                self.record_history(command, cwd, 1, 0);
+                return Err(ShellError::PipelineError(e));
+            }
+        };
 
         // Execute pipeline with shell context
-        let result =
-            pipeline.execute_with_context(&env_vars, &self.config.get_aliases(), &self.executor);
+        self.executor.run_trap(TrapCondition::Debug);
+        let result = pipeline.execute_with_context(&self.config.get_aliases(), &self.executor);
+        // The pipeline runs the ERR trap for commands that fail; a line that
+        // couldn't run at all fails as a whole
+        if let Err(e) = &result {
+            self.executor.set_last_status(1);
+            if !matches!(e, PipelineError::Exit(_)) {
+                self.executor.run_trap(TrapCondition::Err);
+            }
+        }
+        let exit_code = self.executor.last_status();
+        self.executor.run_pending_traps();
 
         // Calculate duration
-        let duration = start_time.elapsed().as_millis() as u64;
-
-        // Add to history with execution details
-        if let Err(e) = self.history.add_with_details(
-            command, // Use original command for history
-            result.is_err() as i32,
-            duration,
-        ) {
-            if !self.flags.is_set("quiet") {
-                eprintln!("Warning: Failed to add command to history: {}", e);
-            }
+        let elapsed = start_time.elapsed();
+        self.last_duration = Some(elapsed);
+        let duration = elapsed.as_millis() as u64;
+
+        if self.flags.is_set("debug") {
+            eprintln!(
+                "aorta: debug: '{}' exited {} in {}ms",
+                command.trim(),
+                exit_code,
+                duration
+            );
+        }
+
+        self.record_history(command, cwd, exit_code, duration);
+
+        // `exit` inside a pipeline or errexit ends the shell
+        if let Err(PipelineError::Exit(code)) = result {
+            self.executor.run_exit_trap();
+            std::process::exit(code & 0xff);
         }
 
         // Update current directory on success
@@ -57,3 +83,60 @@ impl CommandHandler for super::Shell {
         }
     }
 }
+
+/// Whether an error ends a script: a word that can't be expanded, such as
+/// an unbound variable under `set -u`, stops a non-interactive shell.
+pub(crate) fn ends_script(error: &ShellError) -> bool {
+    matches!(
+        error,
+        ShellError::PipelineError(PipelineError::Expansion(_))
+    )
+}
+
+impl super::Shell {
+    // Adds a command to the history with execution details; scripts don't
+    // record any
+    fn record_history(&mut self, command: &str, cwd: String, exit_code: i32, duration: u64) {
+        if !self.interactive {
+            return;
+        }
+        let entry =
+            HistoryEntry::new_command(command.to_string(), exit_code, duration).with_cwd(cwd);
+        if let Err(e) = self.history().add_entry(entry) {
+            if !self.flags.is_set("quiet") {
+                eprintln!("Warning: Failed to add command to history: {}", e);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::flags::Flags;
+    use crate::shell::Shell;
+
+    // A shell reading a script, as `aorta -s` does, without startup files
+    fn run_script(script: &str) -> i32 {
+        let mut flags = Flags::new();
+        for name in ["stdin", "norc", "noprofile", "quiet"] {
+            flags.set(name);
+        }
+        let mut shell = Shell::new(flags).unwrap();
+        shell.run_lines(script.as_bytes()).unwrap()
+    }
+
+    #[test]
+    fn test_noexec_script() {
+        let status = run_script("set -n\nexport AORTA_NOEXEC_RAN=yes\n");
+        assert_eq!(status, 0);
+        assert!(std::env::var("AORTA_NOEXEC_RAN").is_err());
+    }
+
+    #[test]
+    fn test_nounset_ends_script() {
+        let status = run_script("set -u\necho $AORTA_UNBOUND_X\nexport AORTA_UNBOUND_RAN=yes\n");
+        assert_eq!(status, 1);
+        assert!(std::env::var("AORTA_UNBOUND_RAN").is_err());
+        assert_eq!(run_script("echo $AORTA_UNBOUND_X\nfalse\ntrue\n"), 0);
+    }
+}
@@ -0,0 +1,342 @@
+use std::iter::Peekable;
+use std::str::Chars;
+
+use super::pipeline::PipelineError;
+use crate::core::options::ShellOptions;
+use crate::path::glob;
+
+/// Turns the raw text of a command into words: removes quoting and expands
+/// `~`, `$VAR`/`${VAR}`/`$?`/`$$` and unquoted pathname patterns.
+pub(crate) fn expand_words(
+    input: &str,
+    options: &ShellOptions,
+    last_status: i32,
+) -> Result<Vec<String>, PipelineError> {
+    let mut expander = WordExpander {
+        options,
+        last_status,
+        words: Vec::new(),
+        word: Word::default(),
+    };
+    expander.run(input)?;
+    Ok(expander.words)
+}
+
+/// `word` in single quotes, each `'` in it written `'\''`, so that
+/// [`expand_words`] reads it back as that one word.
+pub(crate) fn shell_quote(word: &str) -> String {
+    format!("'{}'", word.replace('\'', r"'\''"))
+}
+
+/// Expands `$VAR`, `${VAR}`, `$?` and `$$` in free text such as the message
+/// of the day, leaving everything else as written. `\$` gives a literal `$`.
+pub(crate) fn expand_text(input: &str, last_status: i32) -> String {
+    let mut output = String::with_capacity(input.len());
+    let mut chars = input.chars().peekable();
+
+    while let Some(c) = chars.next() {
+        match c {
+            '\\' if chars.peek() == Some(&'$') => output.extend(chars.next()),
+            '$' => match chars.peek().copied() {
+                Some('?') => {
+                    chars.next();
+                    output.push_str(&last_status.to_string());
+                }
+                Some('$') => {
+                    chars.next();
+                    output.push_str(&std::process::id().to_string());
+                }
+                Some('{') => {
+                    chars.next();
+                    // Without a closing brace the rest of the text is kept
+                    let rest: String = chars.clone().collect();
+                    match braced_name(&mut chars) {
+                        Ok(name) => output.push_str(&std::env::var(name).unwrap_or_default()),
+                        Err(_) => {
+                            output.push_str("${");
+                            output.push_str(&rest);
+                        }
+                    }
+                }
+                Some(c) if c.is_ascii_alphabetic() || c == '_' => {
+                    let name = plain_name(&mut chars);
+                    output.push_str(&std::env::var(name).unwrap_or_default());
+                }
+                _ => output.push('$'),
+            },
+            c => output.push(c),
+        }
+    }
+    output
+}
+
+#[derive(Default)]
+struct Word {
+    // The word as it will be passed on
+    text: String,
+    // The same word with quoted glob characters escaped, for matching
+    pattern: String,
+    started: bool,
+    globbing: bool,
+}
+
+impl Word {
+    fn push_literal(&mut self, c: char) {
+        if matches!(c, '*' | '?' | '[' | '\\') {
+            self.pattern.push('\\');
+        }
+        self.pattern.push(c);
+        self.text.push(c);
+        self.started = true;
+    }
+
+    fn push_str_literal(&mut self, s: &str) {
+        s.chars().for_each(|c| self.push_literal(c));
+    }
+
+    fn push_glob(&mut self, c: char) {
+        self.text.push(c);
+        self.pattern.push(c);
+        self.globbing = true;
+        self.started = true;
+    }
+}
+
+struct WordExpander<'a> {
+    options: &'a ShellOptions,
+    last_status: i32,
+    words: Vec<String>,
+    word: Word,
+}
+
+impl WordExpander<'_> {
+    fn run(&mut self, input: &str) -> Result<(), PipelineError> {
+        let mut chars = input.chars().peekable();
+
+        while let Some(c) = chars.next() {
+            match c {
+                c if c.is_whitespace() => self.finish_word(),
+                '\'' => self.single_quoted(&mut chars)?,
+                '"' => self.double_quoted(&mut chars)?,
+                '\\' => match chars.next() {
+                    Some(next) => self.word.push_literal(next),
+                    None => self.word.push_literal('\\'),
+                },
+                '$' => self.variable(&mut chars)?,
+                '~' if !self.word.started => self.tilde(&mut chars),
+                '*' | '?' | '[' => self.word.push_glob(c),
+                c => self.word.push_literal(c),
+            }
+        }
+
+        self.finish_word();
+        Ok(())
+    }
+
+    fn finish_word(&mut self) {
+        let word = std::mem::take(&mut self.word);
+        if !word.started {
+            return;
+        }
+
+        if word.globbing && !self.options.noglob() {
+            let matches = glob::expand(&word.pattern, self.options.is_shopt("dotglob"));
+            if !matches.is_empty() {
+                self.words.extend(matches);
+                return;
+            }
+            if self.options.is_shopt("nullglob") {
+                return;
+            }
+        }
+        self.words.push(word.text);
+    }
+
+    fn single_quoted(&mut self, chars: &mut Peekable<Chars<'_>>) -> Result<(), PipelineError> {
+        self.word.started = true;
+        for c in chars.by_ref() {
+            if c == '\'' {
+                return Ok(());
+            }
+            self.word.push_literal(c);
+        }
+        Err(PipelineError::Parse("Unterminated quote".to_string()))
+    }
+
+    fn double_quoted(&mut self, chars: &mut Peekable<Chars<'_>>) -> Result<(), PipelineError> {
+        self.word.started = true;
+        while let Some(c) = chars.next() {
+            match c {
+                '"' => return Ok(()),
+                '\\' => match chars.next() {
+                    Some(next @ ('"' | '\\' | '$' | '`')) => self.word.push_literal(next),
+                    Some(next) => {
+                        self.word.push_literal('\\');
+                        self.word.push_literal(next);
+                    }
+                    None => break,
+                },
+                '$' => self.variable(chars)?,
+                c => self.word.push_literal(c),
+            }
+        }
+        Err(PipelineError::Parse("Unterminated quote".to_string()))
+    }
+
+    fn tilde(&mut self, chars: &mut Peekable<Chars<'_>>) {
+        let at_boundary = chars.peek().is_none_or(|c| *c == '/' || c.is_whitespace());
+        match std::env::var("HOME") {
+            Ok(home) if at_boundary => self.word.push_str_literal(&home),
+            _ => self.word.push_literal('~'),
+        }
+    }
+
+    fn variable(&mut self, chars: &mut Peekable<Chars<'_>>) -> Result<(), PipelineError> {
+        let name = match chars.peek().copied() {
+            Some('?') => {
+                chars.next();
+                let status = self.last_status.to_string();
+                self.word.push_str_literal(&status);
+                return Ok(());
+            }
+            Some('$') => {
+                chars.next();
+                self.word.push_str_literal(&std::process::id().to_string());
+                return Ok(());
+            }
+            Some('-') => {
+                chars.next();
+                let flags = self.options.flags();
+                self.word.push_str_literal(&flags);
+                return Ok(());
+            }
+            Some('{') => {
+                chars.next();
+                braced_name(chars)?
+            }
+            Some(c) if c.is_ascii_alphabetic() || c == '_' => plain_name(chars),
+            _ => {
+                self.word.push_literal('$');
+                return Ok(());
+            }
+        };
+
+        match std::env::var(&name) {
+            Ok(value) => self.word.push_str_literal(&value),
+            Err(_) if self.options.nounset() => {
+                return Err(PipelineError::Expansion(format!(
+                    "{}: unbound variable",
+                    name
+                )))
+            }
+            // An unquoted empty expansion doesn't produce a word
+            Err(_) => {}
+        }
+        Ok(())
+    }
+}
+
+fn plain_name(chars: &mut Peekable<Chars<'_>>) -> String {
+    let mut name = String::new();
+    while let Some(&c) = chars.peek() {
+        if !(c.is_ascii_alphanumeric() || c == '_') {
+            break;
+        }
+        name.push(c);
+        chars.next();
+    }
+    name
+}
+
+fn braced_name(chars: &mut Peekable<Chars<'_>>) -> Result<String, PipelineError> {
+    let mut name = String::new();
+    for c in chars.by_ref() {
+        if c == '}' {
+            return Ok(name);
+        }
+        name.push(c);
+    }
+    Err(PipelineError::Expansion("bad substitution".to_string()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::env;
+
+    fn expand(input: &str) -> Vec<String> {
+        expand_words(input, &ShellOptions::new(), 0).unwrap()
+    }
+
+    #[test]
+    fn test_shell_quote_reads_back() {
+        for word in ["", "echo 'hi'", "$HOME *", "it's"] {
+            assert_eq!(expand(&shell_quote(word)), vec![word]);
+        }
+        assert_eq!(shell_quote("echo 'hi'"), r"'echo '\''hi'\'''");
+    }
+
+    #[test]
+    fn test_quotes_and_variables() {
+        env::set_var("AORTA_EXPAND_TEST", "a b");
+        assert_eq!(
+            expand("echo '$AORTA_EXPAND_TEST' \"[$AORTA_EXPAND_TEST]\" ${AORTA_EXPAND_TEST}x"),
+            vec!["echo", "$AORTA_EXPAND_TEST", "[a b]", "a bx"]
+        );
+    }
+
+    #[test]
+    fn test_empty_and_unset() {
+        assert_eq!(expand("printf '' $AORTA_UNSET_VAR_X"), vec!["printf", ""]);
+        assert_eq!(expand("echo \"$AORTA_UNSET_VAR_X\""), vec!["echo", ""]);
+    }
+
+    #[test]
+    fn test_nounset() {
+        let mut options = ShellOptions::new();
+        options.set("nounset", true).unwrap();
+        assert!(expand_words("echo $AORTA_UNSET_VAR_Y", &options, 0).is_err());
+        assert!(expand_words("echo $?", &options, 2).is_ok());
+    }
+
+    #[test]
+    fn test_status_and_tilde() {
+        let home = env::var("HOME").unwrap_or_default();
+        let words = expand_words("echo $? ~/x a~", &ShellOptions::new(), 3).unwrap();
+        assert_eq!(
+            words,
+            vec![
+                "echo".to_string(),
+                "3".into(),
+                format!("{}/x", home),
+                "a~".into()
+            ]
+        );
+    }
+
+    #[test]
+    fn test_expand_text() {
+        env::set_var("AORTA_TEXT_TEST", "world");
+        assert_eq!(
+            expand_text(
+                "Hello, $AORTA_TEXT_TEST! ${AORTA_TEXT_TEST}s \\$5 $? 'kept' *",
+                1
+            ),
+            "Hello, world! worlds $5 1 'kept' *"
+        );
+        assert_eq!(
+            expand_text("cost: $ ${unterminated", 0),
+            "cost: $ ${unterminated"
+        );
+    }
+
+    #[test]
+    fn test_noglob_keeps_pattern() {
+        let mut options = ShellOptions::new();
+        options.set("noglob", true).unwrap();
+        let words = expand_words("ls /*", &options, 0).unwrap();
+        assert_eq!(words, vec!["ls", "/*"]);
+        assert!(expand("ls /*").len() > 2);
+        assert_eq!(expand("ls '/*'"), vec!["ls", "/*"]);
+    }
+}
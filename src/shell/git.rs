@@ -0,0 +1,395 @@
+use std::collections::HashMap;
+use std::fs;
+use std::io::Read;
+use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::{Duration, Instant, SystemTime};
+
+// Work tree edits don't touch anything under .git, so entries also expire
+const CACHE_TTL: Duration = Duration::from_secs(2);
+
+/// What the prompt shows about the repository around the current directory.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub(crate) struct GitStatus {
+    // The branch name, or the abbreviated commit when HEAD is detached
+    pub branch: String,
+    pub detached: bool,
+    pub staged: usize,
+    pub modified: usize,
+    pub untracked: usize,
+    pub conflicts: usize,
+    pub ahead: usize,
+    pub behind: usize,
+    // An operation in progress, like "rebase" or "merge"
+    pub state: Option<&'static str>,
+}
+
+impl GitStatus {
+    /// The compact form used by `\g`, like `main +1 ~2 ?3 ↑1 ↓2 (rebase)`.
+    pub fn summary(&self) -> String {
+        let mut summary = self.branch.clone();
+        let counts = [
+            ('+', self.staged),
+            ('~', self.modified),
+            ('!', self.conflicts),
+            ('?', self.untracked),
+            ('↑', self.ahead),
+            ('↓', self.behind),
+        ];
+        for (symbol, count) in counts {
+            if count > 0 {
+                summary.push_str(&format!(" {}{}", symbol, count));
+            }
+        }
+        if let Some(state) = self.state {
+            summary.push_str(&format!(" ({})", state));
+        }
+        summary
+    }
+
+    /// A single field for `\g{name}`.
+    pub fn field(&self, name: &str) -> Option<String> {
+        let count = match name {
+            "branch" => return Some(self.branch.clone()),
+            "state" => return Some(self.state.unwrap_or_default().to_string()),
+            "staged" => self.staged,
+            "modified" => self.modified,
+            "untracked" => self.untracked,
+            "conflicts" => self.conflicts,
+            "ahead" => self.ahead,
+            "behind" => self.behind,
+            _ => return None,
+        };
+        Some(count.to_string())
+    }
+}
+
+/// Git statuses per directory, shared so they can be filled from another
+/// thread. An entry is reused until HEAD or the index changes or it ages.
+#[derive(Debug, Clone, Default)]
+pub(crate) struct GitCache {
+    entries: Arc<Mutex<HashMap<PathBuf, CacheEntry>>>,
+}
+
+#[derive(Debug, Clone)]
+struct CacheEntry {
+    fingerprint: Fingerprint,
+    read_at: Instant,
+    status: GitStatus,
+}
+
+type Fingerprint = (Option<SystemTime>, Option<SystemTime>);
+
+impl GitCache {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// The status for `dir`, running `git status` for the counts when
+    /// `timeout` allows; past it only what `.git` itself says is shown.
+    pub fn status(&self, dir: &Path, timeout: Duration) -> Option<GitStatus> {
+        let repo = Repo::find(dir)?;
+        let fingerprint = repo.fingerprint();
+        if let Some(status) = self.fresh(dir, &fingerprint) {
+            return Some(status);
+        }
+
+        let status = repo.status(timeout);
+        if let Ok(mut entries) = self.entries.lock() {
+            entries.insert(
+                dir.to_path_buf(),
+                CacheEntry {
+                    fingerprint,
+                    read_at: Instant::now(),
+                    status: status.clone(),
+                },
+            );
+        }
+        Some(status)
+    }
+
+    /// The cached status for `dir` while it's still current; never runs git.
+    pub fn cached(&self, dir: &Path) -> Option<GitStatus> {
+        let repo = Repo::find(dir)?;
+        self.fresh(dir, &repo.fingerprint())
+    }
+
+    /// What can be shown for `dir` without running git: the branch and
+    /// state from `.git`, with the counts from the last status read, if any.
+    pub fn placeholder(&self, dir: &Path) -> Option<GitStatus> {
+        let repo = Repo::find(dir)?;
+        let quick = repo.status(Duration::ZERO);
+        Some(match self.lookup(dir) {
+            Some(entry) => GitStatus {
+                branch: quick.branch,
+                detached: quick.detached,
+                state: quick.state,
+                ..entry.status
+            },
+            None => quick,
+        })
+    }
+
+    fn fresh(&self, dir: &Path, fingerprint: &Fingerprint) -> Option<GitStatus> {
+        let entry = self.lookup(dir)?;
+        (entry.fingerprint == *fingerprint && entry.read_at.elapsed() < CACHE_TTL)
+            .then_some(entry.status)
+    }
+
+    fn lookup(&self, dir: &Path) -> Option<CacheEntry> {
+        self.entries.lock().ok()?.get(dir).cloned()
+    }
+}
+
+struct Repo {
+    work_tree: PathBuf,
+    git_dir: PathBuf,
+}
+
+impl Repo {
+    /// Finds the repository containing `dir`. `.git` may be a file pointing
+    /// elsewhere, as in worktrees and submodules.
+    fn find(dir: &Path) -> Option<Self> {
+        let mut current = Some(dir);
+        while let Some(path) = current {
+            let dot_git = path.join(".git");
+            if dot_git.is_dir() {
+                return Some(Self {
+                    work_tree: path.to_path_buf(),
+                    git_dir: dot_git,
+                });
+            }
+            if let Ok(text) = fs::read_to_string(&dot_git) {
+                let target = text.trim().strip_prefix("gitdir:")?.trim();
+                return Some(Self {
+                    work_tree: path.to_path_buf(),
+                    git_dir: path.join(target),
+                });
+            }
+            current = path.parent();
+        }
+        None
+    }
+
+    fn fingerprint(&self) -> Fingerprint {
+        let modified = |name: &str| {
+            fs::metadata(self.git_dir.join(name))
+                .and_then(|meta| meta.modified())
+                .ok()
+        };
+        (modified("HEAD"), modified("index"))
+    }
+
+    fn status(&self, timeout: Duration) -> GitStatus {
+        let mut status = GitStatus {
+            state: self.state(),
+            ..Default::default()
+        };
+        self.read_head(&mut status);
+
+        if !timeout.is_zero() {
+            if let Some(output) = run_status(&self.work_tree, timeout) {
+                parse_porcelain(&output, &mut status);
+            }
+        }
+        status
+    }
+
+    fn read_head(&self, status: &mut GitStatus) {
+        let head = fs::read_to_string(self.git_dir.join("HEAD")).unwrap_or_default();
+        let head = head.trim();
+        match head.strip_prefix("ref: ") {
+            Some(reference) => {
+                status.branch = reference
+                    .strip_prefix("refs/heads/")
+                    .unwrap_or(reference)
+                    .to_string();
+            }
+            None => {
+                status.branch = head.chars().take(7).collect();
+                status.detached = true;
+            }
+        }
+    }
+
+    fn state(&self) -> Option<&'static str> {
+        let markers = [
+            ("rebase-merge", "rebase"),
+            ("rebase-apply", "rebase"),
+            ("MERGE_HEAD", "merge"),
+            ("CHERRY_PICK_HEAD", "cherry-pick"),
+            ("REVERT_HEAD", "revert"),
+            ("BISECT_LOG", "bisect"),
+        ];
+        markers
+            .into_iter()
+            .find(|(marker, _)| self.git_dir.join(marker).exists())
+            .map(|(_, state)| state)
+    }
+}
+
+// Runs `git status`, killing it if it outlives the timeout
+fn run_status(work_tree: &Path, timeout: Duration) -> Option<String> {
+    let mut child = Command::new("git")
+        .args(["status", "--porcelain=v2", "--branch"])
+        .current_dir(work_tree)
+        // Don't take the index lock, which would race with the user's git
+        .env("GIT_OPTIONAL_LOCKS", "0")
+        .stdin(Stdio::null())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::null())
+        .spawn()
+        .ok()?;
+
+    let mut stdout = child.stdout.take()?;
+    let reader = thread::spawn(move || {
+        let mut output = String::new();
+        stdout.read_to_string(&mut output).ok().map(|_| output)
+    });
+
+    let deadline = Instant::now() + timeout;
+    loop {
+        match child.try_wait() {
+            Ok(Some(exit)) if exit.success() => return reader.join().ok().flatten(),
+            Ok(Some(_)) => return None,
+            Ok(None) if Instant::now() < deadline => thread::sleep(Duration::from_millis(5)),
+            _ => {
+                let _ = child.kill();
+                let _ = child.wait();
+                return None;
+            }
+        }
+    }
+}
+
+fn parse_porcelain(output: &str, status: &mut GitStatus) {
+    for line in output.lines() {
+        if let Some(header) = line.strip_prefix("# ") {
+            parse_header(header, status);
+            continue;
+        }
+        let mut fields = line.split(' ');
+        match (fields.next(), fields.next()) {
+            (Some("1" | "2"), Some(xy)) => {
+                let mut xy = xy.chars();
+                status.staged += usize::from(xy.next().is_some_and(|x| x != '.'));
+                status.modified += usize::from(xy.next().is_some_and(|y| y != '.'));
+            }
+            (Some("u"), _) => status.conflicts += 1,
+            (Some("?"), _) => status.untracked += 1,
+            _ => {}
+        }
+    }
+}
+
+fn parse_header(header: &str, status: &mut GitStatus) {
+    if let Some(counts) = header.strip_prefix("branch.ab ") {
+        for count in counts.split(' ') {
+            if let Some(ahead) = count.strip_prefix('+') {
+                status.ahead = ahead.parse().unwrap_or(0);
+            } else if let Some(behind) = count.strip_prefix('-') {
+                status.behind = behind.parse().unwrap_or(0);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const PORCELAIN: &str = "\
+# branch.oid 1234567890abcdef
+# branch.head main
+# branch.upstream origin/main
+# branch.ab +2 -1
+1 M. N... 100644 100644 100644 aaa bbb src/lib.rs
+1 .M N... 100644 100644 100644 aaa bbb src/main.rs
+1 MM N... 100644 100644 100644 aaa bbb README.md
+2 R. N... 100644 100644 100644 aaa bbb R100 new.rs\told.rs
+u UU N... 100644 100644 100644 100644 aaa bbb ccc conflict.rs
+? notes.txt
+? scratch/
+";
+
+    #[test]
+    fn test_parse_porcelain() {
+        let mut status = GitStatus::default();
+        parse_porcelain(PORCELAIN, &mut status);
+        assert_eq!((status.staged, status.modified), (3, 2));
+        assert_eq!((status.conflicts, status.untracked), (1, 2));
+        assert_eq!((status.ahead, status.behind), (2, 1));
+    }
+
+    #[test]
+    fn test_summary_and_fields() {
+        let status = GitStatus {
+            branch: "main".into(),
+            staged: 1,
+            untracked: 3,
+            behind: 2,
+            state: Some("rebase"),
+            ..Default::default()
+        };
+        assert_eq!(status.summary(), "main +1 ?3 ↓2 (rebase)");
+        assert_eq!(status.field("untracked").as_deref(), Some("3"));
+        assert_eq!(status.field("ahead").as_deref(), Some("0"));
+        assert_eq!(status.field("nonsense"), None);
+    }
+
+    #[test]
+    fn test_reads_git_dir_directly() {
+        let root = std::env::temp_dir().join("aorta_git_prompt_test");
+        let git_dir = root.join(".git");
+        let nested = root.join("src").join("deep");
+        fs::create_dir_all(&nested).unwrap();
+        fs::create_dir_all(git_dir.join("rebase-merge")).unwrap();
+        fs::write(git_dir.join("HEAD"), "ref: refs/heads/feature/x\n").unwrap();
+
+        let cache = GitCache::new();
+        assert_eq!(cache.cached(&nested), None);
+        let status = cache.status(&nested, Duration::ZERO).unwrap();
+        assert_eq!(status.branch, "feature/x");
+        assert_eq!(status.state, Some("rebase"));
+        assert_eq!(cache.cached(&nested), Some(status));
+
+        fs::write(git_dir.join("HEAD"), "0123456789abcdef\n").unwrap();
+        let status = Repo::find(&nested).unwrap().status(Duration::ZERO);
+        assert!(status.detached);
+        assert_eq!(status.branch, "0123456");
+        fs::remove_dir_all(root).unwrap();
+    }
+
+    #[test]
+    fn test_placeholder_keeps_old_counts() {
+        let root = std::env::temp_dir().join("aorta_git_placeholder_test");
+        fs::create_dir_all(root.join(".git")).unwrap();
+        fs::write(root.join(".git").join("HEAD"), "ref: refs/heads/main\n").unwrap();
+
+        let cache = GitCache::new();
+        assert_eq!(cache.placeholder(&root).unwrap().branch, "main");
+
+        let old = GitStatus {
+            branch: "old".into(),
+            modified: 4,
+            ..Default::default()
+        };
+        cache.entries.lock().unwrap().insert(
+            root.clone(),
+            CacheEntry {
+                fingerprint: (None, None),
+                read_at: Instant::now(),
+                status: old,
+            },
+        );
+        let placeholder = cache.placeholder(&root).unwrap();
+        assert_eq!(
+            (placeholder.branch.as_str(), placeholder.modified),
+            ("main", 4)
+        );
+        assert_eq!(cache.cached(&root), None);
+        fs::remove_dir_all(root).unwrap();
+    }
+}
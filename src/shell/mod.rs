@@ -1,43 +1,97 @@
-use rustyline::{config::Configurer, history::FileHistory, Editor};
+use rustyline::{
+    config::Configurer, Config as EditorConfig, Editor, EventHandler, KeyCode, KeyEvent, Modifiers,
+};
 use std::env;
-use std::io::{self, Write};
+use std::io::{self, BufRead, IsTerminal, Write};
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex, MutexGuard};
+use std::time::Duration;
 
-mod environment;
 mod executor;
+pub(crate) mod expand;
+mod git;
+mod motd;
 pub(crate) mod pipeline;
+mod prompt;
+mod redirect;
+mod refresh;
 
 use crate::{
-    core::{commands::CommandExecutor, config::Config},
+    core::{
+        commands::CommandExecutor,
+        config::{Config, Settings},
+    },
     error::ShellError,
     flags::Flags,
-    input::{History, HistoryEntry, ShellCompleter},
+    input::{
+        history, AcceptSuggestion, EditorHistory, ExpandAbbreviation, History, HistoryEntry,
+        HistoryFilter, HistoryFinder, ShellCompleter,
+    },
+    process::signal,
 };
 
 use executor::CommandHandler;
+use git::{GitCache, GitStatus};
+use pipeline::{Continuation, Pipeline};
+use prompt::{PromptContext, Templates};
+use refresh::PromptRefresh;
 
 pub struct Shell {
-    pub(crate) editor: Editor<ShellCompleter, FileHistory>,
+    pub(crate) editor: Editor<ShellCompleter, EditorHistory>,
     pub(crate) current_dir: String,
     pub(crate) config: Config,
     pub(crate) completer: ShellCompleter,
-    pub(crate) history: History,
+    // Shared with the completer, which suggests commands from it
+    pub(crate) history: Arc<Mutex<History>>,
     pub(crate) flags: Flags,
     pub(crate) executor: CommandExecutor,
+    // Reading commands from a terminal rather than a script on stdin
+    pub(crate) interactive: bool,
+    pub(crate) git: GitCache,
+    // How long the last command ran, for the right prompt
+    pub(crate) last_duration: Option<Duration>,
+    // Redraws prompts with slow segments; only on a terminal
+    pub(crate) refresh: Option<PromptRefresh>,
+    // An expanded command to edit at the next prompt, with history.verify
+    pub(crate) pending_input: Option<String>,
 }
 
 impl Shell {
-    pub fn new(flags: Flags) -> Result<Self, ShellError> {
-        let completer = ShellCompleter::new();
-        let mut editor = Editor::<ShellCompleter, FileHistory>::new()?;
+    pub fn new(mut flags: Flags) -> Result<Self, ShellError> {
+        let interactive = Self::detect_mode(&mut flags);
+
+        // The executor holds the history; the editor recalls from it too
+        let executor = CommandExecutor::new(&flags)?;
+        let history = executor.history();
+        let mut completer = ShellCompleter::new();
+        completer.set_history(Arc::clone(&history));
+        completer.set_specs(executor.completions());
+        let mut editor = Editor::with_history(
+            EditorConfig::default(),
+            EditorHistory::new(Arc::clone(&history)),
+        )?;
+        let refresh = if interactive {
+            editor
+                .create_external_printer()
+                .ok()
+                .map(|printer| PromptRefresh::new(Box::new(printer), completer.clone()))
+        } else {
+            None
+        };
 
         editor.set_helper(Some(completer.clone()));
-        editor.set_auto_add_history(true);
 
         let current_dir = env::current_dir()?.to_string_lossy().to_string();
 
-        // Load config and executor
-        let executor = CommandExecutor::new(&flags)?;
-        let mut config = Config::new()?.with_executor(executor);
+        // Catch SIGINT and ignore SIGQUIT/SIGTERM before any startup file runs
+        if interactive {
+            signal::init_interactive()?;
+        }
+
+        // Load config; it shares traps and shell state with the executor
+        let mut config = Config::new()?
+            .with_flags(&flags)
+            .with_executor(executor.clone());
         config.load()?;
 
         // After loading config, update the current process environment
@@ -45,31 +99,7 @@ impl Shell {
             env::set_var("PATH", path.clone());
         }
 
-        // Set up history
-        let history_file = dirs::home_dir()
-            .ok_or(ShellError::HomeDirNotFound)?
-            .join(".aorta_history");
-        let history = History::new(history_file.clone(), 1000)?;
-
-        // Load history entries into editor
-        for entry in history.get_recent(1000).into_iter().rev() {
-            if let HistoryEntry::Command { command, .. } = entry {
-                if let Err(e) = editor.add_history_entry(command.as_ref()) {
-                    if !flags.is_set("quiet") {
-                        eprintln!("Warning: Failed to add history entry: {}", e);
-                    }
-                }
-            }
-        }
-
-        // Set up ctrl-c handler
-        ctrlc::set_handler(move || {
-            // println!("\nUse 'exit' to exit the shell");
-        })?;
-
-        let executor = CommandExecutor::new(&flags)?;
-
-        Ok(Shell {
+        let mut shell = Shell {
             editor,
             current_dir,
             config,
@@ -77,25 +107,369 @@ impl Shell {
             history,
             flags,
             executor,
-        })
+            interactive,
+            git: GitCache::new(),
+            last_duration: None,
+            refresh,
+            pending_input: None,
+        };
+        shell.apply_settings(None)?;
+        Ok(shell)
+    }
+
+    fn history_path(settings: &Settings) -> Result<PathBuf, ShellError> {
+        match &settings.history.path {
+            Some(path) => Ok(path.clone()),
+            None => Ok(dirs::home_dir()
+                .ok_or(ShellError::HomeDirNotFound)?
+                .join(".aorta_history")),
+        }
+    }
+
+    /// Applies `aorta.toml` to the editor, completer and history. On reload
+    /// `previous` holds the settings being replaced.
+    fn apply_settings(&mut self, previous: Option<&Settings>) -> Result<(), ShellError> {
+        let settings = self.config.settings().clone();
+        self.editor.set_edit_mode(settings.edit_mode);
+        self.editor.set_completion_type(settings.completion.mode);
+        self.editor
+            .set_completion_prompt_limit(settings.completion.max_items);
+
+        for binding in previous.map_or(&[][..], |p| &p.keybindings) {
+            self.editor.unbind_sequence(binding.key);
+        }
+        // Right takes a suggestion already, and Ctrl-R opens the fuzzy
+        // finder; the settings can rebind all of these below
+        let accept = [
+            (KeyEvent::ctrl('f'), AcceptSuggestion::whole()),
+            (KeyEvent::alt('f'), AcceptSuggestion::word()),
+        ];
+        for (key, handler) in accept {
+            self.editor
+                .bind_sequence(key, EventHandler::Conditional(Box::new(handler)));
+        }
+        let finder = HistoryFinder::new(Arc::clone(&self.history));
+        self.editor.bind_sequence(
+            KeyEvent::ctrl('r'),
+            EventHandler::Conditional(Box::new(finder)),
+        );
+        self.apply_abbreviations(&settings, previous);
+        for binding in &settings.keybindings {
+            self.editor
+                .bind_sequence(binding.key, binding.action.clone());
+        }
+
+        self.completer.set_colors(settings.colors);
+        self.editor.set_helper(Some(self.completer.clone()));
+
+        // Move the history where the settings put it
+        let path = Self::history_path(&settings)?;
+        let size = settings.history.size;
+        if &path == self.history().path() {
+            self.history().set_max_entries(size);
+        } else {
+            *self.history() = History::new(path, size)?;
+        }
+        Ok(())
+    }
+
+    // Puts the abbreviations from the settings alongside those made with
+    // `abbr`, dropping any the previous settings had, and expands them as
+    // Space is typed
+    fn apply_abbreviations(&mut self, settings: &Settings, previous: Option<&Settings>) {
+        let abbreviations = self.executor.abbreviations();
+        if let Ok(mut abbreviations) = abbreviations.lock() {
+            for abbreviation in previous.map_or(&[][..], |p| &p.abbreviations) {
+                abbreviations.remove(&abbreviation.name);
+            }
+            for abbreviation in &settings.abbreviations {
+                abbreviations.add(abbreviation.clone());
+            }
+        }
+        let expand = ExpandAbbreviation::new(abbreviations);
+        self.completer.set_abbreviations(expand.clone());
+        self.editor.bind_sequence(
+            KeyEvent(KeyCode::Char(' '), Modifiers::NONE),
+            EventHandler::Conditional(Box::new(expand)),
+        );
+    }
+
+    // Applies HISTCONTROL and HISTIGNORE, or the settings in their place,
+    // and with a shared history brings in commands other sessions recorded
+    fn refresh_history(&mut self) {
+        let filter = self.history_filter();
+        self.history().set_filter(filter);
+        if !self.config.settings().history.shared {
+            return;
+        }
+        let reload = self.history().reload();
+        if let Err(e) = reload {
+            if !self.flags.is_set("quiet") {
+                eprintln!("Warning: Couldn't reload history: {}", e);
+            }
+        }
+    }
+
+    // Lets the completer, and the editor's copy of it, suggest the aliases
+    // defined so far
+    fn refresh_aliases(&mut self) {
+        let aliases = self.config.get_aliases();
+        if let Some(helper) = self.editor.helper_mut() {
+            helper.update_aliases(aliases.clone());
+        }
+        self.completer.update_aliases(aliases);
+    }
+
+    fn history_filter(&self) -> HistoryFilter {
+        let settings = &self.config.settings().history;
+        let control = env::var("HISTCONTROL").ok();
+        let control: Vec<&str> = match &control {
+            Some(control) => control.split(':').collect(),
+            None => settings.control.iter().map(String::as_str).collect(),
+        };
+        let ignore = match env::var("HISTIGNORE") {
+            Ok(ignore) => ignore
+                .split(':')
+                .filter(|pattern| !pattern.is_empty())
+                .map(String::from)
+                .collect(),
+            Err(_) => settings.ignore.clone(),
+        };
+        HistoryFilter::default()
+            .with_control(control)
+            .with_ignore(ignore)
+            .with_redaction(settings.redact_secrets)
+    }
+
+    pub(crate) fn history(&self) -> MutexGuard<'_, History> {
+        // A panic mid-update leaves at worst a missing entry, so keep going
+        self.history.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    // Picks up edits to aorta.toml made since the last prompt
+    fn reload_settings(&mut self) {
+        let previous = self.config.settings().clone();
+        let result = match self.config.reload_settings() {
+            Ok(true) => self.apply_settings(Some(&previous)),
+            Ok(false) => Ok(()),
+            Err(e) => Err(e.into()),
+        };
+        if let Err(e) = result {
+            eprintln!("aorta: {}", e);
+        }
+    }
+
+    // PS1 and PS2 come from the environment, then the [prompt] settings.
+    // RPROMPT too, except that neither being set leaves the built-in one
+    fn prompt_templates(&self, continuation: bool) -> Templates {
+        let settings = self.config.settings();
+        let (name, setting, default) = if continuation {
+            ("PS2", &settings.prompt.continuation, "> ")
+        } else {
+            ("PS1", &settings.prompt.format, "\\w > ")
+        };
+        Templates {
+            left: env::var(name)
+                .ok()
+                .or_else(|| setting.clone())
+                .unwrap_or_else(|| default.to_string()),
+            right: env::var("RPROMPT")
+                .ok()
+                .or_else(|| settings.prompt.right.clone()),
+            continuation,
+            colors: settings.colors,
+        }
+    }
+
+    // Renders a prompt and returns the visible text for the editor to measure
+    fn prompt(&mut self, continuation: bool) -> String {
+        let templates = self.prompt_templates(continuation);
+        let (git, pending) = self.git_status(&templates);
+        let threshold = Duration::from_millis(self.config.settings().prompt.duration_threshold_ms);
+        let context = PromptContext {
+            cwd: self.current_dir.clone(),
+            last_status: self.executor.last_status(),
+            jobs: self.executor.job_count(),
+            duration: self.last_duration.filter(|duration| *duration >= threshold),
+            git,
+        };
+        let (prompt, right) = templates.render(&context);
+        let generation = self.completer.set_prompt(&prompt.visible, &prompt.text);
+        self.completer
+            .set_right_prompt(&right.visible, &right.text, &prompt.visible);
+
+        if let (true, Some(refresh)) = (pending, &self.refresh) {
+            let timeout = Duration::from_millis(self.config.settings().git.timeout_ms);
+            refresh.git_status(generation, templates, context, self.git.clone(), timeout);
+        }
+        prompt.visible
+    }
+
+    // The status for templates that show it. Unless it's cached, a prompt
+    // that can be redrawn gets a placeholder now and is refreshed once
+    // `git status` is done, which the returned flag asks for.
+    fn git_status(&self, templates: &Templates) -> (Option<GitStatus>, bool) {
+        let settings = &self.config.settings().git;
+        if !settings.enabled || !templates.uses_git() {
+            return (None, false);
+        }
+        let dir = Path::new(&self.current_dir);
+        if !settings.async_status || self.refresh.is_none() {
+            let timeout = Duration::from_millis(settings.timeout_ms);
+            return (self.git.status(dir, timeout), false);
+        }
+        match self.git.cached(dir) {
+            Some(status) => (Some(status), false),
+            None => {
+                let placeholder = self.git.placeholder(dir);
+                let pending = placeholder.is_some();
+                (placeholder, pending)
+            }
+        }
+    }
+
+    // `PROMPT_COMMAND` and the precmd setting run before each prompt; `$?`
+    // still shows the status of the user's last command afterwards
+    fn run_precmd(&mut self) {
+        let status = self.executor.last_status();
+        let commands = [
+            env::var("PROMPT_COMMAND").ok(),
+            self.config.settings().prompt.precmd.clone(),
+        ];
+        for command in commands.into_iter().flatten() {
+            if let Err(e) = self.executor.execute_line(&command) {
+                eprintln!("aorta: {}", e);
+            }
+        }
+        self.executor.set_last_status(status);
+    }
+
+    // Reads a line at the prompt, starting from an expansion to verify
+    fn read_line(&mut self, prompt: &str) -> rustyline::Result<String> {
+        let line = match self.pending_input.take() {
+            Some(input) => self.editor.readline_with_initial(prompt, (&input, "")),
+            None => self.editor.readline(prompt),
+        };
+        self.completer.end_prompt();
+        line.map(|line| self.expand_abbreviation(line))
+    }
+
+    // Enter expands an abbreviation still being typed at the end of the
+    // line, redrawn where the line was so the screen shows what's run
+    fn expand_abbreviation(&self, line: String) -> String {
+        let abbreviations = self.executor.abbreviations();
+        let expanded = match abbreviations.lock() {
+            Ok(abbreviations) => abbreviations.expand_last(&line).into_owned(),
+            Err(_) => return line,
+        };
+        if expanded != line {
+            match self.completer.redraw_accepted(&line, &expanded) {
+                Some(redraw) => println!("{}", redraw),
+                None => println!("{}", expanded),
+            }
+        }
+        expanded
+    }
+
+    // `!!`, `^old^new` and friends; None when there's nothing to run, after
+    // an error, for `:p`, or with the expansion going back to the editor
+    fn expand_history(&mut self, line: String) -> Option<String> {
+        let settings = &self.config.settings().history;
+        let verify = settings.verify;
+        if !settings.expand || !(line.contains('!') || line.starts_with('^')) {
+            return Some(line);
+        }
+        let expansion = history::expand(&line, &self.history());
+        let expansion = match expansion {
+            Ok(expansion) if expansion.expanded => expansion,
+            Ok(_) => return Some(line),
+            Err(e) => {
+                eprintln!("aorta: {}", e);
+                return None;
+            }
+        };
+
+        if expansion.print_only {
+            println!("{}", expansion.line);
+            let entry = HistoryEntry::new_command(expansion.line, 0, 0).with_cwd(&self.current_dir);
+            let _ = self.history().add_entry(entry);
+            return None;
+        }
+        if verify {
+            self.pending_input = Some(expansion.line);
+            return None;
+        }
+        // Show what's about to run, as csh and bash do
+        println!("{}", expansion.line);
+        Some(expansion.line)
+    }
+
+    // Reads more lines with PS2 until the command is complete; gives up on
+    // Ctrl-C or Ctrl-D like the first line would
+    fn read_continuation(&mut self, mut line: String) -> Option<String> {
+        while let Some(kind) = Pipeline::continuation(&line) {
+            let prompt = self.prompt(true);
+            let next = self.editor.readline(&prompt);
+            self.completer.end_prompt();
+            let next = next.ok()?;
+            join_continuation(&mut line, kind, &next);
+        }
+        Some(line)
+    }
+
+    // `-s` forces reading stdin; otherwise a terminal means interactive
+    fn detect_mode(flags: &mut Flags) -> bool {
+        if !flags.is_set("stdin") && io::stdin().is_terminal() {
+            flags.set("interactive");
+        }
+        let interactive = flags.is_set("interactive");
+        if flags.is_set("debug") {
+            eprintln!(
+                "aorta: debug: starting {} {} shell",
+                if flags.is_set("login") {
+                    "login"
+                } else {
+                    "non-login"
+                },
+                if interactive {
+                    "interactive"
+                } else {
+                    "non-interactive"
+                }
+            );
+        }
+        interactive
     }
 
     pub fn run(&mut self) -> Result<(), ShellError> {
+        if !self.interactive {
+            return self.run_script();
+        }
+
         self.register_as_shell()?;
+        if !self.flags.is_set("quiet") {
+            motd::show(&self.config.settings().motd, &self.executor);
+        }
         self.completer.refresh_commands();
-        self.completer.update_aliases(self.config.get_aliases());
 
         // Implement the command loop here instead of calling run_command_loop
         loop {
-            let prompt = format!("{} > ", self.current_dir);
-            match self.editor.readline(&prompt) {
+            self.executor.run_pending_traps();
+            self.report_jobs();
+            self.reload_settings();
+            self.refresh_history();
+            self.refresh_aliases();
+            self.run_precmd();
+            let prompt = self.prompt(false);
+            let line = self.read_line(&prompt);
+            match line {
                 Ok(line) => {
-                    if let Err(e) = self.editor.add_history_entry(line.as_str()) {
-                        if !self.flags.is_set("quiet") {
-                            eprintln!("Warning: Couldn't add to history: {}", e);
-                        }
-                    }
-
+                    let Some(line) = self
+                        .read_continuation(line)
+                        .and_then(|line| self.expand_history(line))
+                    else {
+                        continue;
+                    };
                     if let Err(e) = self.execute_command(&line) {
                         if !self.flags.is_set("quiet") {
                             eprintln!("{}", e);
@@ -112,6 +486,7 @@ impl Shell {
                     if !self.flags.is_set("quiet") {
                         println!("CTRL-D");
                     }
+                    self.executor.run_exit_trap();
                     break;
                 }
                 Err(e) => {
@@ -125,6 +500,53 @@ impl Shell {
         Ok(())
     }
 
+    // Runs the commands read from stdin and exits with the last status
+    fn run_script(&mut self) -> Result<(), ShellError> {
+        let status = self.run_lines(io::stdin().lock())?;
+        self.executor.run_exit_trap();
+        std::process::exit(status & 0xff);
+    }
+
+    // Runs the commands read from `input` until it ends or one fails in a
+    // way that ends the script, and returns the status to exit with
+    fn run_lines(&mut self, input: impl BufRead) -> Result<i32, ShellError> {
+        let mut command = String::new();
+        let mut pending = None;
+        for line in input.lines() {
+            let line = line.map_err(|e| ShellError::IoError(e.to_string()))?;
+            match pending {
+                Some(kind) => join_continuation(&mut command, kind, &line),
+                None => command = line,
+            }
+            pending = Pipeline::continuation(&command);
+            if pending.is_some() {
+                continue;
+            }
+            if let Err(e) = self.execute_command(&command) {
+                eprintln!("aorta: {}", e);
+                if executor::ends_script(&e) {
+                    return Ok(self.executor.last_status());
+                }
+            }
+        }
+        if pending.is_some() {
+            eprintln!("aorta: syntax error: unexpected end of file");
+            self.executor.set_last_status(2);
+        }
+        Ok(self.executor.last_status())
+    }
+
+    // Announces background jobs that finished since the last prompt
+    fn report_jobs(&self) {
+        for job in self.executor.reap_jobs() {
+            let state = match job.status() {
+                Some(0) | None => "Done".to_string(),
+                Some(code) => format!("Exit {}", code),
+            };
+            eprintln!("[{}]  {}\t{}", job.id, state, job.command);
+        }
+    }
+
     fn register_as_shell(&self) -> Result<(), ShellError> {
         let current_exe = env::current_exe().map_err(|e| ShellError::PathError(e.to_string()))?;
         let shell_path = current_exe.to_string_lossy();
@@ -194,3 +616,16 @@ impl Shell {
         Ok(())
     }
 }
+
+// Joins a continuation line the way the shell reads it: quoted newlines
+// are kept and a trailing backslash disappears with its newline
+fn join_continuation(line: &mut String, kind: Continuation, next: &str) {
+    match kind {
+        Continuation::Quote => line.push('\n'),
+        Continuation::Escape => {
+            line.pop();
+        }
+        Continuation::Operator => line.push(' '),
+    }
+    line.push_str(next);
+}
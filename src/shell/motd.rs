@@ -0,0 +1,121 @@
+use std::fs;
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use super::expand::expand_text;
+use crate::core::{commands::CommandExecutor, config::MotdSettings, env::EnvPaths};
+use crate::sys::format_time;
+
+/// Shows the message of the day: the motd file with its variables expanded,
+/// then the output of the configured command. With `once_per_day` a date
+/// stamp in the cache directory keeps it to the first shell of the day.
+pub(crate) fn show(settings: &MotdSettings, executor: &CommandExecutor) {
+    if !settings.enabled {
+        return;
+    }
+    let Ok(paths) = EnvPaths::new() else {
+        return;
+    };
+
+    let stamp = paths.get_cache_file("aorta/motd_shown");
+    let today = today();
+    if settings.once_per_day && shown_on(&stamp, &today) {
+        return;
+    }
+
+    let text = match motd_file(settings, &paths) {
+        Some(path) => match fs::read_to_string(&path) {
+            Ok(text) => Some(text),
+            Err(e) => {
+                eprintln!("aorta: motd: {}: {}", path.display(), e);
+                None
+            }
+        },
+        None => None,
+    };
+    if text.is_none() && settings.command.is_none() {
+        return;
+    }
+
+    if let Some(text) = text {
+        print_text(&expand_text(&text, 0));
+    }
+    if let Some(command) = &settings.command {
+        if let Err(e) = executor.execute_line(command) {
+            eprintln!("aorta: motd: {}", e);
+        }
+    }
+
+    if settings.once_per_day {
+        if let Err(e) = mark_shown(&stamp, &today) {
+            eprintln!("aorta: motd: {}: {}", stamp.display(), e);
+        }
+    }
+}
+
+// A configured file is used even if missing, so the error gets reported
+fn motd_file(settings: &MotdSettings, paths: &EnvPaths) -> Option<PathBuf> {
+    if let Some(path) = &settings.path {
+        return Some(path.clone());
+    }
+    [
+        paths.home().join(".aorta_motd"),
+        paths.get_config_file("aorta/motd"),
+    ]
+    .into_iter()
+    .find(|path| path.is_file())
+}
+
+fn print_text(text: &str) {
+    let mut stdout = io::stdout().lock();
+    let _ = stdout.write_all(text.as_bytes());
+    if !text.is_empty() && !text.ends_with('\n') {
+        let _ = stdout.write_all(b"\n");
+    }
+    let _ = stdout.flush();
+}
+
+fn shown_on(stamp: &Path, day: &str) -> bool {
+    fs::read_to_string(stamp).is_ok_and(|text| text.trim() == day)
+}
+
+fn mark_shown(stamp: &Path, day: &str) -> io::Result<()> {
+    if let Some(dir) = stamp.parent() {
+        fs::create_dir_all(dir)?;
+    }
+    fs::write(stamp, format!("{}\n", day))
+}
+
+// The local date as YYYY-MM-DD, so the day turns over at local midnight
+fn today() -> String {
+    let now = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |elapsed| elapsed.as_secs());
+    format_time(now, "%F")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_once_per_day_stamp() {
+        let stamp = std::env::temp_dir()
+            .join("aorta_motd_test")
+            .join("motd_shown");
+        let _ = fs::remove_file(&stamp);
+
+        assert!(!shown_on(&stamp, "2024-05-01"));
+        mark_shown(&stamp, "2024-05-01").unwrap();
+        assert!(shown_on(&stamp, "2024-05-01"));
+        assert!(!shown_on(&stamp, "2024-05-02"));
+    }
+
+    #[test]
+    fn test_today_format() {
+        let day = today();
+        assert_eq!(day.len(), 10);
+        assert_eq!(&day[4..5], "-");
+    }
+}
@@ -1,24 +1,45 @@
 use std::{
     borrow::Cow,
-    collections::{BTreeMap, HashMap},
-    process::{Command, Stdio},
+    collections::BTreeMap,
+    fs::File,
+    io::{self, Read, Write},
+    iter::Peekable,
+    os::fd::{AsRawFd, FromRawFd, OwnedFd},
+    os::unix::process::CommandExt,
+    process::{Child, ChildStdout, Command, Stdio},
+    str::Chars,
+    thread,
 };
 
-use crate::core::commands::{CommandError, CommandExecutor};
+use super::expand::{expand_words, shell_quote};
+use super::redirect::{self, Opened, Redirect};
+use crate::core::commands::{CommandError, CommandExecutor, TrapCondition};
+use crate::core::config::expand_aliases;
+use crate::core::options::ShellOptions;
+use crate::process::{exit_code, signal};
+
+/// Why a line of input needs another one, which decides how they join.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Continuation {
+    Quote,
+    Escape,
+    Operator,
+}
 
 #[derive(Debug)]
 pub enum PipelineOperator {
-    Pipe,     // |
-    And,      // &&
-    Or,       // ||
-    Sequence, // ;
-    Redirect, // >
+    Pipe,       // |
+    And,        // &&
+    Or,         // ||
+    Sequence,   // ;
+    Background, // &
 }
 
 #[derive(Debug)]
 pub struct PipelineStage {
-    pub command: String,
-    pub args: Vec<String>,
+    // The stage's text as typed, expanded when the stage runs
+    pub raw: String,
+    pub redirects: Vec<Redirect>,
     pub operator: Option<PipelineOperator>,
 }
 
@@ -28,6 +49,9 @@ pub enum PipelineError {
     Command(CommandError),
     Parse(String),
     Execution(String),
+    Expansion(String),
+    // `exit` or errexit asked the shell to terminate with this status
+    Exit(i32),
 }
 
 impl std::fmt::Display for PipelineError {
@@ -37,6 +61,8 @@ impl std::fmt::Display for PipelineError {
             Self::Command(err) => write!(f, "Command error: {}", err),
             Self::Parse(msg) => write!(f, "Parse error: {}", msg),
             Self::Execution(msg) => write!(f, "Execution error: {}", msg),
+            Self::Expansion(msg) => write!(f, "{}", msg),
+            Self::Exit(code) => write!(f, "exit {}", code),
         }
     }
 }
@@ -66,14 +92,25 @@ impl Pipeline {
     pub fn parse(input: &str) -> Result<Self, PipelineError> {
         let mut stages = Vec::new();
         let mut current_command = String::new();
+        let mut redirects = Vec::new();
         let mut chars = input.chars().peekable();
+        let mut quote: Option<char> = None;
 
         while let Some(c) = chars.next() {
+            if Self::push_quoted(c, &mut quote, &mut chars, &mut current_command) {
+                continue;
+            }
+
             match c {
                 '|' => {
                     if chars.peek() == Some(&'|') {
                         chars.next(); // consume second '|'
-                        Self::add_stage(&mut stages, &current_command, Some(PipelineOperator::Or))?;
+                        Self::add_stage(
+                            &mut stages,
+                            &current_command,
+                            &mut redirects,
+                            Some(PipelineOperator::Or),
+                        )?;
                     } else {
                         // Check if there's any non-whitespace content after the pipe
                         let remaining: String = chars.clone().collect();
@@ -85,6 +122,7 @@ impl Pipeline {
                         Self::add_stage(
                             &mut stages,
                             &current_command,
+                            &mut redirects,
                             Some(PipelineOperator::Pipe),
                         )?;
                     }
@@ -99,32 +137,46 @@ impl Pipeline {
                             "Incomplete command: missing command after &&".to_string(),
                         ));
                     }
-                    Self::add_stage(&mut stages, &current_command, Some(PipelineOperator::And))?;
+                    Self::add_stage(
+                        &mut stages,
+                        &current_command,
+                        &mut redirects,
+                        Some(PipelineOperator::And),
+                    )?;
                     current_command.clear();
                 }
-                ';' => {
+                '&' => {
                     Self::add_stage(
                         &mut stages,
                         &current_command,
-                        Some(PipelineOperator::Sequence),
+                        &mut redirects,
+                        Some(PipelineOperator::Background),
                     )?;
                     current_command.clear();
                 }
-                '>' => {
+                ';' => {
                     Self::add_stage(
                         &mut stages,
                         &current_command,
-                        Some(PipelineOperator::Redirect),
+                        &mut redirects,
+                        Some(PipelineOperator::Sequence),
                     )?;
                     current_command.clear();
                 }
+                '<' | '>' => {
+                    redirects.push(Redirect::parse(c, &mut current_command, &mut chars)?);
+                }
                 _ => current_command.push(c),
             }
         }
 
+        if quote.is_some() {
+            return Err(PipelineError::Parse("Unterminated quote".to_string()));
+        }
+
         // Add the last command if any
-        if !current_command.trim().is_empty() {
-            Self::add_stage(&mut stages, &current_command, None)?;
+        if !current_command.trim().is_empty() || !redirects.is_empty() {
+            Self::add_stage(&mut stages, &current_command, &mut redirects, None)?;
         }
 
         if stages.is_empty() {
@@ -134,24 +186,69 @@ impl Pipeline {
         Ok(Self { stages })
     }
 
+    /// Tells why `input` goes on past the end of the line, if it does: an
+    /// open quote, a trailing `\`, or a trailing `|`, `&&` or `||`.
+    pub fn continuation(input: &str) -> Option<Continuation> {
+        let mut quote = None;
+        let mut scratch = String::new();
+        let mut chars = input.chars().peekable();
+        while let Some(c) = chars.next() {
+            Self::push_quoted(c, &mut quote, &mut chars, &mut scratch);
+        }
+
+        let trailing = input.len() - input.trim_end_matches('\\').len();
+        let trimmed = input.trim_end();
+        if quote.is_some() {
+            Some(Continuation::Quote)
+        } else if trailing % 2 == 1 {
+            Some(Continuation::Escape)
+        } else if trimmed.ends_with('|') || trimmed.ends_with("&&") {
+            Some(Continuation::Operator)
+        } else {
+            None
+        }
+    }
+
+    // Copies quoted text and escapes through untouched so operators inside
+    // them are not split on; quote removal happens during expansion.
+    fn push_quoted(
+        c: char,
+        quote: &mut Option<char>,
+        chars: &mut Peekable<Chars<'_>>,
+        current_command: &mut String,
+    ) -> bool {
+        match (*quote, c) {
+            (Some(q), _) if c == q => *quote = None,
+            (None, '\'') | (None, '"') => *quote = Some(c),
+            (Some('\''), _) => {}
+            (_, '\\') => {
+                current_command.push(c);
+                if let Some(next) = chars.next() {
+                    current_command.push(next);
+                }
+                return true;
+            }
+            (Some(_), _) => {}
+            (None, _) => return false,
+        }
+        current_command.push(c);
+        true
+    }
+
     fn add_stage(
         stages: &mut Vec<PipelineStage>,
         command_str: &str,
+        redirects: &mut Vec<Redirect>,
         operator: Option<PipelineOperator>,
     ) -> Result<(), PipelineError> {
-        let trimmed = command_str.trim();
-        if trimmed.is_empty() {
-            return Err(PipelineError::Parse("Empty command".to_string()));
-        }
-
-        let parts: Vec<&str> = trimmed.split_whitespace().collect();
-        if parts.is_empty() {
+        // A stage of only redirections, like `> file`, is allowed
+        if split_words(command_str)?.is_empty() && redirects.is_empty() {
             return Err(PipelineError::Parse("Empty command".to_string()));
         }
 
         stages.push(PipelineStage {
-            command: parts[0].to_string(),
-            args: parts[1..].iter().map(|s| s.to_string()).collect(),
+            raw: command_str.trim().to_string(),
+            redirects: std::mem::take(redirects),
             operator,
         });
 
@@ -160,165 +257,597 @@ impl Pipeline {
 
     pub fn execute_with_context(
         &self,
-        _env_vars: &HashMap<String, String>,
         aliases: &BTreeMap<Cow<'_, str>, Cow<'_, str>>,
         executor: &CommandExecutor,
     ) -> Result<(), PipelineError> {
-        let mut previous_output: Option<Vec<u8>> = None;
-
-        for (index, stage) in self.stages.iter().enumerate() {
-            // First expand aliases and split into parts
-            let expanded_parts = if let Some(alias) = aliases.get(stage.command.as_str()) {
-                alias
-                    .split_whitespace()
-                    .map(|s| s.to_string())
-                    .collect::<Vec<String>>()
-            } else {
-                vec![stage.command.clone()]
-            };
-
-            let command = expanded_parts[0].clone();
-            let mut args = expanded_parts[1..].to_vec();
-            args.extend(stage.args.clone());
+        let mut index = 0;
+        let mut skip = false;
+
+        while index < self.stages.len() {
+            let group = self.next_group(index);
+            if !skip {
+                // Options are re-read per command so `set -e; false` takes effect
+                let options = executor.options();
+                let status = self.run_group(&group, aliases, executor, &options)?;
+                executor.set_last_status(status);
+
+                // As with errexit, a failure that an `&&` or `||` goes on
+                // to test doesn't run the ERR trap
+                let ends_list = !matches!(
+                    group.operator,
+                    Some(PipelineOperator::And) | Some(PipelineOperator::Or)
+                );
+                if status != 0 && ends_list {
+                    executor.run_trap(TrapCondition::Err);
+                    if options.errexit() {
+                        return Err(PipelineError::Exit(status));
+                    }
+                }
+            }
 
-            match &stage.operator {
-                Some(PipelineOperator::Pipe) => {
-                    if command == "grep" {
-                        if args.is_empty() {
-                            return Err(PipelineError::Execution(
-                                "grep: no pattern specified".to_string(),
-                            ));
-                        }
+            let status = executor.last_status();
+            skip = match group.operator {
+                Some(PipelineOperator::And) => status != 0,
+                Some(PipelineOperator::Or) => status == 0,
+                _ => false,
+            };
+            index = group.next;
+        }
 
-                        // Create a temp file for grep input
-                        let temp_input = format!("/tmp/aorta_input_{}", std::process::id());
+        Ok(())
+    }
 
-                        // Write previous output or empty string to temp file
-                        if let Some(prev_out) = previous_output.take() {
-                            std::fs::write(&temp_input, prev_out)?;
-                        } else {
-                            std::fs::write(&temp_input, "")?;
-                        }
+    // Collects the stages joined by `|` starting at `start`
+    fn next_group(&self, start: usize) -> Group<'_> {
+        let mut end = start;
+        while matches!(self.stages[end].operator, Some(PipelineOperator::Pipe))
+            && end + 1 < self.stages.len()
+        {
+            end += 1;
+        }
 
-                        // Create a temp file for grep output
-                        let temp_output = format!("/tmp/aorta_output_{}", std::process::id());
-
-                        // Keep the pattern and any options, add temp file as last argument
-                        let mut grep_args = args;
-                        grep_args.push(temp_input.clone());
-
-                        // Execute grep through executor
-                        executor
-                            .execute(&command, &grep_args)
-                            .map_err(|e| PipelineError::Execution(e.to_string()))?;
-
-                        // Read the output if it exists
-                        if let Ok(output) = std::fs::read(&temp_output) {
-                            previous_output = Some(output);
-                        } else {
-                            // If no output file, try reading from stdout capture
-                            let mut cmd = Command::new("grep");
-                            cmd.args(&grep_args)
-                                .stdout(Stdio::piped())
-                                .stderr(Stdio::inherit());
-
-                            let output = cmd
-                                .output()
-                                .map_err(|e| PipelineError::Execution(e.to_string()))?;
-                            previous_output = Some(output.stdout);
-                        }
+        Group {
+            stages: &self.stages[start..=end],
+            operator: self.stages[end].operator.as_ref(),
+            next: end + 1,
+        }
+    }
 
-                        // Clean up temp files
-                        let _ = std::fs::remove_file(temp_input);
-                        let _ = std::fs::remove_file(temp_output);
-                    } else {
-                        // For other commands (including ls)
-                        let mut cmd = Command::new(&command);
-                        cmd.args(&args)
-                            .stdout(Stdio::piped())
-                            .stderr(Stdio::inherit());
-
-                        let output = cmd
-                            .output()
-                            .map_err(|e| PipelineError::Execution(e.to_string()))?;
-                        previous_output = Some(output.stdout);
-                    }
-                }
-                Some(PipelineOperator::And)
-                | Some(PipelineOperator::Or)
-                | Some(PipelineOperator::Sequence)
-                | None => {
-                    executor
-                        .execute(&command, &args)
-                        .map_err(|e| PipelineError::Execution(e.to_string()))?;
-                    previous_output = None;
-                }
-                Some(PipelineOperator::Redirect) => {
-                    if let Some(next_stage) = self.stages.get(index + 1) {
-                        if let Some(output) = previous_output.take() {
-                            std::fs::write(&next_stage.command, output)?;
-                        } else {
-                            let mut cmd = Command::new(&command);
-                            cmd.args(&args)
-                                .stdout(Stdio::piped())
-                                .stderr(Stdio::inherit());
-
-                            let output = cmd
-                                .output()
-                                .map_err(|e| PipelineError::Execution(e.to_string()))?;
-                            std::fs::write(&next_stage.command, output.stdout)?;
-                        }
-                        break;
-                    } else {
-                        return Err(PipelineError::Execution(
-                            "Redirect operator requires a file path".to_string(),
-                        ));
+    fn run_group(
+        &self,
+        group: &Group<'_>,
+        aliases: &BTreeMap<Cow<'_, str>, Cow<'_, str>>,
+        executor: &CommandExecutor,
+        options: &ShellOptions,
+    ) -> Result<i32, PipelineError> {
+        let mut commands = Vec::new();
+        for stage in group.stages {
+            let raw = expand_alias(&stage.raw, aliases, options);
+            let words = expand_words(&raw, options, executor.last_status())?;
+            // Files are opened even for a stage without a command
+            let redirects =
+                match redirect::open_all(&stage.redirects, options, executor.last_status()) {
+                    Ok(redirects) => redirects,
+                    Err(msg) => {
+                        eprintln!("aorta: {}", msg);
+                        return Ok(1);
                     }
-                }
+                };
+            if words.is_empty() {
+                continue;
+            }
+            if options.xtrace() {
+                trace(&words);
             }
+            commands.push(Stage { words, redirects });
         }
 
-        // Print remaining output if any
-        if let Some(output) = previous_output {
-            if !output.is_empty() {
-                if let Ok(s) = String::from_utf8(output) {
-                    print!("{}", s);
-                }
+        if commands.is_empty() {
+            return Ok(0);
+        }
+        if matches!(group.operator, Some(PipelineOperator::Background)) {
+            let text: Vec<&str> = group.stages.iter().map(|s| s.raw.as_str()).collect();
+            return run_background(commands, &text.join(" | "), executor);
+        }
+        match commands.len() {
+            1 if commands[0].redirects.is_empty() => run_simple(&commands[0].words, executor),
+            _ => run_pipeline(commands, executor, options.pipefail()),
+        }
+    }
+}
+
+struct Group<'a> {
+    stages: &'a [PipelineStage],
+    operator: Option<&'a PipelineOperator>,
+    next: usize,
+}
+
+// A stage ready to run: expanded words and opened redirections
+struct Stage {
+    words: Vec<String>,
+    redirects: Vec<Opened>,
+}
+
+enum StageInput {
+    Pipe(ChildStdout),
+    Bytes(Vec<u8>),
+}
+
+enum Running {
+    Child(Child),
+    Done(i32),
+}
+
+// Expands the aliases the stage starts with, keeping the rest of the text
+// so alias values go through the same expansion as typed input.
+fn expand_alias(
+    raw: &str,
+    aliases: &BTreeMap<Cow<'_, str>, Cow<'_, str>>,
+    options: &ShellOptions,
+) -> String {
+    if !options.is_shopt("expand_aliases") {
+        return raw.to_string();
+    }
+    expand_aliases(raw, |word| aliases.get(word).map(|value| value.as_ref())).into_owned()
+}
+
+fn trace(words: &[String]) {
+    let prefix = std::env::var("PS4").unwrap_or_else(|_| "+ ".to_string());
+    let line: Vec<String> = words
+        .iter()
+        .map(|word| {
+            if word.is_empty()
+                || word.contains(|c: char| c.is_whitespace() || "'\"$|&;<>".contains(c))
+            {
+                shell_quote(word)
+            } else {
+                word.clone()
             }
+        })
+        .collect();
+    eprintln!("{}{}", prefix, line.join(" "));
+}
+
+fn run_simple(words: &[String], executor: &CommandExecutor) -> Result<i32, PipelineError> {
+    match executor.execute(&words[0], &words[1..]) {
+        Ok(()) => Ok(executor.last_status()),
+        Err(CommandError::Exit(code)) => Err(PipelineError::Exit(code)),
+        Err(e) => {
+            eprintln!("aorta: {}: {}", words[0], e);
+            Ok(executor.last_status().max(1))
         }
+    }
+}
 
-        Ok(())
+// Runs a builtin with its redirections applied to the shell's descriptors.
+// `exec` keeps them afterwards, which is how `exec 2>&1` rewires the shell.
+fn run_redirected(stage: Stage, executor: &CommandExecutor) -> Result<i32, PipelineError> {
+    let permanent = stage.words[0] == "exec";
+    let saved = match redirect::apply(stage.redirects, permanent) {
+        Ok(saved) => saved,
+        Err(e) => {
+            eprintln!("aorta: {}: {}", stage.words[0], e);
+            return Ok(1);
+        }
+    };
+    let result = run_simple(&stage.words, executor);
+    redirect::restore(saved);
+    result
+}
+
+fn run_pipeline(
+    commands: Vec<Stage>,
+    executor: &CommandExecutor,
+    pipefail: bool,
+) -> Result<i32, PipelineError> {
+    let running = start_pipeline(commands, executor, false)?;
+    let statuses: Vec<i32> = running
+        .into_iter()
+        .map(|stage| match stage {
+            Running::Child(mut child) => child.wait().map(exit_code).unwrap_or(1),
+            Running::Done(status) => status,
+        })
+        .collect();
+
+    Ok(pipeline_status(&statuses, pipefail))
+}
+
+// Starts a job in its own process group and returns without waiting; it is
+// reaped through the executor's job table. Builtins still run in the shell.
+fn run_background(
+    commands: Vec<Stage>,
+    text: &str,
+    executor: &CommandExecutor,
+) -> Result<i32, PipelineError> {
+    let running = start_pipeline(commands, executor, true)?;
+    let pids: Vec<i32> = running
+        .iter()
+        .filter_map(|stage| match stage {
+            Running::Child(child) => Some(child.id() as i32),
+            Running::Done(_) => None,
+        })
+        .collect();
+
+    // Only a shell at a terminal announces the job, as bash does
+    if let Some(last) = pids.last().copied() {
+        let id = executor.add_job(pids, text);
+        if executor.is_interactive() {
+            eprintln!("[{}] {}", id, last);
+        }
     }
+    Ok(0)
+}
 
-    // Currently testing methods for expanding environment variables
-    // leaving this experimental expand_env_vars method for now
-    // FIX:TODO: Remove this once we have a proper way to expand environment variables
-    #[allow(dead_code)]
-    fn expand_env_vars(&self, input: &str, env_vars: &HashMap<String, String>) -> String {
-        let mut result = input.to_string();
+fn start_pipeline(
+    commands: Vec<Stage>,
+    executor: &CommandExecutor,
+    background: bool,
+) -> Result<Vec<Running>, PipelineError> {
+    let last = commands.len() - 1;
+    let mut input: Option<StageInput> = None;
+    let mut running = Vec::with_capacity(commands.len());
+    let mut pgid = 0;
+
+    for (index, stage) in commands.into_iter().enumerate() {
+        if executor.is_builtin(&stage.words[0]) {
+            let (status, next) = run_builtin_stage(stage, index == last, executor)?;
+            input = next;
+            running.push(Running::Done(status));
+            continue;
+        }
+
+        let mut cmd = Command::new(&stage.words[0]);
+        cmd.args(&stage.words[1..]);
+        let feed = connect_stage(
+            &mut cmd,
+            input.take(),
+            index == 0 && !background,
+            index == last,
+        );
+        if background {
+            cmd.process_group(pgid);
+        }
+        signal::prepare_child(&mut cmd);
+        redirect::attach(&mut cmd, &stage.redirects);
+
+        let stage = spawn_stage(&mut cmd, &stage.words[0], feed, &mut input);
+        if let (Running::Child(child), 0) = (&stage, pgid) {
+            pgid = child.id() as i32;
+        }
+        running.push(stage);
+    }
+    Ok(running)
+}
+
+// Builtins run in the shell process; their output is captured when it has
+// to flow into the next stage.
+fn run_builtin_stage(
+    stage: Stage,
+    is_last: bool,
+    executor: &CommandExecutor,
+) -> Result<(i32, Option<StageInput>), PipelineError> {
+    if is_last {
+        return Ok((run_redirected(stage, executor)?, None));
+    }
 
-        // Handle $VAR style variables
-        while let Some(dollar_pos) = result.find('$') {
-            if dollar_pos + 1 >= result.len() {
-                break;
+    let (status, bytes) = capture_builtin(stage, executor)?;
+    Ok((status, Some(StageInput::Bytes(bytes))))
+}
+
+// Wires up stdin/stdout for an external stage; returns builtin output that
+// still has to be written to the child's stdin.
+fn connect_stage(
+    cmd: &mut Command,
+    input: Option<StageInput>,
+    terminal_input: bool,
+    is_last: bool,
+) -> Option<Vec<u8>> {
+    let mut feed = None;
+    match input {
+        Some(StageInput::Pipe(pipe)) => cmd.stdin(Stdio::from(pipe)),
+        Some(StageInput::Bytes(bytes)) => {
+            feed = Some(bytes);
+            cmd.stdin(Stdio::piped())
+        }
+        None if terminal_input => cmd.stdin(Stdio::inherit()),
+        None => cmd.stdin(Stdio::null()),
+    };
+    if is_last {
+        cmd.stdout(Stdio::inherit());
+    } else {
+        cmd.stdout(Stdio::piped());
+    }
+    feed
+}
+
+fn spawn_stage(
+    cmd: &mut Command,
+    name: &str,
+    feed: Option<Vec<u8>>,
+    next_input: &mut Option<StageInput>,
+) -> Running {
+    match cmd.spawn() {
+        Ok(mut child) => {
+            if let (Some(bytes), Some(mut stdin)) = (feed, child.stdin.take()) {
+                thread::spawn(move || {
+                    let _ = stdin.write_all(&bytes);
+                });
             }
+            *next_input = child.stdout.take().map(StageInput::Pipe);
+            Running::Child(child)
+        }
+        Err(e) if e.kind() == io::ErrorKind::NotFound => {
+            eprintln!("aorta: command not found: {}", name);
+            Running::Done(127)
+        }
+        Err(e) => {
+            eprintln!("aorta: {}: {}", name, e);
+            Running::Done(126)
+        }
+    }
+}
+
+// Runs a builtin with fd 1 pointed at a pipe and returns what it printed.
+fn capture_builtin(
+    stage: Stage,
+    executor: &CommandExecutor,
+) -> Result<(i32, Vec<u8>), PipelineError> {
+    let mut fds = [0; 2];
+    if unsafe { libc::pipe(fds.as_mut_ptr()) } != 0 {
+        return Err(io::Error::last_os_error().into());
+    }
+    let mut reader = unsafe { File::from_raw_fd(fds[0]) };
+    let writer = unsafe { OwnedFd::from_raw_fd(fds[1]) };
 
-            // Find the end of the variable name
-            let var_end = result[dollar_pos + 1..]
-                .find(|c: char| !c.is_alphanumeric() && c != '_')
-                .map_or(result.len(), |pos| pos + dollar_pos + 1);
+    io::stdout().flush()?;
+    let saved = unsafe { libc::dup(libc::STDOUT_FILENO) };
+    if saved < 0 {
+        return Err(io::Error::last_os_error().into());
+    }
+    unsafe { libc::dup2(writer.as_raw_fd(), libc::STDOUT_FILENO) };
+    drop(writer);
+
+    let collector = thread::spawn(move || {
+        let mut bytes = Vec::new();
+        let _ = reader.read_to_end(&mut bytes);
+        bytes
+    });
+    let result = run_redirected(stage, executor);
+
+    let _ = io::stdout().flush();
+    unsafe {
+        libc::dup2(saved, libc::STDOUT_FILENO);
+        libc::close(saved);
+    }
+    let bytes = collector.join().unwrap_or_default();
+    Ok((result?, bytes))
+}
 
-            let var_name = &result[dollar_pos + 1..var_end];
+// The status of the last stage, or with pipefail the last failing one
+fn pipeline_status(statuses: &[i32], pipefail: bool) -> i32 {
+    if pipefail {
+        statuses
+            .iter()
+            .rev()
+            .copied()
+            .find(|s| *s != 0)
+            .unwrap_or(0)
+    } else {
+        statuses.last().copied().unwrap_or(0)
+    }
+}
 
-            // Get the value from environment
-            if let Some(value) = env_vars.get(var_name) {
-                result.replace_range(dollar_pos..var_end, value);
-            } else {
-                // If variable not found, replace with empty string
-                result.replace_range(dollar_pos..var_end, "");
+/// Splits a command into words, honouring single quotes, double quotes and
+/// backslash escapes, and removes the quoting from the result.
+pub(crate) fn split_words(input: &str) -> Result<Vec<String>, PipelineError> {
+    let mut words = Vec::new();
+    let mut word = String::new();
+    let mut in_word = false;
+    let mut quote: Option<char> = None;
+    let mut chars = input.chars();
+
+    while let Some(c) = chars.next() {
+        match (quote, c) {
+            (None, c) if c.is_whitespace() => {
+                if in_word {
+                    words.push(std::mem::take(&mut word));
+                    in_word = false;
+                }
+            }
+            (None, '\'') | (None, '"') => {
+                quote = Some(c);
+                in_word = true;
+            }
+            (Some(q), c) if c == q => quote = None,
+            (None, '\\') | (Some('"'), '\\') => {
+                in_word = true;
+                match chars.next() {
+                    Some(next) if quote.is_none() || matches!(next, '"' | '\\' | '$' | '`') => {
+                        word.push(next)
+                    }
+                    Some(next) => {
+                        word.push('\\');
+                        word.push(next);
+                    }
+                    None => word.push('\\'),
+                }
+            }
+            _ => {
+                word.push(c);
+                in_word = true;
             }
         }
+    }
+
+    if quote.is_some() {
+        return Err(PipelineError::Parse("Unterminated quote".to_string()));
+    }
+    if in_word {
+        words.push(word);
+    }
+    Ok(words)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_split_words_quotes() {
+        let words = split_words(r#"trap 'echo a; echo b' "INT" it\'s"#).unwrap();
+        assert_eq!(words, vec!["trap", "echo a; echo b", "INT", "it's"]);
+    }
+
+    #[test]
+    fn test_split_words_empty_quotes() {
+        assert_eq!(split_words("trap '' INT").unwrap(), vec!["trap", "", "INT"]);
+    }
+
+    #[test]
+    fn test_parse_ignores_operators_in_quotes() {
+        let pipeline = Pipeline::parse("echo 'a | b; c > d'").unwrap();
+        assert_eq!(pipeline.stages.len(), 1);
+        assert_eq!(pipeline.stages[0].raw, "echo 'a | b; c > d'");
+    }
+
+    #[test]
+    fn test_parse_unterminated_quote() {
+        assert!(Pipeline::parse("echo 'oops").is_err());
+    }
+
+    #[test]
+    fn test_continuation() {
+        assert_eq!(
+            Pipeline::continuation("echo 'oops"),
+            Some(Continuation::Quote)
+        );
+        assert_eq!(
+            Pipeline::continuation("echo a \\"),
+            Some(Continuation::Escape)
+        );
+        assert_eq!(Pipeline::continuation("ls |"), Some(Continuation::Operator));
+        assert_eq!(
+            Pipeline::continuation("true && "),
+            Some(Continuation::Operator)
+        );
+        assert_eq!(Pipeline::continuation("echo a\\\\"), None);
+        assert_eq!(Pipeline::continuation("echo '|'"), None);
+        assert_eq!(Pipeline::continuation("sleep 1 &"), None);
+    }
+
+    fn run(line: &str, executor: &CommandExecutor) -> Result<(), PipelineError> {
+        Pipeline::parse(line)?.execute_with_context(&BTreeMap::new(), executor)
+    }
+
+    fn executor() -> CommandExecutor {
+        CommandExecutor::new(&crate::flags::Flags::default()).unwrap()
+    }
+
+    #[test]
+    fn test_and_or_lists() {
+        let executor = executor();
+        run(
+            "cd /aorta/missing && export AORTA_AND=ran || export AORTA_OR=ran",
+            &executor,
+        )
+        .unwrap();
+        assert!(std::env::var("AORTA_AND").is_err());
+        assert_eq!(std::env::var("AORTA_OR").unwrap(), "ran");
+        assert_eq!(executor.last_status(), 0);
+    }
+
+    #[test]
+    fn test_errexit_and_pipefail() {
+        let executor = executor();
+        run("set -e; cd /aorta/missing || export AORTA_E=1", &executor).unwrap();
+
+        let result = run("cd /aorta/missing; export AORTA_AFTER_E=1", &executor);
+        assert!(matches!(result, Err(PipelineError::Exit(1))));
+        assert!(std::env::var("AORTA_AFTER_E").is_err());
+
+        run("set +e; cd /aorta/missing | export AORTA_PIPE=1", &executor).unwrap();
+        assert_eq!(executor.last_status(), 0);
+        run(
+            "set -o pipefail; cd /aorta/missing | export AORTA_PIPE=1",
+            &executor,
+        )
+        .unwrap();
+        assert_eq!(executor.last_status(), 1);
+    }
+
+    #[test]
+    fn test_err_trap_skips_tested_failures() {
+        let executor = executor();
+        run(
+            "trap 'export AORTA_ERR_LIST=$AORTA_ERR_LIST.' ERR",
+            &executor,
+        )
+        .unwrap();
+        run("cd /aorta/missing && export AORTA_ERR_AND=1", &executor).unwrap();
+        run("cd /aorta/missing || cd /aorta/missing; true", &executor).unwrap();
+        assert_eq!(std::env::var("AORTA_ERR_LIST").unwrap(), ".");
+
+        run("cd /aorta/missing | export AORTA_ERR_PIPE=1", &executor).unwrap();
+        run("export AORTA_ERR_AND=1 && cd /aorta/missing", &executor).unwrap();
+        assert_eq!(std::env::var("AORTA_ERR_LIST").unwrap(), "..");
+    }
+
+    #[test]
+    fn test_noclobber_redirect() {
+        let executor = executor();
+        let file = std::env::temp_dir().join("aorta_noclobber_test");
+        std::fs::write(&file, "keep").unwrap();
+        let path = file.display();
+
+        run(&format!("set -C; trap > {}", path), &executor).unwrap();
+        assert_eq!(executor.last_status(), 1);
+        assert_eq!(std::fs::read_to_string(&file).unwrap(), "keep");
+
+        run(&format!("trap >| {}", path), &executor).unwrap();
+        assert_eq!(executor.last_status(), 0);
+        assert_eq!(std::fs::read_to_string(&file).unwrap(), "");
+        std::fs::remove_file(file).unwrap();
+    }
+
+    #[test]
+    fn test_parse_redirections() {
+        let pipeline = Pipeline::parse("ls 2>/dev/null | wc -l >> out; > empty").unwrap();
+        assert_eq!(pipeline.stages.len(), 3);
+        assert_eq!(pipeline.stages[0].raw, "ls");
+        assert_eq!(pipeline.stages[0].redirects[0].fd, 2);
+        assert_eq!(pipeline.stages[1].redirects[0].target, "out");
+        assert_eq!(pipeline.stages[2].raw, "");
+    }
+
+    #[test]
+    fn test_parse_background() {
+        let pipeline = Pipeline::parse("sleep 5 & cmd 2>&1 && true").unwrap();
+        assert!(matches!(
+            pipeline.stages[0].operator,
+            Some(PipelineOperator::Background)
+        ));
+        assert_eq!(pipeline.stages[1].raw, "cmd");
+        assert!(matches!(
+            pipeline.stages[1].operator,
+            Some(PipelineOperator::And)
+        ));
+    }
+
+    #[test]
+    fn test_redirection_only_stage() {
+        let executor = executor();
+        let file = std::env::temp_dir().join("aorta_truncate_test");
+        std::fs::write(&file, "old").unwrap();
+
+        run(&format!(">| {}", file.display()), &executor).unwrap();
+        assert_eq!(std::fs::read_to_string(&file).unwrap(), "");
+        std::fs::remove_file(file).unwrap();
+    }
 
-        result
+    #[test]
+    fn test_pipeline_status() {
+        assert_eq!(pipeline_status(&[1, 0], false), 0);
+        assert_eq!(pipeline_status(&[2, 1, 0], true), 1);
+        assert_eq!(pipeline_status(&[0, 0], true), 0);
     }
 }
@@ -0,0 +1,400 @@
+use std::ffi::CStr;
+use std::iter::Peekable;
+use std::str::Chars;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use super::expand::expand_text;
+use super::git::GitStatus;
+use crate::{
+    core::config::ColorSettings,
+    highlight::SyntaxHighlighter,
+    process::signal,
+    sys::{format_time, host_name},
+};
+
+// Readline's markers around `\[ ... \]`: text between them takes no columns
+const START_IGNORE: char = '\x01';
+const END_IGNORE: char = '\x02';
+
+/// What the prompt escapes need to know about the shell. It's owned so a
+/// prompt can be rendered again on another thread.
+#[derive(Debug, Clone)]
+pub(crate) struct PromptContext {
+    pub cwd: String,
+    pub last_status: i32,
+    pub jobs: usize,
+    // How long the last command took, when over the duration threshold
+    pub duration: Option<Duration>,
+    // Only looked up when the template uses `\g`
+    pub git: Option<GitStatus>,
+}
+
+/// The templates for one prompt. A `right` of `None` means the built-in
+/// right prompt; continuation lines get none at all.
+#[derive(Debug, Clone)]
+pub(crate) struct Templates {
+    pub left: String,
+    pub right: Option<String>,
+    pub continuation: bool,
+    // For the status in the built-in right prompt
+    pub colors: ColorSettings,
+}
+
+impl Templates {
+    pub fn uses_git(&self) -> bool {
+        uses_git(&self.left) || self.right.as_deref().is_some_and(uses_git)
+    }
+
+    /// Renders the left and right prompts.
+    pub fn render(&self, context: &PromptContext) -> (Prompt, Prompt) {
+        let left = render(&self.left, context);
+        let right = match &self.right {
+            _ if self.continuation => Prompt::default(),
+            Some(template) => render(template, context),
+            None => {
+                let highlighter = SyntaxHighlighter::new().with_colors(self.colors);
+                default_right(context, |status| highlighter.highlight_error(status))
+            }
+        };
+        (left, right)
+    }
+}
+
+/// A rendered prompt. `text` is printed, escape sequences and all, while
+/// the editor measures `visible` so cursor movement stays right.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub(crate) struct Prompt {
+    pub text: String,
+    pub visible: String,
+}
+
+/// Renders a `PS1`-style template: backslash escapes first, then `$VAR`
+/// expansion of the text between them.
+pub(crate) fn render(template: &str, context: &PromptContext) -> Prompt {
+    let mut output = String::new();
+    let mut literal = String::new();
+    let mut chars = template.chars().peekable();
+
+    while let Some(c) = chars.next() {
+        if c != '\\' {
+            literal.push(c);
+            continue;
+        }
+        output.push_str(&expand_text(
+            &std::mem::take(&mut literal),
+            context.last_status,
+        ));
+        match chars.next() {
+            Some(escape) => push_escape(escape, &mut chars, context, &mut output),
+            None => output.push('\\'),
+        }
+    }
+    output.push_str(&expand_text(&literal, context.last_status));
+
+    Prompt {
+        visible: strip_escapes(&output),
+        text: output.replace([START_IGNORE, END_IGNORE], ""),
+    }
+}
+
+fn push_escape(
+    escape: char,
+    chars: &mut Peekable<Chars<'_>>,
+    context: &PromptContext,
+    output: &mut String,
+) {
+    let home = std::env::var("HOME").unwrap_or_default();
+    match escape {
+        'u' => output.push_str(&user_name()),
+        'h' => output.push_str(host_name().split('.').next().unwrap_or_default()),
+        'H' => output.push_str(&host_name()),
+        'w' => output.push_str(&abbreviate_home(&context.cwd, &home)),
+        'W' => output.push_str(&base_name(&context.cwd, &home)),
+        '$' => output.push(prompt_char()),
+        't' => output.push_str(&clock(true)),
+        'A' => output.push_str(&clock(false)),
+        'j' => output.push_str(&context.jobs.to_string()),
+        '?' => output.push_str(&context.last_status.to_string()),
+        'S' => output.push_str(&status_text(context.last_status)),
+        'D' => output.extend(context.duration.map(format_duration)),
+        'n' => output.push('\n'),
+        'e' => output.push('\x1b'),
+        'a' => output.push('\x07'),
+        '\\' => output.push('\\'),
+        '[' => output.push(START_IGNORE),
+        ']' => output.push(END_IGNORE),
+        'g' => push_git(chars, context.git.as_ref(), output),
+        '0'..='7' => output.push(octal(escape, chars)),
+        other => {
+            output.push('\\');
+            output.push(other);
+        }
+    }
+}
+
+// `\g` is the summary and `\g{field}` a single field, both empty outside a
+// repository
+fn push_git(chars: &mut Peekable<Chars<'_>>, git: Option<&GitStatus>, output: &mut String) {
+    let field = chars
+        .next_if_eq(&'{')
+        .map(|_| chars.by_ref().take_while(|c| *c != '}').collect::<String>());
+    let Some(git) = git else {
+        return;
+    };
+    match field {
+        Some(name) => output.extend(git.field(&name)),
+        None => output.push_str(&git.summary()),
+    }
+}
+
+/// Whether a template uses the git escapes, which cost a `git status`.
+fn uses_git(template: &str) -> bool {
+    template.contains("\\g")
+}
+
+/// The last status for `\S`: empty on success, with the signal's name when
+/// the command was killed by one, like `130 SIGINT`.
+pub(crate) fn status_text(status: i32) -> String {
+    match status {
+        0 => String::new(),
+        code if code > 128 => match signal::name(code - 128) {
+            Some(name) => format!("{} SIG{}", code, name),
+            None => code.to_string(),
+        },
+        code => code.to_string(),
+    }
+}
+
+/// A duration for `\D`, as `4.2s`, `3m07s` or `1h05m`.
+pub(crate) fn format_duration(duration: Duration) -> String {
+    let seconds = duration.as_secs();
+    match seconds {
+        0..60 => format!("{:.1}s", duration.as_secs_f64()),
+        60..3600 => format!("{}m{:02}s", seconds / 60, seconds % 60),
+        _ => format!("{}h{:02}m", seconds / 3600, seconds % 3600 / 60),
+    }
+}
+
+/// The right prompt when no template is set: the failed status, how long a
+/// slow command took and the time, leaving out the empty parts. `color`
+/// paints the status.
+fn default_right(context: &PromptContext, color: impl Fn(&str) -> String) -> Prompt {
+    let status = status_text(context.last_status);
+    let parts = [
+        (!status.is_empty()).then(|| color(&status)),
+        context.duration.map(format_duration),
+        Some(clock(true)),
+    ];
+    let text = parts.into_iter().flatten().collect::<Vec<_>>().join(" ");
+    Prompt {
+        visible: strip_escapes(&text),
+        text,
+    }
+}
+
+// `\033` and friends: up to three octal digits
+fn octal(first: char, chars: &mut Peekable<Chars<'_>>) -> char {
+    let mut value = first.to_digit(8).unwrap_or(0);
+    for _ in 0..2 {
+        match chars.peek().and_then(|c| c.to_digit(8)) {
+            Some(digit) => {
+                value = value * 8 + digit;
+                chars.next();
+            }
+            None => break,
+        }
+    }
+    char::from_u32(value).unwrap_or('?')
+}
+
+/// Removes what takes no columns on screen: `\[ \]` regions and ANSI
+/// escape sequences written without them.
+pub(crate) fn strip_escapes(text: &str) -> String {
+    let mut visible = String::with_capacity(text.len());
+    let mut chars = text.chars().peekable();
+
+    while let Some(c) = chars.next() {
+        match c {
+            START_IGNORE => {
+                for c in chars.by_ref() {
+                    if c == END_IGNORE {
+                        break;
+                    }
+                }
+            }
+            END_IGNORE => {}
+            '\x1b' => skip_escape_sequence(&mut chars),
+            c => visible.push(c),
+        }
+    }
+    visible
+}
+
+fn skip_escape_sequence(chars: &mut Peekable<Chars<'_>>) {
+    match chars.next() {
+        // CSI, as in `\e[1;32m`: parameters up to a final byte
+        Some('[') => {
+            for c in chars.by_ref() {
+                if ('\x40'..='\x7e').contains(&c) {
+                    break;
+                }
+            }
+        }
+        // OSC, as in terminal titles: up to BEL or ESC backslash
+        Some(']') => {
+            while let Some(c) = chars.next() {
+                if c == '\x07' || (c == '\x1b' && chars.next_if_eq(&'\\').is_some()) {
+                    break;
+                }
+            }
+        }
+        _ => {}
+    }
+}
+
+pub(crate) fn abbreviate_home(path: &str, home: &str) -> String {
+    if home.is_empty() || home == "/" {
+        return path.to_string();
+    }
+    match path.strip_prefix(home) {
+        Some("") => "~".to_string(),
+        Some(rest) if rest.starts_with('/') => format!("~{}", rest),
+        _ => path.to_string(),
+    }
+}
+
+fn base_name(path: &str, home: &str) -> String {
+    if !home.is_empty() && path == home {
+        return "~".to_string();
+    }
+    match path.trim_end_matches('/').rsplit('/').next() {
+        Some("") | None => "/".to_string(),
+        Some(name) => name.to_string(),
+    }
+}
+
+fn user_name() -> String {
+    if let Ok(user) = std::env::var("USER").or_else(|_| std::env::var("LOGNAME")) {
+        return user;
+    }
+    let uid = unsafe { libc::getuid() };
+    let passwd = unsafe { libc::getpwuid(uid) };
+    if passwd.is_null() {
+        return uid.to_string();
+    }
+    unsafe { CStr::from_ptr((*passwd).pw_name) }
+        .to_string_lossy()
+        .into_owned()
+}
+
+// `#` for root, `$` for everyone else
+fn prompt_char() -> char {
+    if unsafe { libc::geteuid() } == 0 {
+        '#'
+    } else {
+        '$'
+    }
+}
+
+fn clock(seconds: bool) -> String {
+    let now = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |elapsed| elapsed.as_secs());
+    format_time(now, if seconds { "%H:%M:%S" } else { "%H:%M" })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn context(cwd: &str) -> PromptContext {
+        PromptContext {
+            cwd: cwd.to_string(),
+            last_status: 2,
+            jobs: 1,
+            duration: None,
+            git: None,
+        }
+    }
+
+    #[test]
+    fn test_render_escapes() {
+        std::env::set_var("AORTA_PROMPT_TEST", "dev");
+        let prompt = render(
+            "[\\j:\\?] \\W $AORTA_PROMPT_TEST\\n> ",
+            &context("/usr/local"),
+        );
+        assert_eq!(prompt.text, "[1:2] local dev\n> ");
+        assert_eq!(prompt.visible, prompt.text);
+
+        let prompt = render("\\\\ \\x", &context("/"));
+        assert_eq!(prompt.text, "\\ \\x");
+    }
+
+    #[test]
+    fn test_colors_take_no_width() {
+        let prompt = render("\\[\\e[1;32m\\]ok\\[\\033[0m\\] ", &context("/"));
+        assert_eq!(prompt.text, "\x1b[1;32mok\x1b[0m ");
+        assert_eq!(prompt.visible, "ok ");
+
+        // Bare sequences are recognised without the brackets too
+        let prompt = render("\\e[31mred\\e[0m\\e]0;title\\a$", &context("/"));
+        assert_eq!(prompt.visible, "red$");
+    }
+
+    #[test]
+    fn test_git_escapes() {
+        let status = GitStatus {
+            branch: "main".into(),
+            modified: 2,
+            ..Default::default()
+        };
+        let with_git = PromptContext {
+            git: Some(status),
+            ..context("/")
+        };
+        let prompt = render("[\\g] \\g{branch}:\\g{modified}", &with_git);
+        assert_eq!(prompt.text, "[main ~2] main:2");
+
+        assert!(uses_git("\\w \\g{branch}"));
+        assert_eq!(render("[\\g{branch}]", &context("/")).text, "[]");
+    }
+
+    #[test]
+    fn test_status_and_duration() {
+        let with_duration = PromptContext {
+            last_status: 130,
+            duration: Some(Duration::from_millis(4250)),
+            ..context("/")
+        };
+        let prompt = render("[\\S] \\D", &with_duration);
+        assert_eq!(prompt.text, "[130 SIGINT] 4.2s");
+        assert_eq!(render("[\\S]\\D", &context("/")).text, "[2]");
+        assert_eq!(status_text(0), "");
+
+        assert_eq!(format_duration(Duration::from_secs(187)), "3m07s");
+        assert_eq!(format_duration(Duration::from_secs(3900)), "1h05m");
+    }
+
+    #[test]
+    fn test_default_right_prompt() {
+        let ok = PromptContext {
+            last_status: 0,
+            ..context("/")
+        };
+        assert_eq!(default_right(&ok, str::to_string).text.len(), 8);
+
+        let failed = default_right(&context("/"), |s| format!("\x1b[31m{}\x1b[0m", s));
+        assert!(failed.text.starts_with("\x1b[31m2\x1b[0m "));
+        assert_eq!(failed.visible.len(), 10);
+    }
+
+    #[test]
+    fn test_home_abbreviation() {
+        assert_eq!(abbreviate_home("/home/u", "/home/u"), "~");
+        assert_eq!(abbreviate_home("/home/u/src", "/home/u"), "~/src");
+        assert_eq!(abbreviate_home("/home/user2", "/home/u"), "/home/user2");
+        assert_eq!(base_name("/home/u", "/home/u"), "~");
+        assert_eq!(base_name("/", "/home/u"), "/");
+    }
+}
@@ -0,0 +1,362 @@
+use std::fs::OpenOptions;
+use std::io::{self, Write};
+use std::iter::Peekable;
+use std::os::fd::{AsRawFd, IntoRawFd, OwnedFd, RawFd};
+use std::os::unix::process::CommandExt;
+use std::process::Command;
+use std::str::Chars;
+
+use super::expand::expand_words;
+use super::pipeline::PipelineError;
+use crate::core::options::ShellOptions;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RedirectOp {
+    Read,      // <
+    Write,     // >
+    Append,    // >>
+    Clobber,   // >|
+    ReadWrite, // <>
+    DupOut,    // >&
+    DupIn,     // <&
+}
+
+/// A redirection as written; the target is expanded when the command runs.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Redirect {
+    pub fd: RawFd,
+    pub op: RedirectOp,
+    pub target: String,
+}
+
+impl Redirect {
+    /// Parses a redirection starting at `first` (`<` or `>`). A file
+    /// descriptor number written right before the operator, as in `2>`, is
+    /// taken off the end of `command`.
+    pub(crate) fn parse(
+        first: char,
+        command: &mut String,
+        chars: &mut Peekable<Chars<'_>>,
+    ) -> Result<Self, PipelineError> {
+        let op = match (first, chars.peek()) {
+            ('>', Some('>')) => RedirectOp::Append,
+            ('>', Some('|')) => RedirectOp::Clobber,
+            ('>', Some('&')) => RedirectOp::DupOut,
+            ('<', Some('>')) => RedirectOp::ReadWrite,
+            ('<', Some('&')) => RedirectOp::DupIn,
+            ('>', _) => RedirectOp::Write,
+            _ => RedirectOp::Read,
+        };
+        if !matches!(op, RedirectOp::Write | RedirectOp::Read) {
+            chars.next();
+        }
+
+        let default_fd = if first == '<' { 0 } else { 1 };
+        let fd = take_fd_prefix(command).unwrap_or(default_fd);
+        let target = read_target(chars)?;
+        Ok(Self { fd, op, target })
+    }
+}
+
+// Strips a trailing fd number that forms a whole word, like the `2` in `ls 2`
+fn take_fd_prefix(command: &mut String) -> Option<RawFd> {
+    let digits = command.len() - command.trim_end_matches(|c: char| c.is_ascii_digit()).len();
+    if digits == 0 {
+        return None;
+    }
+    let start = command.len() - digits;
+    if !command[..start].is_empty() && !command[..start].ends_with(char::is_whitespace) {
+        return None;
+    }
+    let fd = command[start..].parse().ok()?;
+    command.truncate(start);
+    Some(fd)
+}
+
+// Reads the target word, keeping its quoting for expansion
+fn read_target(chars: &mut Peekable<Chars<'_>>) -> Result<String, PipelineError> {
+    while chars.next_if(|c| c.is_whitespace()).is_some() {}
+
+    let mut target = String::new();
+    let mut quote: Option<char> = None;
+    while let Some(&c) = chars.peek() {
+        if quote.is_none() && (c.is_whitespace() || "|&;<>".contains(c)) {
+            break;
+        }
+        chars.next();
+        target.push(c);
+        match (quote, c) {
+            (Some(q), _) if c == q => quote = None,
+            (None, '\'') | (None, '"') => quote = Some(c),
+            (Some('\''), _) => {}
+            (_, '\\') => target.extend(chars.next()),
+            _ => {}
+        }
+    }
+
+    if quote.is_some() {
+        return Err(PipelineError::Parse("Unterminated quote".to_string()));
+    }
+    if target.is_empty() {
+        return Err(PipelineError::Parse(
+            "Missing file name after redirection".to_string(),
+        ));
+    }
+    Ok(target)
+}
+
+/// Where a redirected descriptor should point once the target is opened.
+pub enum Source {
+    File(OwnedFd),
+    Dup(RawFd),
+    Close,
+}
+
+pub struct Opened {
+    fd: RawFd,
+    source: Source,
+}
+
+impl Opened {
+    fn raw_source(&self) -> Option<RawFd> {
+        match &self.source {
+            Source::File(file) => Some(file.as_raw_fd()),
+            Source::Dup(fd) => Some(*fd),
+            Source::Close => None,
+        }
+    }
+}
+
+/// Expands the targets and opens the files of a command's redirections, in
+/// order. Errors are reported as messages since they only fail the command.
+pub(crate) fn open_all(
+    redirects: &[Redirect],
+    options: &ShellOptions,
+    last_status: i32,
+) -> Result<Vec<Opened>, String> {
+    redirects
+        .iter()
+        .map(|redirect| open(redirect, options, last_status))
+        .collect()
+}
+
+fn open(redirect: &Redirect, options: &ShellOptions, last_status: i32) -> Result<Opened, String> {
+    let words = expand_words(&redirect.target, options, last_status).map_err(|e| e.to_string())?;
+    let [target] = words.as_slice() else {
+        return Err(format!("{}: ambiguous redirect", redirect.target));
+    };
+
+    let source = match redirect.op {
+        RedirectOp::DupOut | RedirectOp::DupIn => match target.as_str() {
+            "-" => Source::Close,
+            fd => Source::Dup(
+                fd.parse()
+                    .map_err(|_| format!("{}: ambiguous redirect", target))?,
+            ),
+        },
+        op => Source::File(open_file(target, op, options)?),
+    };
+    Ok(Opened {
+        fd: redirect.fd,
+        source,
+    })
+}
+
+fn open_file(path: &str, op: RedirectOp, options: &ShellOptions) -> Result<OwnedFd, String> {
+    let mut open = OpenOptions::new();
+    match op {
+        RedirectOp::Read => open.read(true),
+        RedirectOp::ReadWrite => open.read(true).write(true).create(true),
+        RedirectOp::Append => open.append(true).create(true),
+        RedirectOp::Write if options.noclobber() => return open_noclobber(path),
+        _ => open.write(true).create(true).truncate(true),
+    };
+    open.open(path)
+        .map(OwnedFd::from)
+        .map_err(|e| format!("{}: {}", path, e))
+}
+
+// `>` under `set -C`: the file must be new, checked by creating it rather
+// than beforehand so one made in between isn't overwritten. Devices such as
+// /dev/null aren't regular files, and are opened as they are.
+fn open_noclobber(path: &str) -> Result<OwnedFd, String> {
+    let error = |e: io::Error| format!("{}: {}", path, e);
+    let created = OpenOptions::new().write(true).create_new(true).open(path);
+    let file = match created {
+        Err(e) if e.kind() == io::ErrorKind::AlreadyExists => {
+            let file = OpenOptions::new().write(true).open(path).map_err(error)?;
+            if file.metadata().map_err(error)?.is_file() {
+                return Err(format!("{}: cannot overwrite existing file", path));
+            }
+            file
+        }
+        created => created.map_err(error)?,
+    };
+    Ok(OwnedFd::from(file))
+}
+
+/// Makes a child process apply the redirections before it execs.
+pub(crate) fn attach(cmd: &mut Command, opened: &[Opened]) {
+    let plan: Vec<(RawFd, Option<RawFd>)> = opened
+        .iter()
+        .map(|opened| (opened.fd, opened.raw_source()))
+        .collect();
+    if plan.is_empty() {
+        return;
+    }
+
+    unsafe {
+        cmd.pre_exec(move || {
+            for (fd, source) in &plan {
+                let result = match source {
+                    Some(source) if source == fd => libc::fcntl(*fd, libc::F_SETFD, 0),
+                    Some(source) => libc::dup2(*source, *fd),
+                    None => libc::close(*fd),
+                };
+                if result < 0 && source.is_some() {
+                    return Err(io::Error::last_os_error());
+                }
+            }
+            Ok(())
+        });
+    }
+}
+
+/// The descriptors replaced by `apply`, to put back with `restore`.
+pub struct Saved {
+    fds: Vec<(RawFd, Option<RawFd>)>,
+}
+
+/// Applies redirections to the shell's own descriptors. With `permanent`
+/// (as for `exec`) nothing is saved and the change outlives the command.
+pub(crate) fn apply(opened: Vec<Opened>, permanent: bool) -> io::Result<Saved> {
+    let _ = io::stdout().flush();
+    let mut saved = Saved { fds: Vec::new() };
+
+    for redirect in opened {
+        // The file was opened on the target number itself because that was
+        // free; keep it open past the `OwnedFd` and close it on restore
+        if let Source::File(file) = &redirect.source {
+            if file.as_raw_fd() == redirect.fd {
+                if let Source::File(file) = redirect.source {
+                    let fd = file.into_raw_fd();
+                    unsafe { libc::fcntl(fd, libc::F_SETFD, 0) };
+                }
+                if !permanent {
+                    saved.fds.push((redirect.fd, None));
+                }
+                continue;
+            }
+        }
+
+        if !permanent {
+            let copy = unsafe { libc::fcntl(redirect.fd, libc::F_DUPFD_CLOEXEC, 10) };
+            saved.fds.push((redirect.fd, (copy >= 0).then_some(copy)));
+        }
+
+        let result = match redirect.raw_source() {
+            Some(source) => unsafe { libc::dup2(source, redirect.fd) },
+            None => unsafe { libc::close(redirect.fd) },
+        };
+        if result < 0 && redirect.raw_source().is_some() {
+            let err = io::Error::last_os_error();
+            restore(saved);
+            return Err(err);
+        }
+    }
+    Ok(saved)
+}
+
+pub(crate) fn restore(saved: Saved) {
+    let _ = io::stdout().flush();
+    for (fd, copy) in saved.fds.into_iter().rev() {
+        unsafe {
+            match copy {
+                Some(copy) => {
+                    libc::dup2(copy, fd);
+                    libc::close(copy);
+                }
+                None => {
+                    libc::close(fd);
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(input: &str) -> (String, Redirect) {
+        let (command, rest) = input.split_at(input.find(['<', '>']).unwrap());
+        let mut command = command.to_string();
+        let mut chars = rest.chars().peekable();
+        let first = chars.next().unwrap();
+        let redirect = Redirect::parse(first, &mut command, &mut chars).unwrap();
+        (command, redirect)
+    }
+
+    #[test]
+    fn test_parse_fd_prefix() {
+        let (command, redirect) = parse("ls 2>/dev/null");
+        assert_eq!(command, "ls ");
+        assert_eq!(redirect.fd, 2);
+        assert_eq!(redirect.op, RedirectOp::Write);
+        assert_eq!(redirect.target, "/dev/null");
+
+        let (command, redirect) = parse("echo a2>> 'my log'");
+        assert_eq!(command, "echo a2");
+        assert_eq!((redirect.fd, redirect.op), (1, RedirectOp::Append));
+        assert_eq!(redirect.target, "'my log'");
+    }
+
+    #[test]
+    fn test_parse_operators() {
+        let (_, redirect) = parse("exec 2>&1");
+        assert_eq!((redirect.fd, redirect.op), (2, RedirectOp::DupOut));
+        assert_eq!(redirect.target, "1");
+
+        let (_, redirect) = parse("cat <input");
+        assert_eq!((redirect.fd, redirect.op), (0, RedirectOp::Read));
+    }
+
+    #[test]
+    fn test_missing_target() {
+        let mut command = "echo".to_string();
+        let mut chars = " ;".chars().peekable();
+        assert!(Redirect::parse('>', &mut command, &mut chars).is_err());
+    }
+
+    #[test]
+    fn test_open_noclobber() {
+        let path = std::env::temp_dir().join("aorta_redirect_noclobber");
+        std::fs::write(&path, "keep").unwrap();
+        let mut options = ShellOptions::new();
+        options.set("noclobber", true).unwrap();
+
+        let redirect = Redirect {
+            fd: 1,
+            op: RedirectOp::Write,
+            target: path.display().to_string(),
+        };
+        assert!(open_all(std::slice::from_ref(&redirect), &options, 0).is_err());
+        assert_eq!(std::fs::read_to_string(&path).unwrap(), "keep");
+
+        let device = Redirect {
+            target: "/dev/null".to_string(),
+            ..redirect.clone()
+        };
+        assert!(open_all(&[device], &options, 0).is_ok());
+        std::fs::remove_file(&path).unwrap();
+        assert!(open_all(std::slice::from_ref(&redirect), &options, 0).is_ok());
+        assert!(open_all(std::slice::from_ref(&redirect), &options, 0).is_err());
+
+        let forced = Redirect {
+            op: RedirectOp::Clobber,
+            ..redirect
+        };
+        assert!(open_all(&[forced], &options, 0).is_ok());
+        std::fs::remove_file(path).unwrap();
+    }
+}
@@ -0,0 +1,68 @@
+use std::path::PathBuf;
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::Duration;
+
+use rustyline::ExternalPrinter;
+
+use super::git::GitCache;
+use super::prompt::{PromptContext, Templates};
+use crate::input::ShellCompleter;
+
+// Printed through the editor to make it repaint: it clears the prompt,
+// prints this and a newline, then draws the prompt again. Moving up first
+// leaves the prompt on the row it was on.
+const REPAINT: &str = "\x1b[A\n";
+
+type Printer = Arc<Mutex<Box<dyn ExternalPrinter + Send>>>;
+
+/// Finishes prompts drawn with placeholders for slow segments: a worker
+/// thread computes the segments within their deadline, renders the prompt
+/// again and has the editor repaint it if it's still waiting for input.
+#[derive(Clone)]
+pub(crate) struct PromptRefresh {
+    printer: Printer,
+    completer: ShellCompleter,
+}
+
+impl PromptRefresh {
+    pub fn new(printer: Box<dyn ExternalPrinter + Send>, completer: ShellCompleter) -> Self {
+        Self {
+            printer: Arc::new(Mutex::new(printer)),
+            completer,
+        }
+    }
+
+    /// Reads the git status for `dir` in the background, giving `git
+    /// status` up to `timeout`, then redraws prompt `generation`.
+    pub fn git_status(
+        &self,
+        generation: u64,
+        templates: Templates,
+        mut context: PromptContext,
+        git: GitCache,
+        timeout: Duration,
+    ) {
+        let refresh = self.clone();
+        let dir = PathBuf::from(&context.cwd);
+        thread::spawn(move || {
+            context.git = git.status(&dir, timeout);
+            refresh.redraw(generation, &templates, &context);
+        });
+    }
+
+    fn redraw(&self, generation: u64, templates: &Templates, context: &PromptContext) {
+        let (left, right) = templates.render(context);
+        let updated = self.completer.update_prompt(
+            generation,
+            (&left.visible, &left.text),
+            (&right.visible, &right.text),
+        );
+        if !updated {
+            return;
+        }
+        if let Ok(mut printer) = self.printer.lock() {
+            let _ = printer.print(REPAINT.to_string());
+        }
+    }
+}
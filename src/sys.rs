@@ -0,0 +1,81 @@
+//! Safe wrappers for the libc calls that more than one part of the shell
+//! makes.
+
+use std::ffi::{CStr, CString};
+use std::mem::MaybeUninit;
+
+/// The machine's host name, or an empty string if it can't be read.
+pub fn host_name() -> String {
+    let mut buffer = [0u8; 256];
+    let result = unsafe { libc::gethostname(buffer.as_mut_ptr().cast(), buffer.len()) };
+    if result != 0 {
+        return String::new();
+    }
+    CStr::from_bytes_until_nul(&buffer)
+        .map(|name| name.to_string_lossy().into_owned())
+        .unwrap_or_default()
+}
+
+/// `timestamp`, in seconds since the epoch, broken down in the local time
+/// zone.
+pub fn local_time(timestamp: u64) -> Option<libc::tm> {
+    let time = libc::time_t::try_from(timestamp).ok()?;
+    let mut tm = MaybeUninit::<libc::tm>::zeroed();
+    // SAFETY: localtime_r fills in the struct it's given, and returns null
+    // on failure
+    unsafe {
+        if libc::localtime_r(&time, tm.as_mut_ptr()).is_null() {
+            return None;
+        }
+        Some(tm.assume_init())
+    }
+}
+
+/// `timestamp` in the local time zone, laid out by a strftime `format`
+/// such as `%F %T`.
+pub fn format_time(timestamp: u64, format: &str) -> String {
+    let (Some(tm), Ok(format)) = (local_time(timestamp), CString::new(format)) else {
+        return timestamp.to_string();
+    };
+    let mut buffer = [0u8; 256];
+    // SAFETY: the buffer's length is passed along, and strftime writes at
+    // most that many bytes, returning how many
+    let written = unsafe {
+        libc::strftime(
+            buffer.as_mut_ptr().cast(),
+            buffer.len(),
+            format.as_ptr(),
+            &tm,
+        )
+    };
+    String::from_utf8_lossy(&buffer[..written]).into_owned()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_format_time() {
+        // 2023-11-14 22:13:20 UTC, whatever the local zone makes of it
+        let timestamp = 1_700_000_000;
+        let tm = local_time(timestamp).unwrap();
+        assert_eq!(
+            format_time(timestamp, "%F %T"),
+            format!(
+                "{:04}-{:02}-{:02} {:02}:{:02}:{:02}",
+                tm.tm_year + 1900,
+                tm.tm_mon + 1,
+                tm.tm_mday,
+                tm.tm_hour,
+                tm.tm_min,
+                tm.tm_sec
+            )
+        );
+        assert_eq!(format_time(timestamp, "%s"), "1700000000");
+        assert_eq!(format_time(timestamp, "100%% [%%s]"), "100% [%s]");
+        assert_eq!(format_time(timestamp, ""), "");
+        // Formats strftime can't take fall back to the raw timestamp
+        assert_eq!(format_time(timestamp, "bad\0format"), "1700000000");
+    }
+}